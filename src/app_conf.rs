@@ -0,0 +1,119 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::{ROOT_PATH_CONF, ROOT_PATH_BACKUP};
+
+/// 获取 installer 的配置文件夹，即 `conf/<app_run_port>`。
+///
+/// 在此文件夹中存放 `application-prod.yml` 等不能从 Block Lang 平台下载的配置文件，
+/// 运行 APP 时通过 `--spring.config.additional-location` 传给 Spring Boot。
+pub fn get_conf_dir(app_run_port: u32) -> PathBuf {
+    Path::new(ROOT_PATH_CONF).join(app_run_port.to_string())
+}
+
+/// 创建 installer 的配置文件夹，如果已存在则不做任何操作。
+///
+/// 因为配置文件中可能存放密码等敏感信息，所以在 linux 下只允许当前用户访问此文件夹。
+pub fn create_conf_dir(app_run_port: u32) -> io::Result<PathBuf> {
+    create_conf_dir_in(Path::new(""), app_run_port)
+}
+
+fn create_conf_dir_in(root: &Path, app_run_port: u32) -> io::Result<PathBuf> {
+    let conf_dir = root.join(get_conf_dir(app_run_port));
+    if conf_dir.exists() {
+        return Ok(conf_dir);
+    }
+
+    fs::create_dir_all(&conf_dir)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&conf_dir, fs::Permissions::from_mode(0o700))?;
+    }
+
+    Ok(conf_dir)
+}
+
+/// 获取传给 Spring Boot 的配置文件位置参数，如果配置文件夹不存在则返回 `None`。
+///
+/// 使用 installer 根目录下配置文件夹的绝对路径，因此不受 APP 工作目录的影响。
+/// 注意，Spring Boot 要求文件夹必须以 `/` 结尾。
+pub fn get_additional_location_arg(app_run_port: u32) -> Option<String> {
+    get_additional_location_arg_in(&env::current_dir().ok()?, app_run_port)
+}
+
+fn get_additional_location_arg_in(root: &Path, app_run_port: u32) -> Option<String> {
+    let conf_dir = root.join(get_conf_dir(app_run_port));
+    if !conf_dir.is_dir() {
+        return None;
+    }
+
+    // Spring Boot 的 file: 路径统一使用 `/` 分隔
+    let conf_dir = conf_dir.to_string_lossy().replace('\\', "/");
+    Some(format!("--spring.config.additional-location=file:{}/", conf_dir.trim_end_matches('/')))
+}
+
+/// 将 installer 的配置文件夹移到 `backup/conf/<app_run_port>-<timestamp>` 中，并返回备份的路径。
+///
+/// 如果配置文件夹不存在，则返回 `None`。
+pub fn backup(app_run_port: u32) -> io::Result<Option<PathBuf>> {
+    backup_in(Path::new(""), app_run_port)
+}
+
+fn backup_in(root: &Path, app_run_port: u32) -> io::Result<Option<PathBuf>> {
+    let conf_dir = root.join(get_conf_dir(app_run_port));
+    if !conf_dir.exists() {
+        return Ok(None);
+    }
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    let backup_parent = root.join(ROOT_PATH_BACKUP).join(ROOT_PATH_CONF);
+    let backup_dir = backup_parent.join(format!("{}-{}", app_run_port, timestamp));
+
+    fs::create_dir_all(&backup_parent)?;
+    fs::rename(&conf_dir, &backup_dir)?;
+
+    Ok(Some(backup_dir))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::fs::{self, File};
+    use std::io::prelude::*;
+    use tempfile::TempDir;
+    use super::{backup_in, create_conf_dir_in, get_additional_location_arg_in};
+
+    #[test]
+    fn get_additional_location_arg_not_exist() -> Result<(), Box<dyn std::error::Error>> {
+        let root = TempDir::new()?;
+        assert_eq!(None, get_additional_location_arg_in(root.path(), 65001));
+        Ok(())
+    }
+
+    #[test]
+    fn create_conf_dir_and_backup_success() -> Result<(), Box<dyn std::error::Error>> {
+        let root = TempDir::new()?;
+        let conf_dir = create_conf_dir_in(root.path(), 65002)?;
+        File::create(conf_dir.join("application-prod.yml"))?.write_all(b"password: 1")?;
+
+        // 使用绝对路径，不依赖 APP 的工作目录
+        let expected_dir = root.path().join("conf").join("65002").to_string_lossy().replace('\\', "/");
+        assert_eq!(Some(format!("--spring.config.additional-location=file:{}/", expected_dir)),
+            get_additional_location_arg_in(root.path(), 65002));
+
+        let backup_dir = backup_in(root.path(), 65002)?.unwrap();
+        assert!(!conf_dir.exists());
+        assert!(backup_dir.starts_with(root.path().join("backup").join("conf")));
+        assert_eq!("password: 1", fs::read_to_string(backup_dir.join("application-prod.yml"))?);
+        assert_eq!(None, backup_in(root.path(), 65002)?);
+        Ok(())
+    }
+}
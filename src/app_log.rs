@@ -0,0 +1,280 @@
+use std::collections::VecDeque;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, PipeWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+use crate::config::{ROOT_PATH_LOGS, MAX_LOG_FILE_SIZE, MAX_LOG_FILES};
+
+/// 跟踪日志文件时，检查日志文件是否有新内容的时间间隔
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(500);
+
+/// 获取 APP 日志文件的路径，即 `logs/<app_name>/<app_run_port>.log`
+pub fn get_log_path(app_name: &str, app_run_port: u32) -> PathBuf {
+    Path::new(ROOT_PATH_LOGS)
+        .join(app_name)
+        .join(format!("{}.log", app_run_port))
+}
+
+/// 获取蓝绿部署时 TCP 代理日志文件的路径，即 `logs/<app_name>/<app_run_port>.proxy.log`
+pub fn get_proxy_log_path(app_name: &str, app_run_port: u32) -> PathBuf {
+    Path::new(ROOT_PATH_LOGS)
+        .join(app_name)
+        .join(format!("{}.proxy.log", app_run_port))
+}
+
+/// 打开 APP 的日志，返回的写入端用于接收 APP 的标准输出和标准错误，详见 `spawn_writer`。
+pub fn open(app_name: &str, app_run_port: u32) -> io::Result<PipeWriter> {
+    spawn_writer(&get_log_path(app_name, app_run_port))
+}
+
+/// 打开 TCP 代理的日志
+pub fn open_proxy(app_name: &str, app_run_port: u32) -> io::Result<PipeWriter> {
+    spawn_writer(&get_proxy_log_path(app_name, app_run_port))
+}
+
+/// 在后台启动日志进程，即执行 `blocklang-installer log-writer --path <log_path>`，并返回日志进程标准输入的写入端。
+///
+/// 日志进程负责写入日志文件，并在日志文件过大时轮转，因此不论 APP 是否由守护进程启动，日志文件都不会无限增长。
+/// 所有写入端关闭，即 APP 退出后，日志进程随之退出。
+fn spawn_writer(log_path: &Path) -> io::Result<PipeWriter> {
+    let (reader, writer) = io::pipe()?;
+    let mut child = Command::new(std::env::current_exe()?)
+        .arg("log-writer")
+        .arg("--path")
+        .arg(log_path)
+        .stdin(reader)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    // 日志进程退出后回收进程，以免在守护进程中留下僵尸进程
+    thread::spawn(move || child.wait());
+
+    Ok(writer)
+}
+
+/// 将 `input` 中的内容写入日志文件，直到 `input` 结束，由日志进程调用。
+///
+/// 日志文件超过 `MAX_LOG_FILE_SIZE` 后轮转，最多保留 `MAX_LOG_FILES` 个历史日志文件。
+pub fn write(input: impl Read, log_path: &Path) -> io::Result<()> {
+    write_rotating(input, log_path, MAX_LOG_FILE_SIZE, MAX_LOG_FILES)
+}
+
+fn write_rotating(mut input: impl Read, log_path: &Path, max_size: u64, max_files: usize) -> io::Result<()> {
+    if let Some(parent) = log_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    rotate_if_larger_than(log_path, max_size, max_files)?;
+    let mut file = open_append(log_path)?;
+    let mut size = file.metadata()?.len();
+    let mut buf = [0; 8 * 1024];
+
+    loop {
+        let len = match input.read(&mut buf) {
+            Ok(0) => return Ok(()),
+            Ok(len) => len,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        file.write_all(&buf[..len])?;
+        size += len as u64;
+
+        if size >= max_size {
+            // 只有日志进程写日志文件，所以关闭日志文件后就可以直接重命名，不会丢失日志
+            drop(file);
+            rotate_if_larger_than(log_path, max_size, max_files)?;
+            file = open_append(log_path)?;
+            size = file.metadata()?.len();
+        }
+    }
+}
+
+fn open_append(log_path: &Path) -> io::Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_path)
+}
+
+/// 如果日志文件超过 `max_size`，则轮转日志文件，并返回 `true`。
+///
+/// 轮转时，将 `<port>.log.1` 重命名为 `<port>.log.2`，依此类推，最多保留 `max_files` 个历史日志文件；
+/// 然后将 `<port>.log` 重命名为 `<port>.log.1`。
+fn rotate_if_larger_than(log_path: &Path, max_size: u64, max_files: usize) -> io::Result<bool> {
+    match fs::metadata(log_path) {
+        Ok(metadata) if metadata.len() >= max_size => {},
+        _ => return Ok(false),
+    }
+
+    // 删除最早的日志文件，注意 Windows 下重命名时目标文件不能已存在
+    let oldest = get_rotated_log_path(log_path, max_files);
+    if oldest.exists() {
+        fs::remove_file(oldest)?;
+    }
+    for index in (1..max_files).rev() {
+        let from = get_rotated_log_path(log_path, index);
+        if from.exists() {
+            fs::rename(from, get_rotated_log_path(log_path, index + 1))?;
+        }
+    }
+
+    fs::rename(log_path, get_rotated_log_path(log_path, 1))?;
+
+    Ok(true)
+}
+
+fn get_rotated_log_path(log_path: &Path, index: usize) -> PathBuf {
+    let mut file_name = log_path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(format!(".{}", index));
+    log_path.with_file_name(file_name)
+}
+
+/// 读取日志文件中的最后 `lines` 行
+pub fn tail(log_path: &Path, lines: usize) -> io::Result<Vec<String>> {
+    let mut reader = BufReader::new(File::open(log_path)?);
+    let mut result = VecDeque::with_capacity(lines);
+    let mut buf = Vec::new();
+
+    loop {
+        buf.clear();
+        if reader.read_until(b'\n', &mut buf)? == 0 {
+            break;
+        }
+        if lines == 0 {
+            continue;
+        }
+        if result.len() == lines {
+            result.pop_front();
+        }
+        // APP 输出的日志不一定是 UTF-8 编码，如 Windows 下的 GBK
+        let line = String::from_utf8_lossy(&buf);
+        result.push_back(line.trim_end_matches(['\n', '\r']).to_string());
+    }
+
+    Ok(result.into_iter().collect())
+}
+
+/// 持续输出日志文件中新增的内容，按 CTRL + C 退出。
+///
+/// 如果日志文件被轮转，则输出完轮转前的剩余内容后，从头开始输出新的日志文件。
+pub fn follow(log_path: &Path) -> io::Result<()> {
+    let mut file = File::open(log_path)?;
+    let mut position = file.seek(SeekFrom::End(0))?;
+
+    loop {
+        thread::sleep(FOLLOW_INTERVAL);
+
+        // 轮转时会重命名日志文件，所以要根据路径获取当前日志文件的大小
+        let len = match fs::metadata(log_path) {
+            Ok(metadata) => metadata.len(),
+            Err(_) => continue,
+        };
+        if len < position {
+            print_from(&mut file, position)?;
+            file = File::open(log_path)?;
+            position = 0;
+        }
+        if len == position {
+            continue;
+        }
+
+        position += print_from(&mut file, position)?;
+    }
+}
+
+/// 输出 `file` 中从 `position` 开始的内容，并返回输出的字节数
+fn print_from(file: &mut File, position: u64) -> io::Result<u64> {
+    let mut buf = Vec::new();
+    file.seek(SeekFrom::Start(position))?;
+    let len = file.read_to_end(&mut buf)?;
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    out.write_all(String::from_utf8_lossy(&buf).as_bytes())?;
+    out.flush()?;
+    Ok(len as u64)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::fs::{self, File};
+    use std::io::prelude::*;
+    use std::path::Path;
+    use super::{get_log_path, rotate_if_larger_than, tail, write_rotating};
+
+    #[test]
+    fn get_log_path_success() {
+        assert_eq!(Path::new("logs").join("app").join("80.log"), get_log_path("app", 80));
+    }
+
+    #[test]
+    fn tail_success() -> Result<(), Box<dyn std::error::Error>> {
+        let file_name = "app_log_tail_success.log";
+        File::create(file_name)?.write_all(b"1\r\n2\n3\n4")?;
+
+        assert_eq!(vec!["3", "4"], tail(Path::new(file_name), 2)?);
+        assert_eq!(vec!["1", "2", "3", "4"], tail(Path::new(file_name), 10)?);
+        assert!(tail(Path::new(file_name), 0)?.is_empty());
+
+        fs::remove_file(file_name)?;
+        Ok(())
+    }
+
+    #[test]
+    fn rotate_if_larger_than_success() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = "app_log_rotate_if_larger_than_success";
+        fs::create_dir_all(dir)?;
+        let log_path = Path::new(dir).join("80.log");
+
+        File::create(&log_path)?.write_all(b"a")?;
+        // 没有超过最大值时，不轮转
+        assert!(!rotate_if_larger_than(&log_path, 2, 2)?);
+
+        File::create(&log_path)?.write_all(b"first")?;
+        assert!(rotate_if_larger_than(&log_path, 2, 2)?);
+        assert!(!log_path.exists());
+        assert_eq!("first", fs::read_to_string(Path::new(dir).join("80.log.1"))?);
+
+        File::create(&log_path)?.write_all(b"second")?;
+        assert!(rotate_if_larger_than(&log_path, 2, 2)?);
+        assert_eq!("second", fs::read_to_string(Path::new(dir).join("80.log.1"))?);
+        assert_eq!("first", fs::read_to_string(Path::new(dir).join("80.log.2"))?);
+
+        // 最多保留 2 个历史日志文件
+        File::create(&log_path)?.write_all(b"third")?;
+        assert!(rotate_if_larger_than(&log_path, 2, 2)?);
+        assert_eq!("third", fs::read_to_string(Path::new(dir).join("80.log.1"))?);
+        assert_eq!("second", fs::read_to_string(Path::new(dir).join("80.log.2"))?);
+        assert!(!Path::new(dir).join("80.log.3").exists());
+
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn write_rotating_success() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = "app_log_write_rotating_success";
+        fs::create_dir_all(dir)?;
+        let log_path = Path::new(dir).join("80.log");
+        File::create(&log_path)?.write_all(b"0\n")?;
+
+        // 每次最多读取 8 KB，超过 10 KB 后轮转
+        let input: Vec<u8> = (1..=3000).flat_map(|i| format!("{}\n", i).into_bytes()).collect();
+        write_rotating(input.as_slice(), &log_path, 10 * 1024, 5)?;
+
+        // 轮转时不会丢失日志
+        let mut content = fs::read_to_string(Path::new(dir).join("80.log.1"))?;
+        content.push_str(&fs::read_to_string(&log_path)?);
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(3001, lines.len());
+        assert_eq!("0", lines[0]);
+        assert_eq!("3000", lines[3000]);
+        assert!(!Path::new(dir).join("80.log.2").exists());
+
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
@@ -1,135 +1,175 @@
 use std::path::{Path, PathBuf};
-use std::fs::{self, File};
-use std::time::Instant;
-use std::io::{self, Write};
+use std::fs;
+use std::thread;
+use std::time::{Duration, Instant};
+use std::sync::PoisonError;
+use std::io;
+use std::process::Child;
 use version_compare::Version;
+use serde_derive::Serialize;
 
 use crate::config;
-use crate::installer_config::{Installer, InstallerConfig};
+use crate::error::InstallerError;
+use crate::installer_config::{Installer, InstallerConfig, HealthCheck, Selector};
 use crate::http::client;
 use crate::jar;
-use crate::util::{zip, process};
+use crate::app_log;
+use crate::app_conf;
+use crate::health_check;
+use crate::proxy;
+use crate::output::{self, AppRecord};
+use crate::{progress, progressln};
+use crate::run_record::{PortLock, RunRecord};
+use crate::util::{zip, process, checksum};
+use crate::util::parallel::{self, PathLocks};
+use crate::util::process::PortOwner;
 use prettytable::{Table, Row, Cell, row, cell};
 use indicatif::HumanDuration;
 
+/// 等待代理开始监听端口的最长时间
+const PROXY_START_TIMEOUT: Duration = Duration::from_secs(10);
+/// 重启 APP 时，关闭进程后等待端口释放的最长时间
+const PORT_RELEASE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 并发处理多个 installer 时，防止同时下载或解压同一个 JDK 或 jar
+static DOWNLOAD_LOCKS: PathLocks = PathLocks::new();
+
 /// 注册命令
 pub fn register_installer(url: &str,
     registration_token: &str,
-    app_run_port: u32) -> Result<(), Box<dyn std::error::Error>> {
+    app_run_port: u32) -> Result<(), InstallerError> {
     
-    let mut installer_config = InstallerConfig::new();
+    let installer_config = InstallerConfig::new()?;
 
     let server_token = &installer_config.get_data().server_token;
     // 向 Block Lang 平台发送注册请求
     let installer_info = client::register_installer(url, registration_token, app_run_port, server_token)?;
     // 添加安装信息
-    installer_config.add(installer_info);
+    InstallerConfig::modify(|installer_config| installer_config.add(installer_info))?;
+
+    // 创建存放 Spring Boot 配置文件的文件夹
+    let conf_dir = app_conf::create_conf_dir(app_run_port)?;
+    progressln!("> [INFO]: 可将 application-prod.yml 等配置文件放在 {} 文件夹中", conf_dir.display());
 
     Ok(())
 }
 
-pub fn list_installers() -> Result<(), Box<dyn std::error::Error>> {
-    println!("开始查找已注册的安装器");
+pub fn list_installers() -> Result<(), InstallerError> {
+    progressln!("开始查找已注册的安装器");
 
-    let installer_config = InstallerConfig::new();
+    let installer_config = InstallerConfig::new()?;
 
     let installers = &installer_config.get_data().installers;
-    if installers.is_empty() {
-        println!("> [INFO]: 共找到 0 个 installer，请使用 `blocklang-installer register` 命令注册。");
+    if output::is_structured() {
+        let records: Vec<AppRecord> = installers.iter().map(AppRecord::new).collect();
+        output::print_records(&records)?;
+    } else if installers.is_empty() {
+        progressln!("> [INFO]: 共找到 0 个 installer，请使用 `blocklang-installer register` 命令注册。");
     } else {
-        println!("> [INFO]: 共找到 {} 个 installer。", installers.len());
+        progressln!("> [INFO]: 共找到 {} 个 installer。", installers.len());
         print_installers(&installers);
     }
 
     Ok(())
 }
 
-pub fn unregister_single_installer(app_run_port: u32) -> Result<(), Box<dyn std::error::Error>> {
-    println!("开始注销 {} 端口上的 installer", app_run_port);
-    let installer_config = InstallerConfig::new();
+pub fn unregister_single_installer(app_run_port: u32) -> Result<(), InstallerError> {
+    progressln!("开始注销 {} 端口上的 installer", app_run_port);
+    let installer_config = InstallerConfig::new()?;
 
     // 注意：不能关闭未注册的端口，防止误关安装在应用服务器上的其他应用。
     if let Some(installer) = installer_config.get_by_port(app_run_port) {
-        println!("> [INFO]: 端口号 {} 上注册的 installer 信息如下：", app_run_port);
+        progressln!("> [INFO]: 端口号 {} 上注册的 installer 信息如下：", app_run_port);
         
         print_one_installer(installer);
 
         // 询问用户是否要注销
-        println!("> [WARN]: 注销之后，项目将无法访问，确定要注销吗？输入 Y 确定注销，输入 N 退出(默认为 N)：");
+        progressln!("> [WARN]: 注销之后，项目将无法访问，确定要注销吗？输入 Y 确定注销，输入 N 退出(默认为 N)：");
 
         if !confirm_to_continue() {
-            println!("> [INFO]: 已退出");
+            progressln!("> [INFO]: 已退出");
             return Ok(());
         }
 
         // 向 Block Lang 平台注销 installer
         unregister_installer(&installer)?;
 
-        println!("注销完成！");
+        progressln!("注销完成！");
     } else {
-        println!("> [WARN]: 端口 {} 上未注册 installer，可执行 `blocklang-installer --list` 命令查看已注册的 installer", app_run_port);
+        progressln!("> [WARN]: 端口 {} 上未注册 installer，可执行 `blocklang-installer --list` 命令查看已注册的 installer", app_run_port);
     }
     
     Ok(())
 }
 
-pub fn unregister_all_installers() -> Result<(), Box<dyn std::error::Error>> {
-    println!("开始注销所有 installer");
+pub fn unregister_all_installers() -> Result<(), InstallerError> {
+    progressln!("开始注销所有 installer");
 
-    let mut installer_config = InstallerConfig::new();
+    let installer_config = InstallerConfig::new()?;
 
     let installers = &installer_config.get_data().installers;
     if installers.is_empty() {
-        println!("> [INFO]: 共找到 0 个 installer");
+        progressln!("> [INFO]: 共找到 0 个 installer");
         return Ok(());
     }
 
     // 展示所有注册的 installer
     let installer_len = installers.len();
-    println!("> [INFO]: 共找到 {} 个 installer。", installer_len);
+    progressln!("> [INFO]: 共找到 {} 个 installer。", installer_len);
     print_installers(&installers);
 
     // 向用户确认，是否要注销
-    println!("> [WARN]: 注销之后，项目将无法访问，确定要全部注销吗？输入 Y 确定注销，输入 N 退出(默认为 N)：");
+    progressln!("> [WARN]: 注销之后，项目将无法访问，确定要全部注销吗？输入 Y 确定注销，输入 N 退出(默认为 N)：");
     if !confirm_to_continue() {
-        println!("> [INFO]: 已退出");
+        progressln!("> [INFO]: 已退出");
         return Ok(());
     }
 
-    let mut num = 1;
-    installer_config.remove_all(|installer| {
-        println!();
-        println!("===== [{}/{}] 开始注销 {} 端口上的 installer =====", num, installer_len, installer.app_run_port);
-        num += 1;
+    // 注销成功后，unregister_installer 会在持有文件锁时从配置文件中删除此 installer，
+    // 注销失败的 installer 依然保留在配置文件中
+    for (index, installer) in installers.iter().enumerate() {
+        progressln!();
+        progressln!("===== [{}/{}] 开始注销 {} 端口上的 installer =====", index + 1, installer_len, installer.app_run_port);
         // 向 Block Lang 平台注销 installer
-        println!("开始向 Block Lang 平台注销 installer");
+        progressln!("开始向 Block Lang 平台注销 installer");
 
-        unregister_installer(&installer).is_ok()
-    });
+        if let Err(e) = unregister_installer(installer) {
+            progressln!("> [ERROR]: 注销 {} 端口上的 installer 失败，{}", installer.app_run_port, e);
+        }
+    }
 
     Ok(())
 }
 
-fn unregister_installer(installer: &Installer) -> Result<(), Box<dyn std::error::Error>> {
+fn unregister_installer(installer: &Installer) -> Result<(), InstallerError> {
     // 向 Block Lang 平台注销 installer
-    println!("[1/3] 向 Block Lang 平台注销 installer");
-    if client::unregister_installer(&installer.url, &installer.installer_token).is_ok() {
-        println!("> [INFO]: 完成");
-    }else {
-        return Err(Box::from("注销失败"));
-    }
+    progressln!("[1/4] 向 Block Lang 平台注销 installer");
+    client::unregister_installer(&installer.url, &installer.installer_token)?;
+    progressln!("> [INFO]: 完成");
 
-    println!("[2/3] 关闭端口 {}", installer.app_run_port);
+    progressln!("[2/4] 关闭端口 {}", installer.app_run_port);
     // 如果 APP 处于运行状态，则关闭该 APP，此逻辑在 stop_jar 函数中
-    stop_jar(installer.app_run_port);
+    // 如果端口上运行的不是 installer 启动的进程，则不关闭该进程，依然注销 installer
+    if let Some((port, pid)) = find_foreign_process(installer) {
+        progressln!("> [WARN]: 端口 {} 上的进程 {} 不是 installer 启动的，没有关闭此进程", port, pid);
+    } else if let Err(e) = stop_jar(installer, false) {
+        progressln!("> [WARN]: 关闭端口 {} 失败：{}，请手动关闭此端口上的进程", installer.app_run_port, e);
+    }
+
+    // 备份 Spring Boot 配置文件，防止误删其中的密码等信息
+    // 此时已在 Block Lang 平台注销，备份失败时不再中断注销，配置文件依然保留在原文件夹中
+    progressln!("[3/4] 备份 Spring Boot 配置文件");
+    match app_conf::backup(installer.app_run_port) {
+        Ok(Some(backup_dir)) => progressln!("> [INFO]: 已备份到 {}", backup_dir.display()),
+        Ok(None) => progressln!("> [INFO]: 没有配置文件"),
+        Err(e) => progressln!("> [WARN]: 备份失败：{}，配置文件依然保留在 {} 中，请手动备份",
+            e, app_conf::get_conf_dir(installer.app_run_port).display()),
+    }
 
     // 在配置文件中删除此 installer 的配置信息
-    println!("[3/3] 从配置文件中删除配置信息");
-    // 注意：因为 rustc 提示不可变借用了，不能再可变借用，只有暂时重新 new 一个对象了。
-    // TODO: 有没有更好的办法，让只需要 new 一次？
-    let mut installer_config = InstallerConfig::new();
-    installer_config.remove_by_installer_token(&installer.installer_token);
-    println!("> [INFO]: 完成");
+    progressln!("[4/4] 从配置文件中删除配置信息");
+    InstallerConfig::modify(|installer_config| installer_config.remove_by_installer_token(&installer.installer_token))?;
+    progressln!("> [INFO]: 完成");
     Ok(())
 }
 
@@ -146,7 +186,7 @@ fn confirm_to_continue() -> bool {
 fn print_one_installer(installer: &Installer) {
     let mut table = Table::new();
     // 标题行
-    table.add_row(row!["端口号", "Installer Token", "URL", "项目名", "版本号"]);
+    table.add_row(row!["端口号", "Installer Token", "URL", "项目名", "版本号", "标签"]);
     // 数据行
     table.add_row(Row::new(vec![
         Cell::new(&installer.app_run_port.to_string()),
@@ -154,14 +194,38 @@ fn print_one_installer(installer: &Installer) {
         Cell::new(&installer.url),
         Cell::new(&installer.app_name),
         Cell::new(&installer.app_version),
+        Cell::new(&installer.tags.join(" ")),
     ]));
-    table.printstd();
+    print_progress_table(&table);
+}
+
+/// 输出 json 或 yaml 时，打印端口上的 APP 的记录，使用执行命令后的配置信息和进程信息，之后返回命令的执行结果 `result`。
+/// 
+/// 命令执行失败时依然打印记录，并在 `error` 中记录出错信息。
+fn print_app_record(app_run_port: u32, result: Result<(), InstallerError>) -> Result<(), InstallerError> {
+    if !output::is_structured() {
+        return result;
+    }
+
+    let installer_config = match InstallerConfig::new() {
+        Ok(installer_config) => installer_config,
+        Err(e) => return result.and(Err(e)),
+    };
+    let records: Vec<AppRecord> = installer_config.get_by_port(app_run_port)
+        .map(|installer| AppRecord {
+            error: result.as_ref().err().map(|e| e.to_string()),
+            ..AppRecord::new(installer)
+        })
+        .into_iter()
+        .collect();
+    let printed = output::print_records(&records);
+    result.and(printed)
 }
 
 fn print_installers(installers: &[Installer]) {
     let mut table = Table::new();
     // 标题行
-    table.add_row(row!["端口号", "Installer Token", "URL", "项目名", "版本号"]);
+    table.add_row(row!["端口号", "Installer Token", "URL", "项目名", "版本号", "标签"]);
     // 数据行
     installers.iter().for_each(|installer| {
         table.add_row(Row::new(vec![
@@ -170,9 +234,21 @@ fn print_installers(installers: &[Installer]) {
             Cell::new(&installer.url),
             Cell::new(&installer.app_name),
             Cell::new(&installer.app_version),
+            Cell::new(&installer.tags.join(" ")),
         ]));
     });
-    table.printstd();
+    print_progress_table(&table);
+}
+
+/// 打印执行过程中的表格，如注销前确认的 installer 信息。
+/// 
+/// 同 `progressln!`，输出 json 或 yaml 时打印到标准错误，保证标准输出中只有结构化数据。
+fn print_progress_table(table: &Table) {
+    if output::is_structured() {
+        let _ = table.print(&mut io::stderr());
+    } else {
+        table.printstd();
+    }
 }
 
 /// 启动命令，启动单个 APP
@@ -180,299 +256,1198 @@ fn print_installers(installers: &[Installer]) {
 /// 在启动时会使用 `installer_config.toml` 中的 `app_name` 和 `app_version` 等信息
 /// 在 `prod` 文件夹下检查 Spring boot jar 和 JDK 文件是否已存在，如果不存在则先下载。
 /// 下载并解压成功后，启动 Spring Boot jar。
-pub fn run_single_app(app_run_port: u32) -> Result<(), Box<dyn std::error::Error>> {
-    let installer_config = InstallerConfig::new();
+pub fn run_single_app(app_run_port: u32) -> Result<(), InstallerError> {
+    let installer_config = InstallerConfig::new()?;
 
-    match installer_config.get_by_port(app_run_port) {
-        Some(installer) => {
-            run_app(&installer)?;
-        },
-        None => {
-            println!("> [INFO]: 没有找到 installer。请先执行 `blocklang-installer register` 注册 installer");
-        }
+    let result = match installer_config.get_by_port(app_run_port) {
+        Some(installer) => run_app(installer),
+        None => Err(not_registered(app_run_port)),
     };
 
-    Ok(())
+    print_app_record(app_run_port, result)
 }
 
-/// 启动命令，启动所有注册的 APP
-pub fn run_all_apps() -> Result<(), Box<dyn std::error::Error>> {
-    println!("开始启动所有项目");
+/// 端口上未注册 installer 时提示先注册，并返回 `NotFound` 错误，以便脚本根据退出码发现没有注册的端口
+fn not_registered(app_run_port: u32) -> InstallerError {
+    progressln!("> [INFO]: 端口 {} 上未注册 installer。请先执行 `blocklang-installer register` 注册 installer", app_run_port);
+    InstallerError::NotFound(format!("端口 {} 上未注册 installer", app_run_port))
+}
 
-    let installer_config = InstallerConfig::new();
+/// 判断 `selector` 是否选中了 installer。
+/// 
+/// 没有注册任何 installer 时返回 `false`，由调用方提示先注册；
+/// 按项目或标签选择，但没有匹配的 installer 时返回 `NotFound`，以便脚本发现拼错的项目名或标签。
+fn ensure_selected(selector: &Selector, installers: &[&Installer]) -> Result<bool, InstallerError> {
+    if !installers.is_empty() {
+        return Ok(true);
+    }
 
-    let installers = &installer_config.get_data().installers;
-    if installers.is_empty() {
-        println!("> [INFO]: 没有找到 installer。请先执行 `blocklang-installer register` 注册 installer");
-        return Ok(());
+    match selector {
+        Selector::All => Ok(false),
+        _ => {
+            progressln!("> [INFO]: 没有找到匹配的 installer：{}，可执行 `blocklang-installer list` 命令查看已注册的 installer", selector);
+            Err(InstallerError::NotFound(format!("没有匹配的 installer：{}", selector)))
+        }
     }
+}
 
+/// 使用最多 `jobs` 个线程对多个 installer 执行 `f`，一个 installer 出错时不影响其他 installer。
+/// 
+/// 全部完成后打印每个 installer 的执行结果，如果有 installer 出错，则返回第一个错误。
+fn for_each_installer<F>(installers: &[&Installer],
+    jobs: usize,
+    action: &str,
+    f: F) -> Result<(), InstallerError> where F: Fn(&Installer) -> Result<(), InstallerError> + Sync {
     let installer_len = installers.len();
-    println!("> [INFO]: 共找到 {} 个 installer。", installer_len);
-
-    for (index, installer) in installers.iter().enumerate() {
-        println!();
-        println!("===== [{}/{}] 开始启动 {} 端口上的项目 {}-{} =====", 
+    let results = parallel::map(installers, jobs, |index, installer| {
+        // 同时处理多个 APP 时，各 APP 的执行过程会交错打印，在每行前添加端口号
+        if jobs > 1 {
+            output::set_progress_prefix(Some(format!("[{}] ", installer.app_run_port)));
+        }
+        progressln!();
+        progressln!("===== [{}/{}] 开始{} {} 端口上的项目 {}-{} =====", 
             index + 1, 
             installer_len, 
+            action,
             installer.app_run_port, 
             installer.app_name, 
             installer.app_version);
 
-        run_app(installer)?;
+        let result = f(installer);
+        if let Err(e) = &result {
+            progressln!("> [ERROR]: {} 端口上的项目{}失败，{}", installer.app_run_port, action, e);
+        }
+        output::set_progress_prefix(None);
+        result
+    })?;
+
+    print_results(installers, &results)?;
+
+    match results.into_iter().find_map(Result::err) {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// 打印每个 installer 的执行结果，输出 json 或 yaml 时打印 APP 的记录，并在 `error` 中记录出错信息
+fn print_results(installers: &[&Installer], results: &[Result<(), InstallerError>]) -> Result<(), InstallerError> {
+    // 使用执行命令后的配置信息，如升级后的版本号
+    let installer_config = InstallerConfig::new()?;
+    let current_installers: Vec<&Installer> = installers.iter()
+        .map(|installer| installer_config.get_by_port(installer.app_run_port).unwrap_or(installer))
+        .collect();
+
+    if output::is_structured() {
+        let records: Vec<AppRecord> = current_installers.iter()
+            .zip(results)
+            .map(|(installer, result)| AppRecord {
+                error: result.as_ref().err().map(|e| e.to_string()),
+                ..AppRecord::new(installer)
+            })
+            .collect();
+        return output::print_records(&records);
     }
 
+    let failed = results.iter().filter(|result| result.is_err()).count();
+    progressln!();
+    progressln!("> [INFO]: 共 {} 个项目，成功 {} 个，失败 {} 个", results.len(), results.len() - failed, failed);
+
+    let mut table = Table::new();
+    // 标题行
+    table.add_row(row!["端口号", "项目名", "版本号", "结果", "错误信息"]);
+    // 数据行
+    current_installers.iter().zip(results).for_each(|(installer, result)| {
+        table.add_row(Row::new(vec![
+            Cell::new(&installer.app_run_port.to_string()),
+            Cell::new(&installer.app_name),
+            Cell::new(&installer.app_version),
+            Cell::new(if result.is_ok() { "成功" } else { "失败" }),
+            Cell::new(&result.as_ref().err().map(|e| e.to_string()).unwrap_or_default()),
+        ]));
+    });
+    table.printstd();
     Ok(())
 }
 
-fn run_app(installer: &Installer) -> Result<(), Box<dyn std::error::Error>>  {
+/// 启动命令，启动 `selector` 选择的所有 APP
+pub fn run_apps(selector: &Selector, jobs: usize) -> Result<(), InstallerError> {
+    progressln!("开始启动{}", selector);
+
+    let installer_config = InstallerConfig::new()?;
+
+    let installers = installer_config.select(selector);
+    if !ensure_selected(selector, &installers)? {
+        progressln!("> [INFO]: 没有找到 installer。请先执行 `blocklang-installer register` 注册 installer");
+        return Ok(());
+    }
+
+    progressln!("> [INFO]: 共找到 {} 个 installer。", installers.len());
+
+    for_each_installer(&installers, jobs, "启动", run_app)
+}
+
+/// 下载并运行 installer 绑定的 APP，如果 APP 已处于运行状态，则不重启
+pub fn run_app(installer: &Installer) -> Result<(), InstallerError>  {
     let started = Instant::now();
 
-    println!("开始下载并安装 {}-{}，使用 {} 端口", 
+    progressln!("开始下载并安装 {}-{}，使用 {} 端口", 
         installer.app_name,
         installer.app_version,
         installer.app_run_port);
 
-    println!("[1/3] 下载 Jar 包: {}...", installer.app_file_name);
+    progressln!("[1/3] 下载 Jar 包: {}...", installer.app_file_name);
     let prod_spring_boot_jar_path = ensure_spring_boot_jar_exists(
         &installer.url,
         &installer.app_name,
         &installer.app_version,
-        &installer.app_file_name)?;
+        &installer.app_file_name,
+        installer.app_file_sha256.as_deref())?;
 
-    println!("[2/3] 下载 Oracle JDK: {}...", installer.jdk_file_name);
+    progressln!("[2/3] 下载 Oracle JDK: {}...", installer.jdk_file_name);
     let prod_jdk_path = ensure_jdk_exists(
         &installer.url,
         &installer.jdk_name,
         &installer.jdk_version,
-        &installer.jdk_file_name)?;
-
-    println!("[3/3] 在 {} 端口上启动项目...", installer.app_run_port);
-    // 假定运行在所有端口上的项目，都是 installer 管理的
-    // 这样就不会出现在端口上运行的不是我们期望的 APP
-
-    // 如果端口被占用，则认为程序已启动，不需重启
-    if process::get_id(installer.app_run_port) == None {
-        // 运行 Spring Boot Jar
-        jar::run_spring_boot(
-            prod_spring_boot_jar_path.to_str().unwrap(), 
-            prod_jdk_path.to_str().unwrap(),
-            installer.app_run_port);
-        println!("> [INFO]: 项目启动成功");
-    } else {
-        println!("> [INFO]: 项目已处于运行状态");
+        &installer.jdk_file_name,
+        installer.jdk_file_sha256.as_deref())?;
+
+    progressln!("[3/3] 在 {} 端口上启动项目...", installer.app_run_port);
+    // 根据 run 文件夹中记录的进程信息，确认运行在端口上的是否是 installer 启动的项目
+
+    match process::get_port_owner(installer.app_run_port) {
+        PortOwner::Free if installer.uses_proxy() => {
+            // 在内部端口上运行 Spring Boot Jar，并在 APP 的端口上运行代理
+            start_behind_proxy(installer, &prod_spring_boot_jar_path, &prod_jdk_path)?;
+            progressln!("> [INFO]: 项目启动成功");
+        },
+        PortOwner::Free => {
+            // 运行 Spring Boot Jar
+            start_jar(installer, &prod_spring_boot_jar_path, &prod_jdk_path)?;
+            progressln!("> [INFO]: 项目启动成功");
+        },
+        PortOwner::Process(pid) if is_started_by_installer(installer.app_run_port, pid) => {
+            // 如果端口被 installer 启动的进程占用，则认为程序已启动，不需重启
+            progressln!("> [INFO]: 项目已处于运行状态");
+        },
+        PortOwner::Process(pid) => {
+            progressln!("> [ERROR]: 端口 {} 已被进程 {} 占用，该进程不是 installer 启动的", installer.app_run_port, pid);
+            return Err(InstallerError::Process(format!("端口 {} 已被其他进程占用", installer.app_run_port)));
+        },
+        PortOwner::Unknown => {
+            progressln!("> [ERROR]: 端口 {} 已被占用，但无法确定占用端口的进程", installer.app_run_port);
+            return Err(InstallerError::Process(format!("端口 {} 已被其他进程占用", installer.app_run_port)));
+        }
     }
-    println!("完成！耗时 {}", HumanDuration(started.elapsed()));
+    progressln!("完成！耗时 {}", HumanDuration(started.elapsed()));
     Ok(())
 }
 
 /// 升级单个 APP
-pub fn update_single_app(app_run_port: u32) -> Result<(), Box<dyn std::error::Error>> {
-    println!("开始升级运行在端口 {} 上的项目", app_run_port);
+/// 
+/// 如果端口上运行的进程不是 installer 启动的，则只有 `force` 为 `true` 时才重启。
+pub fn update_single_app(app_run_port: u32, force: bool) -> Result<(), InstallerError> {
+    progressln!("开始升级运行在端口 {} 上的项目", app_run_port);
 
-    let installer_config = InstallerConfig::new();
+    let installer_config = InstallerConfig::new()?;
 
-    match installer_config.get_by_port(app_run_port) {
+    let result = match installer_config.get_by_port(app_run_port) {
         Some(installer) => {
-            println!("> [INFO]: 端口 {} 上正在运行 {}-{}，使用的 JDK 版本是 {}", 
+            progressln!("> [INFO]: 端口 {} 上正在运行 {}-{}，使用的 JDK 版本是 {}", 
                 app_run_port, 
                 &installer.app_name, 
                 &installer.app_version, 
                 &installer.jdk_version);
 
-            update_app(&installer)?;
+            update_app(installer, force)
         }
+        None => Err(not_registered(app_run_port)),
+    };
+
+    print_app_record(app_run_port, result)
+}
+
+/// 升级 `selector` 选择的所有 APP
+pub fn update_apps(selector: &Selector, force: bool, jobs: usize) -> Result<(), InstallerError> {
+    progressln!("开始升级{}", selector);
+
+    let installer_config = InstallerConfig::new()?;
+    let installers = installer_config.select(selector);
+    if !ensure_selected(selector, &installers)? {
+        progressln!("> [INFO]: 没有找到 installer。请先执行 `blocklang-installer register` 注册 installer");
+        return Ok(());
+    }
+
+    progressln!("> [INFO]: 共找到 {} 个 installer。", installers.len());
+
+    for_each_installer(&installers, jobs, "升级", |installer| update_app(installer, force))
+}
+
+/// 检查单个 APP 是否有新版本
+/// 
+/// 只打印当前版本和最新版本，不下载文件，不启停进程，也不修改 `installer_config.toml`。
+pub fn dry_run_update_single_app(app_run_port: u32) -> Result<(), InstallerError> {
+    progressln!("开始检查运行在端口 {} 上的项目是否有新版本", app_run_port);
+
+    let installer_config = InstallerConfig::load()?;
+
+    match installer_config.as_ref().and_then(|installer_config| installer_config.get_by_port(app_run_port)) {
+        Some(installer) => dry_run_update(&[installer], 1),
         None => {
-            println!("> [INFO]: 端口 {} 上未注册 installer。请先执行 `blocklang-installer register` 注册 installer", app_run_port);
+            let e = not_registered(app_run_port);
+            output::print_records::<UpdateCheck>(&[])?;
+            Err(e)
         }
     }
+}
 
-    Ok(())
+/// 使用最多 `jobs` 个线程检查 `selector` 选择的所有 APP 是否有新版本，同 `dry_run_update_single_app`
+pub fn dry_run_update_apps(selector: &Selector, jobs: usize) -> Result<(), InstallerError> {
+    progressln!("开始检查{}是否有新版本", selector);
+
+    let installer_config = InstallerConfig::load()?;
+    let installers = installer_config.as_ref()
+        .map(|installer_config| installer_config.select(selector))
+        .unwrap_or_default();
+    if !ensure_selected(selector, &installers)? {
+        progressln!("> [INFO]: 没有找到 installer。请先执行 `blocklang-installer register` 注册 installer");
+        return Ok(());
+    }
+
+    progressln!("> [INFO]: 共找到 {} 个 installer。", installers.len());
+
+    dry_run_update(&installers, jobs)
+}
+
+/// 使用最多 `jobs` 个线程检查每个 installer 是否有新版本，一个 installer 出错时不影响其他 installer。
+/// 
+/// 全部完成后打印每个 installer 的检查结果和汇总信息，如果有 installer 出错，则返回第一个错误。
+fn dry_run_update(installers: &[&Installer], jobs: usize) -> Result<(), InstallerError> {
+    let results = parallel::map(installers, jobs, |_, installer| {
+        if jobs > 1 {
+            output::set_progress_prefix(Some(format!("[{}] ", installer.app_run_port)));
+        }
+        let result = check_update(installer);
+        if let Err(e) = &result {
+            progressln!("> [ERROR]: 检查 {} 端口上的项目是否有新版本失败，{}", installer.app_run_port, e);
+        }
+        output::set_progress_prefix(None);
+        result
+    })?;
+
+    let mut first_error = None;
+    let update_checks: Vec<UpdateCheck> = installers.iter()
+        .zip(results)
+        .map(|(installer, result)| result.unwrap_or_else(|e| {
+            let update_check = UpdateCheck::failed(installer, &e);
+            first_error.get_or_insert(e);
+            update_check
+        }))
+        .collect();
+    print_update_checks(&update_checks)?;
+
+    let upgradable_count = update_checks.iter().filter(|update_check| update_check.upgradable).count();
+    let failed_count = update_checks.iter().filter(|update_check| update_check.error.is_some()).count();
+    progressln!("> [INFO]: 共 {} 个项目，有新版本 {} 个，失败 {} 个", update_checks.len(), upgradable_count, failed_count);
+
+    match first_error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
 }
 
-/// 升级所有 APP
-pub fn update_all_apps() -> Result<(), Box<dyn std::error::Error>> {
-    println!("开始升级所有项目");
+/// 查看单个 APP 的运行状态
+pub fn status_single_app(app_run_port: u32) -> Result<(), InstallerError> {
+    let installer_config = InstallerConfig::new()?;
 
-    let installer_config = InstallerConfig::new();
-    let installers = &installer_config.get_data().installers;
-    if installers.is_empty() {
-        println!("> [INFO]: 没有找到 installer。请先执行 `blocklang-installer register` 注册 installer");
+    match installer_config.get_by_port(app_run_port) {
+        Some(installer) => print_app_statuses(&[AppStatus::new(installer)]),
+        None => {
+            let e = not_registered(app_run_port);
+            output::print_records::<AppStatus>(&[])?;
+            Err(e)
+        }
+    }
+}
+
+/// 查看 `selector` 选择的所有 APP 的运行状态
+pub fn status_apps(selector: &Selector) -> Result<(), InstallerError> {
+    let installer_config = InstallerConfig::new()?;
+    let installers = installer_config.select(selector);
+    if !ensure_selected(selector, &installers)? && !output::is_structured() {
+        progressln!("> [INFO]: 共找到 0 个 installer，请使用 `blocklang-installer register` 命令注册。");
         return Ok(());
     }
 
-    let installer_len = installers.len();
-    println!("> [INFO]: 共找到 {} 个 installer。", installer_len);
+    let app_statuses: Vec<AppStatus> = installers.into_iter().map(AppStatus::new).collect();
+    print_app_statuses(&app_statuses)
+}
 
-    for (index, installer) in installers.iter().enumerate() {
-        println!();
-        println!("===== [{}/{}] 开始升级 {} 端口上的项目 {}-{} =====", 
-            index + 1, 
-            installer_len, 
-            installer.app_run_port, 
-            installer.app_name, 
-            installer.app_version);
+/// APP 的运行状态
+#[derive(Serialize)]
+struct AppStatus {
+    app_run_port: u32,
+    app_name: String,
+    app_version: String,
+    jdk_name: String,
+    jdk_version: String,
+    /// 是否有进程在监听 `app_run_port`
+    listening: bool,
+    /// JVM 进程 id，使用代理时是内部端口上的进程
+    pid: Option<u32>,
+    /// JVM 进程已运行的秒数
+    uptime: Option<u64>,
+    /// JVM 进程占用的物理内存，单位为字节
+    memory: Option<u64>,
+    /// JVM 进程从启动到现在的平均 CPU 使用率，100 表示占满一个 CPU 核
+    cpu_percent: Option<f64>,
+    /// JVM 进程运行的是否是 `prod` 中的当前版本的 jar
+    jar_matched: Option<bool>,
+    /// `prod` 中的 JDK 是否已全部解压
+    jdk_extracted: bool,
+}
+
+impl AppStatus {
+
+    fn new(installer: &Installer) -> Self {
+        let run_port = get_run_port(installer);
+        let owner = process::get_port_owner(run_port);
+        let pid = owner.pid();
+        // 使用代理时，APP 端口上监听的是代理
+        let listening = if run_port == installer.app_run_port {
+            !owner.is_free()
+        } else {
+            !process::get_port_owner(installer.app_run_port).is_free()
+        };
+        let stats = pid.and_then(process::get_stats);
+        let prod_jar_path = get_prod_spring_boot_jar_path(
+            &installer.app_name,
+            &installer.app_version,
+            &installer.app_file_name);
+        let jar_matched = pid.map(|pid| {
+            process::get_command_line(pid)
+                .is_some_and(|command_line| jar::is_running_jar(&command_line, &prod_jar_path.to_string_lossy()))
+        });
+
+        AppStatus {
+            app_run_port: installer.app_run_port,
+            app_name: installer.app_name.clone(),
+            app_version: installer.app_version.clone(),
+            jdk_name: installer.jdk_name.clone(),
+            jdk_version: installer.jdk_version.clone(),
+            listening,
+            pid,
+            uptime: stats.as_ref().map(|stats| stats.uptime.as_secs()),
+            memory: stats.as_ref().map(|stats| stats.memory),
+            cpu_percent: stats.as_ref()
+                .filter(|stats| !stats.uptime.is_zero())
+                .map(|stats| (stats.cpu_time.as_secs_f64() / stats.uptime.as_secs_f64() * 1000.0).round() / 10.0),
+            jar_matched,
+            jdk_extracted: is_jdk_extracted(&installer.jdk_name, &installer.jdk_version, &installer.jdk_file_name),
+        }
+    }
+}
+
+fn print_app_statuses(app_statuses: &[AppStatus]) -> Result<(), InstallerError> {
+    if output::is_structured() {
+        return output::print_records(app_statuses);
+    }
+
+    let yes_or_no = |value: bool| if value { "是" } else { "否" };
+    let or_dash = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+
+    let mut table = Table::new();
+    // 标题行
+    table.add_row(row!["端口号", "项目名", "版本号", "监听", "进程 id", "运行时间", "内存", "CPU", "Jar 一致", "JDK 已解压"]);
+    // 数据行
+    app_statuses.iter().for_each(|app_status| {
+        table.add_row(Row::new(vec![
+            Cell::new(&app_status.app_run_port.to_string()),
+            Cell::new(&app_status.app_name),
+            Cell::new(&app_status.app_version),
+            Cell::new(yes_or_no(app_status.listening)),
+            Cell::new(&or_dash(app_status.pid.map(|pid| pid.to_string()))),
+            Cell::new(&or_dash(app_status.uptime.map(|uptime| HumanDuration(Duration::from_secs(uptime)).to_string()))),
+            Cell::new(&or_dash(app_status.memory.map(|memory| format!("{:.1} MB", memory as f64 / 1024.0 / 1024.0)))),
+            Cell::new(&or_dash(app_status.cpu_percent.map(|cpu_percent| format!("{}%", cpu_percent)))),
+            Cell::new(&or_dash(app_status.jar_matched.map(|jar_matched| yes_or_no(jar_matched).to_string()))),
+            Cell::new(yes_or_no(app_status.jdk_extracted)),
+        ]));
+    });
+    table.printstd();
+    Ok(())
+}
+
+/// 升级检查结果，记录 APP 和 JDK 的当前版本和最新版本
+#[derive(Serialize)]
+struct UpdateCheck {
+    app_run_port: u32,
+    app_name: String,
+    app_version: String,
+    new_app_version: Option<String>,
+    jdk_version: String,
+    new_jdk_version: Option<String>,
+    /// APP 或 JDK 有一个有新版本时就会升级
+    upgradable: bool,
+    /// 检查失败时的出错信息
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl UpdateCheck {
+
+    /// 检查失败时的结果，没有最新版本，也不会升级
+    fn failed(installer: &Installer, e: &InstallerError) -> Self {
+        UpdateCheck {
+            app_run_port: installer.app_run_port,
+            app_name: installer.app_name.clone(),
+            app_version: installer.app_version.clone(),
+            new_app_version: None,
+            jdk_version: installer.jdk_version.clone(),
+            new_jdk_version: None,
+            upgradable: false,
+            error: Some(e.to_string()),
+        }
+    }
+}
+
+/// 从 Block Lang 软件发布中心获取 APP 和 JDK 的最新版本
+fn check_update(installer: &Installer) -> Result<UpdateCheck, InstallerError> {
+    let new_installer = client::update_installer(&installer.url, &installer.installer_token)?;
+
+    let jar_upgraded = is_upgraded(&installer.app_version, &new_installer.app_version)?;
+    let jdk_upgraded = is_upgraded(&installer.jdk_version, &new_installer.jdk_version)?;
+
+    Ok(UpdateCheck {
+        app_run_port: installer.app_run_port,
+        app_name: installer.app_name.clone(),
+        app_version: installer.app_version.clone(),
+        new_app_version: Some(new_installer.app_version),
+        jdk_version: installer.jdk_version.clone(),
+        new_jdk_version: Some(new_installer.jdk_version),
+        upgradable: jar_upgraded || jdk_upgraded,
+        error: None,
+    })
+}
 
-        update_app(installer)?;
+fn print_update_checks(update_checks: &[UpdateCheck]) -> Result<(), InstallerError> {
+    if output::is_structured() {
+        return output::print_records(update_checks);
     }
 
+    let or_dash = |value: &Option<String>| value.clone().unwrap_or_else(|| "-".to_string());
+    let mut table = Table::new();
+    // 标题行
+    table.add_row(row!["端口号", "项目名", "当前版本", "最新版本", "JDK 当前版本", "JDK 最新版本", "是否升级", "错误信息"]);
+    // 数据行
+    update_checks.iter().for_each(|update_check| {
+        table.add_row(Row::new(vec![
+            Cell::new(&update_check.app_run_port.to_string()),
+            Cell::new(&update_check.app_name),
+            Cell::new(&update_check.app_version),
+            Cell::new(&or_dash(&update_check.new_app_version)),
+            Cell::new(&update_check.jdk_version),
+            Cell::new(&or_dash(&update_check.new_jdk_version)),
+            Cell::new(if update_check.upgradable { "是" } else { "否" }),
+            Cell::new(&update_check.error.clone().unwrap_or_default()),
+        ]));
+    });
+    table.printstd();
     Ok(())
 }
 
-fn update_app(installer: &Installer) -> Result<(), Box<dyn std::error::Error>> {
+/// 升级 installer 绑定的 APP，如果 APP 处于运行状态，则重启 APP
+pub fn update_app(installer: &Installer, force: bool) -> Result<(), InstallerError> {
     let started = Instant::now();
+    let _lock = lock_port(installer.app_run_port)?;
 
-    println!("[1/4] 获取 {} 的最新版本和使用的 JDK 最新版本", &installer.app_name);
+    progressln!("[1/4] 获取 {} 的最新版本和使用的 JDK 最新版本", &installer.app_name);
     // 从 Block Lang 软件发布中心获取软件最新版信息
     let new_installer = client::update_installer(&installer.url, &installer.installer_token)?;
 
-    // 检查 spring boot jar 是否有升级
-    let jar_old_ver = Version::from(&installer.app_version).unwrap();
-    let jar_new_ver = Version::from(&new_installer.app_version).unwrap();
-    let jar_upgraded = jar_new_ver > jar_old_ver;
-
-    // 检查 jdk 是否有升级
-    let jdk_old_ver = Version::from(&installer.jdk_version).unwrap();
-    let jdk_new_ver = Version::from(&new_installer.jdk_version).unwrap();
-    let jdk_upgraded = jdk_new_ver > jdk_old_ver;
+    // 检查 spring boot jar 和 jdk 是否有升级
+    let jar_upgraded = is_upgraded(&installer.app_version, &new_installer.app_version)?;
+    let jdk_upgraded = is_upgraded(&installer.jdk_version, &new_installer.jdk_version)?;
 
     // 如果软件版本没有变化，则提示当前运行的 spring boot jar 已是最新版本
     if !jar_upgraded && !jdk_upgraded {
-        println!("> [INFO]: 已是最新版本。{} 的版本是 {}，JDK 的版本是 {}", 
+        progressln!("> [INFO]: 已是最新版本。{} 的版本是 {}，JDK 的版本是 {}", 
             new_installer.app_name,
             new_installer.app_version,
             new_installer.jdk_version);
         return Ok(());
     }
 
-    println!("[2/4] 开始升级 Oracle JDK");
+    progressln!("[2/4] 开始升级 Oracle JDK");
     // 如果版本已有新版本，则更新并运行最新版本(只要 jdk 或 jar 有一个升级就重启)
     // 1. 更新 JDK
     let prod_jdk_path = if jdk_upgraded {
-        println!("> [INFO]: 从 {} 升级到 {}", &installer.jdk_version, &new_installer.jdk_version);
+        progressln!("> [INFO]: 从 {} 升级到 {}", &installer.jdk_version, &new_installer.jdk_version);
         ensure_jdk_exists(
             &installer.url, // 注意，url 注册之后就不会再改变。
             &new_installer.jdk_name,
             &new_installer.jdk_version,
-            &new_installer.jdk_file_name)?
+            &new_installer.jdk_file_name,
+            new_installer.jdk_file_sha256.as_deref())?
     } else {
-        println!("> [INFO]: 文件已存在");
+        progressln!("> [INFO]: 文件已存在");
         get_prod_jdk_path(&installer.jdk_name, &installer.jdk_version)
     };
 
-    println!("[3/4] 开始升级 {}", &new_installer.app_name);
+    progressln!("[3/4] 开始升级 {}", &new_installer.app_name);
     // 2. 更新 spring boot jar
     let prod_spring_boot_jar_path =  if jar_upgraded {
-        println!("> [INFO]: 从 {} 升级到 {}", &installer.app_version, &new_installer.app_version);
+        progressln!("> [INFO]: 从 {} 升级到 {}", &installer.app_version, &new_installer.app_version);
         ensure_spring_boot_jar_exists(
             &installer.url,
             &new_installer.app_name,
             &new_installer.app_version,
-            &new_installer.app_file_name)?
+            &new_installer.app_file_name,
+            new_installer.app_file_sha256.as_deref())?
     } else {
-        println!("> [INFO]: 文件已存在");
+        progressln!("> [INFO]: 文件已存在");
         get_prod_spring_boot_jar_path(
             &installer.app_name,
             &installer.app_version,
             &installer.app_file_name)
     };
 
-    println!("[4/4] 检查端口 {} 上 {}-{} 的运行状态", 
+    progressln!("[4/4] 检查端口 {} 上 {}-{} 的运行状态", 
         installer.app_run_port,
         &new_installer.app_name, 
         &new_installer.app_version);
 
-    if process::get_id(installer.app_run_port).is_none() {
+    if process::get_port_owner(installer.app_run_port).is_free() {
         // 如果 APP 没有运行，则提示程序的运行状态
-        println!("> [INFO]: {}-{} 没有运行。依然保持未运行状态", installer.app_name, installer.app_version);
+        progressln!("> [INFO]: {}-{} 没有运行。依然保持未运行状态", installer.app_name, installer.app_version);
+    } else if installer.is_blue_green() {
+        // 使用蓝绿部署时，新版本通过健康检查后才切换，否则旧版本继续运行
+        progressln!("> [INFO]: {}-{} 运行在 {} 端口上，开始蓝绿部署", installer.app_name, installer.app_version, installer.app_run_port);
+        if let Err(e) = switch_blue_green(installer, &prod_spring_boot_jar_path, &prod_jdk_path, force) {
+            progressln!("> [ERROR]: {}", e);
+            progressln!("> [INFO]: {}-{} 依然运行在 {} 端口上", installer.app_name, installer.app_version, installer.app_run_port);
+            return Err(e);
+        }
     } else {
-        println!("> [INFO]: {}-{} 运行在 {} 端口上，开始重启", installer.app_name, installer.app_version, installer.app_run_port);
+        progressln!("> [INFO]: {}-{} 运行在 {} 端口上，开始重启", installer.app_name, installer.app_version, installer.app_run_port);
         // 如果 APP 正在运行，则重启 APP
-        // 3. 停止旧版 jar
-        stop_jar(installer.app_run_port);
+        // 3. 停止旧版 jar，使用代理时不停止代理，代理会保持重启期间建立的连接
+        stop_jvm(installer, force)?;
         // 4. 启动新版 jar
-        print!("> [INFO]: 开始重启...");
-        io::stdout().flush()?;
+        progress!("> [INFO]: 开始重启...");
 
-        jar::run_spring_boot(
-            prod_spring_boot_jar_path.to_str().unwrap(), 
-            prod_jdk_path.to_str().unwrap(),
-            installer.app_run_port);
+        let mut child = start_jar(installer, &prod_spring_boot_jar_path, &prod_jdk_path)?;
         
-        println!("完成");
+        progressln!("完成");
+
+        // 5. 如果配置了健康检查，则确认新版 jar 是否正常运行，否则重新运行旧版 jar
+        if let Some(health_check) = &installer.health_check {
+            progress!("> [INFO]: 正在检查 {}-{} 的健康状态...", new_installer.app_name, new_installer.app_version);
+
+            if let Err(e) = health_check::wait_until_healthy(get_run_port(installer), health_check, &mut child) {
+                progressln!("失败");
+                progressln!("> [ERROR]: {}", e);
+                restore_previous_jar(installer)?;
+                return Err(InstallerError::Process(format!("{}-{} 没有通过健康检查，已重新运行 {}-{}",
+                    new_installer.app_name,
+                    new_installer.app_version,
+                    installer.app_name,
+                    installer.app_version)));
+            }
+
+            progressln!("通过");
+        }
+    }
+
+    // 更新 installer_config.toml 中的配置信息，并发升级时防止覆盖其他 installer 的修改
+    InstallerConfig::modify(|installer_config| installer_config.update(installer.app_run_port, new_installer))?;
+
+    progressln!("升级完成！耗时 {}", HumanDuration(started.elapsed()));
+    Ok(())
+}
+
+/// 修改配置命令，设置端口上的 installer 运行 APP 时使用的 JVM 参数、程序参数或环境变量。
+/// 
+/// 修改后需重启 APP 才能生效。
+pub fn set_config(app_run_port: u32, key: &str, value: &str) -> Result<(), InstallerError> {
+    InstallerConfig::modify(|installer_config| installer_config.set_option(app_run_port, key, value))?;
+
+    progressln!("> [INFO]: 已将端口 {} 上 installer 的 {} 设置为 \"{}\"", app_run_port, key, value);
+    progressln!("> [INFO]: 请执行 `blocklang-installer stop --port {0}` 和 `blocklang-installer run --port {0}` 命令重启项目，使配置生效", app_run_port);
+    Ok(())
+}
+
+/// 设置所有 installer 默认使用的配置项，详见 `InstallerConfig::set_global_option`
+pub fn set_global_config(key: &str, value: &str) -> Result<(), InstallerError> {
+    InstallerConfig::modify(|installer_config| installer_config.set_global_option(key, value))?;
+
+    progressln!("> [INFO]: 已将所有 installer 默认使用的 {} 设置为 \"{}\"", key, value);
+    Ok(())
+}
+
+/// 查看日志命令，打印运行在端口上的 APP 的最后 `lines` 行日志。
+/// 
+/// 如果 `follow` 为 `true`，则持续输出新增的日志，按 CTRL + C 退出。
+pub fn print_logs(app_run_port: u32, follow: bool, lines: usize) -> Result<(), InstallerError> {
+    let installer_config = InstallerConfig::new()?;
+    let installer = installer_config.get_by_port(app_run_port).ok_or_else(|| not_registered(app_run_port))?;
+
+    let log_path = app_log::get_log_path(&installer.app_name, app_run_port);
+    if !log_path.exists() {
+        progressln!("> [INFO]: 没有找到日志文件 {}，请先执行 `blocklang-installer run --port {}` 运行项目", log_path.display(), app_run_port);
+        return Err(InstallerError::NotFound(format!("日志文件 {}", log_path.display())));
+    }
+
+    for line in app_log::tail(&log_path, lines)? {
+        progressln!("{}", line);
+    }
+
+    if follow {
+        app_log::follow(&log_path)?;
+    }
+
+    Ok(())
+}
+
+/// 新版 jar 没有通过健康检查时，停止新版 jar，重新运行 `installer` 中配置的旧版 jar 和 JDK。
+/// 
+/// 旧版 jar 和 JDK 在升级前正在运行，所以依然在 prod 文件夹中。
+fn restore_previous_jar(installer: &Installer) -> Result<(), InstallerError> {
+    progressln!("> [INFO]: 开始停止新版本，并重新运行 {}-{}", installer.app_name, installer.app_version);
+    // 新版 jar 是 installer 启动的，不需要强制关闭
+    stop_jvm(installer, false)?;
+
+    let prod_spring_boot_jar_path = get_prod_spring_boot_jar_path(
+        &installer.app_name,
+        &installer.app_version,
+        &installer.app_file_name);
+    let prod_jdk_path = get_prod_jdk_path(&installer.jdk_name, &installer.jdk_version);
+    start_jar(installer, &prod_spring_boot_jar_path, &prod_jdk_path)?;
+
+    progressln!("> [INFO]: 已重新运行 {}-{}", installer.app_name, installer.app_version);
+    Ok(())
+}
+
+/// 回滚命令，将端口上的 APP 回滚到上一个部署的版本
+/// 
+/// 上一个版本的 Spring Boot jar 和 JDK 依然保存在 prod 文件夹中，如果已被删除，则重新下载。
+/// 如果 APP 处于运行状态，则使用上一个版本重启 APP；如果 APP 没有运行，则依然保持未运行状态。
+/// 如果端口上运行的进程不是 installer 启动的，则只有 `force` 为 `true` 时才重启。
+pub fn rollback_app(app_run_port: u32, force: bool) -> Result<(), InstallerError> {
+    progressln!("开始回滚运行在端口 {} 上的项目", app_run_port);
+    let started = Instant::now();
+
+    let installer_config = InstallerConfig::new()?;
+    let installer = installer_config.get_by_port(app_run_port).ok_or_else(|| not_registered(app_run_port))?;
+
+    let previous = installer.previous_version().ok_or_else(|| {
+        progressln!("> [INFO]: {}-{} 没有可回滚的版本", installer.app_name, installer.app_version);
+        InstallerError::NotFound(format!("端口 {} 上没有可回滚的版本", app_run_port))
+    })?;
+
+    progressln!("> [INFO]: 从 {}-{}(JDK {}) 回滚到 {}-{}(JDK {})",
+        installer.app_name,
+        installer.app_version,
+        installer.jdk_version,
+        previous.app_name,
+        previous.app_version,
+        previous.jdk_version);
+    let _lock = lock_port(app_run_port)?;
+
+    progressln!("[1/3] 检查 Oracle JDK: {}...", previous.jdk_file_name);
+    let prod_jdk_path = ensure_jdk_exists(
+        &installer.url,
+        &previous.jdk_name,
+        &previous.jdk_version,
+        &previous.jdk_file_name,
+        previous.jdk_file_sha256.as_deref())?;
+
+    progressln!("[2/3] 检查 Jar 包: {}...", previous.app_file_name);
+    let prod_spring_boot_jar_path = ensure_spring_boot_jar_exists(
+        &installer.url,
+        &previous.app_name,
+        &previous.app_version,
+        &previous.app_file_name,
+        previous.app_file_sha256.as_deref())?;
+
+    progressln!("[3/3] 检查端口 {} 上 {}-{} 的运行状态",
+        installer.app_run_port,
+        installer.app_name,
+        installer.app_version);
+
+    if process::get_port_owner(installer.app_run_port).is_free() {
+        progressln!("> [INFO]: {}-{} 没有运行。依然保持未运行状态", installer.app_name, installer.app_version);
+    } else if installer.is_blue_green() {
+        progressln!("> [INFO]: {}-{} 运行在 {} 端口上，开始蓝绿部署", installer.app_name, installer.app_version, installer.app_run_port);
+        switch_blue_green(installer, &prod_spring_boot_jar_path, &prod_jdk_path, force)?;
+    } else {
+        progressln!("> [INFO]: {}-{} 运行在 {} 端口上，开始重启", installer.app_name, installer.app_version, installer.app_run_port);
+        stop_jvm(installer, force)?;
+
+        progress!("> [INFO]: 开始重启...");
+
+        start_jar(installer, &prod_spring_boot_jar_path, &prod_jdk_path)?;
+
+        progressln!("完成");
     }
 
-    // 更新 installer_config.toml 中的配置信息
-    let mut installer_config = InstallerConfig::new();
-    installer_config.update(installer.app_run_port, new_installer);
+    // 恢复 installer_config.toml 中的配置信息
+    InstallerConfig::modify(|installer_config| installer_config.rollback(app_run_port))?;
 
-    println!("升级完成！耗时 {}", HumanDuration(started.elapsed()));
+    progressln!("回滚完成！耗时 {}", HumanDuration(started.elapsed()));
     Ok(())
 }
 
+/// 解析版本号，如果版本号无效则返回错误
+fn parse_version(version: &str) -> Result<Version<'_>, InstallerError> {
+    Version::from(version)
+        .ok_or_else(|| InstallerError::Validation(format!("无效的版本号 {}", version)))
+}
+
+/// 判断 `new_version` 是否比 `old_version` 新，`update` 和 `update --dry-run` 都使用此规则判断是否升级
+fn is_upgraded(old_version: &str, new_version: &str) -> Result<bool, InstallerError> {
+    Ok(parse_version(new_version)? > parse_version(old_version)?)
+}
+
 /// 停止单个 APP
-pub fn stop_single_app(app_run_port: u32) -> Result<(), Box<dyn std::error::Error>> {
-    println!("开始停止运行在 {} 端口上的项目，并关闭此端口", app_run_port);
+/// 
+/// 如果端口上运行的进程不是 installer 启动的，则只有 `force` 为 `true` 时才关闭。
+pub fn stop_single_app(app_run_port: u32, force: bool) -> Result<(), InstallerError> {
+    progressln!("开始停止运行在 {} 端口上的项目，并关闭此端口", app_run_port);
 
-    let installer_config = InstallerConfig::new();
+    let installer_config = InstallerConfig::new()?;
 
     // 注意：只关闭注册 installer 的端口，防止误关安装在应用服务器上的其他应用。
-    match installer_config.get_by_port(app_run_port) {
-        Some(_) => {
-            stop_jar(app_run_port);
-        }
-        None => {
-            println!("> [INFO]: {} 端口上未注册项目", app_run_port);
+    let result = match installer_config.get_by_port(app_run_port) {
+        Some(installer) => stop_jar(installer, force),
+        None => Err(not_registered(app_run_port)),
+    };
+
+    print_app_record(app_run_port, result)
+}
+
+/// 停止 `selector` 选择的所有 APP
+pub fn stop_apps(selector: &Selector, force: bool, jobs: usize) -> Result<(), InstallerError> {
+    progressln!("开始关闭{}", selector);
+
+    let installer_config = InstallerConfig::new()?;
+
+    let installers = installer_config.select(selector);
+    if !ensure_selected(selector, &installers)? {
+        progressln!("> [INFO]: 没有找到 installer。请先执行 `blocklang-installer register` 注册 installer");
+        return Ok(());
+    }
+
+    progressln!("> [INFO]: 共找到 {} 个 installer。", installers.len());
+
+    for_each_installer(&installers, jobs, "关闭", |installer| {
+        // 端口上运行的不是 installer 启动的进程时，只提示并跳过，继续关闭其他 APP
+        if !force {
+            if let Some((port, pid)) = find_foreign_process(installer) {
+                progressln!("> [WARN]: 端口 {} 上运行的进程 {} 不是 installer 启动的，跳过此项目", port, pid);
+                progressln!("> [INFO]: 确定要关闭此进程，请添加 --force 选项");
+                return Ok(());
+            }
         }
+        stop_jar(installer, force)
+    })
+}
+
+/// 重启单个 APP
+/// 
+/// 如果端口上运行的进程不是 installer 启动的，则只有 `force` 为 `true` 时才重启。
+pub fn restart_single_app(app_run_port: u32, force: bool) -> Result<(), InstallerError> {
+    progressln!("开始重启运行在 {} 端口上的项目", app_run_port);
+
+    let installer_config = InstallerConfig::new()?;
+
+    let result = match installer_config.get_by_port(app_run_port) {
+        Some(installer) => restart_app(installer, force),
+        None => Err(not_registered(app_run_port)),
+    };
+
+    print_app_record(app_run_port, result)
+}
+
+/// 重启 `selector` 选择的所有 APP
+pub fn restart_apps(selector: &Selector, force: bool, jobs: usize) -> Result<(), InstallerError> {
+    progressln!("开始重启{}", selector);
+
+    let installer_config = InstallerConfig::new()?;
+
+    let installers = installer_config.select(selector);
+    if !ensure_selected(selector, &installers)? {
+        progressln!("> [INFO]: 没有找到 installer。请先执行 `blocklang-installer register` 注册 installer");
+        return Ok(());
     }
 
-    Ok(())
+    progressln!("> [INFO]: 共找到 {} 个 installer。", installers.len());
+
+    for_each_installer(&installers, jobs, "重启", |installer| restart_app(installer, force))
 }
 
-/// 停止所有 APP
-pub fn stop_all_apps() -> Result<(), Box<dyn std::error::Error>> {
-    println!("开始关闭所有项目");
+/// 使用 `installer_config.toml` 中配置的 jar 和 JDK 重启 APP，不访问 Block Lang 平台，
+/// 所以 jar 和 JDK 必须已存在于 `prod` 文件夹中。修改 JVM 参数或环境变量后可使用此命令使配置生效。
+/// 
+/// 使用代理时只重启 JVM，代理会保持重启期间建立的连接。
+/// 使用蓝绿部署且 APP 正在运行时，在空闲的内部端口上启动 APP 后再切换，不停机。
+pub fn restart_app(installer: &Installer, force: bool) -> Result<(), InstallerError> {
+    let started = Instant::now();
+    let _lock = lock_port(installer.app_run_port)?;
 
-    let installer_config = InstallerConfig::new();
+    let prod_spring_boot_jar_path = get_prod_spring_boot_jar_path(
+        &installer.app_name,
+        &installer.app_version,
+        &installer.app_file_name);
+    if !prod_spring_boot_jar_path.exists() {
+        return Err(InstallerError::NotFound(format!("{}，请先执行 `blocklang-installer run` 运行 APP", 
+            prod_spring_boot_jar_path.display())));
+    }
+    let prod_jdk_path = get_prod_jdk_path(&installer.jdk_name, &installer.jdk_version);
+    if !is_jdk_extracted(&installer.jdk_name, &installer.jdk_version, &installer.jdk_file_name) {
+        return Err(InstallerError::NotFound(format!("{}，请先执行 `blocklang-installer run` 运行 APP", 
+            prod_jdk_path.display())));
+    }
 
-    let installers = &installer_config.get_data().installers;
-    if installers.is_empty() {
-        println!("> [INFO]: 没有找到 installer。请先执行 `blocklang-installer register` 注册 installer");
+    if installer.is_blue_green() && !process::get_port_owner(installer.app_run_port).is_free() {
+        progressln!("> [INFO]: {}-{} 运行在 {} 端口上，开始蓝绿部署", installer.app_name, installer.app_version, installer.app_run_port);
+        switch_blue_green(installer, &prod_spring_boot_jar_path, &prod_jdk_path, force)?;
+        progressln!("> [INFO]: 项目重启成功，耗时 {}", HumanDuration(started.elapsed()));
         return Ok(());
     }
 
-    let installer_len = installers.len();
-    println!("> [INFO]: 共找到 {} 个 installer。", installer_len);
+    progressln!("[1/2] 停止 {}-{}", installer.app_name, installer.app_version);
+    let run_port = get_run_port(installer);
+    stop_jvm(installer, force)?;
+    wait_until_port_released(run_port)?;
 
-    for (index, installer) in installers.iter().enumerate() {
-        println!();
-        println!("===== [{}/{}] 开始关闭 {} 端口上的项目 {}-{} =====", 
-            index + 1, 
-            installer_len, 
-            installer.app_run_port, 
-            installer.app_name, 
-            installer.app_version);
+    progressln!("[2/2] 在 {} 端口上启动 {}-{}", installer.app_run_port, installer.app_name, installer.app_version);
+    if installer.uses_proxy() {
+        start_behind_proxy(installer, &prod_spring_boot_jar_path, &prod_jdk_path)?;
+    } else {
+        start_jar(installer, &prod_spring_boot_jar_path, &prod_jdk_path)?;
+    }
 
-        stop_jar(installer.app_run_port);
+    progressln!("> [INFO]: 项目重启成功，耗时 {}", HumanDuration(started.elapsed()));
+    Ok(())
+}
+
+/// 获取端口上 APP 的操作锁，如果守护进程或其他命令正在停止或启动此 APP，则返回错误
+fn lock_port(app_run_port: u32) -> Result<PortLock, InstallerError> {
+    PortLock::try_acquire(app_run_port)?.ok_or_else(|| {
+        InstallerError::Process(format!("端口 {} 上的 APP 正在被其他命令操作，请稍后重试", app_run_port))
+    })
+}
+
+/// 关闭进程后，操作系统可能还没有释放端口，等到端口没有被监听时再启动新进程
+fn wait_until_port_released(port: u32) -> Result<(), InstallerError> {
+    let started = Instant::now();
+    while !process::get_port_owner(port).is_free() {
+        if started.elapsed() >= PORT_RELEASE_TIMEOUT {
+            return Err(InstallerError::Process(format!("端口 {} 在 {} 秒内没有释放", 
+                port, 
+                PORT_RELEASE_TIMEOUT.as_secs())));
+        }
+        thread::sleep(Duration::from_millis(200));
     }
 
     Ok(())
 }
 
+/// 运行 spring boot jar，并在 run 文件夹中记录进程信息。
+/// 
+/// spring boot jar 的标准输出和标准错误会写入 `logs/<app_name>/<app_run_port>.log` 中。
+/// 如果存在 `conf/<app_run_port>` 文件夹，则通过 `--spring.config.additional-location` 加载其中的配置文件。
+/// 
+/// 如果使用代理，则在内部端口上运行 spring boot jar，并确保 APP 端口上的代理已运行。
+pub(crate) fn start_jar(installer: &Installer,
+    prod_spring_boot_jar_path: &Path,
+    prod_jdk_path: &Path) -> Result<Child, InstallerError> {
+    let run_port = get_run_port(installer);
+    let child = start_jar_on_port(installer, prod_spring_boot_jar_path, prod_jdk_path, run_port)?;
+    if installer.uses_proxy() {
+        ensure_proxy_running(installer, run_port)?;
+    }
+    Ok(child)
+}
+
+/// 获取 spring boot jar 的运行端口。
+/// 
+/// 不使用代理时是 `installer.app_run_port`；使用代理时是代理当前转发到的内部端口，
+/// 如果还没有转发到任何内部端口，则使用第一个内部端口。
+pub(crate) fn get_run_port(installer: &Installer) -> u32 {
+    let backend_ports = installer.backend_ports();
+    if backend_ports.is_empty() {
+        return installer.app_run_port;
+    }
+
+    proxy::read_backend_port(installer.app_run_port)
+        .filter(|port| backend_ports.contains(port))
+        .unwrap_or(backend_ports[0])
+}
+
+/// 在 `run_port` 端口上运行 spring boot jar，使用代理时 `run_port` 是内部端口，否则是 `installer.app_run_port`。
+/// 
+/// 进程信息记录在 `run_port` 对应的记录文件中，日志依然写入 `logs/<app_name>/<app_run_port>.log` 中。
+fn start_jar_on_port(installer: &Installer,
+    prod_spring_boot_jar_path: &Path,
+    prod_jdk_path: &Path,
+    run_port: u32) -> Result<Child, InstallerError> {
+    let log = app_log::open(&installer.app_name, installer.app_run_port)?;
+    let mut app_args = Vec::new();
+    if let Some(arg) = app_conf::get_additional_location_arg(installer.app_run_port) {
+        app_args.push(arg);
+    }
+    app_args.extend_from_slice(&installer.app_args);
+
+    let options = jar::LaunchOptions {
+        jvm_args: &installer.jvm_args,
+        app_args: &app_args,
+        env: Some(&installer.env),
+    };
+    let child = jar::run_spring_boot(
+        &prod_spring_boot_jar_path.to_string_lossy(), 
+        &prod_jdk_path.to_string_lossy(),
+        run_port,
+        &options,
+        log).map_err(|e| {
+            InstallerError::Process(format!("在 {} 端口上启动项目失败，{}", run_port, e))
+        })?;
+
+    if let Err(e) = RunRecord::new(child.id()).save(run_port) {
+        progressln!("> [WARN]: 保存进程 {} 的信息失败：{}", child.id(), e);
+    }
+
+    Ok(child)
+}
+
+/// 根据 run 文件夹中记录的进程信息，判断进程是否是 installer 启动的
+fn is_started_by_installer(app_run_port: u32, pid: u32) -> bool {
+    RunRecord::load(app_run_port)
+        .map(|record| record.matches(pid))
+        .unwrap_or(false)
+}
+
+/// 查找 APP 端口和代理后面的内部端口上运行的不是 installer 启动的进程，返回端口和进程 id
+fn find_foreign_process(installer: &Installer) -> Option<(u32, u32)> {
+    std::iter::once(installer.app_run_port)
+        .chain(proxy::read_backend_port(installer.app_run_port))
+        .find_map(|port| match process::get_port_owner(port) {
+            PortOwner::Process(pid) if !is_started_by_installer(port, pid) => Some((port, pid)),
+            _ => None,
+        })
+}
+
 /// 停止运行 spring boot jar。
-fn stop_jar(run_port: u32) {
-    // 根据在 installer_config.toml 中登记的 spring boot jar 的运行端口来找到进程，并 kill 掉进程，
+/// 
+/// 先通知 spring boot jar 正常退出，如果在 `installer.grace_period` 秒内没有退出，则强制关闭。
+/// 
+/// 只关闭 installer 启动的进程，即与 run 文件夹中记录的进程 id 和启动命令都一致的进程；
+/// 如果端口上运行的是其他进程，则只有 `force` 为 `true` 时才关闭，否则返回错误。
+/// 
+/// 使用蓝绿部署时，先关闭 APP 端口上的代理，再关闭代理后面内部端口上的 spring boot jar。
+pub fn stop_jar(installer: &Installer, force: bool) -> Result<(), InstallerError> {
+    // 根据在 installer_config.toml 中登记的 spring boot jar 的运行端口来找到进程，并关闭进程，
     // 以此来关闭 spring boot jar。
-    match process::get_id(run_port) {
-        Some(x) => {
-            println!("> [INFO]: 端口 {} 运行在 {} 进程上", run_port, x);
-            process::kill(x);
-            println!("> [INFO]: 端口 {} 已关闭", run_port);
+    stop_port(installer.app_run_port, installer.grace_period, force)?;
+
+    if let Some(backend_port) = proxy::read_backend_port(installer.app_run_port) {
+        stop_port(backend_port, installer.grace_period, force)?;
+        proxy::remove_backend_port(installer.app_run_port);
+    }
+
+    Ok(())
+}
+
+/// 只停止 spring boot jar，不停止代理。
+/// 
+/// 使用代理时，代理会保持 APP 重启期间建立的连接，等待 APP 重新运行；
+/// 如果不再使用代理，但代理依然在运行，则同时停止代理，以释放 APP 的端口。
+fn stop_jvm(installer: &Installer, force: bool) -> Result<(), InstallerError> {
+    match proxy::read_backend_port(installer.app_run_port) {
+        Some(backend_port) if installer.uses_proxy() => stop_port(backend_port, installer.grace_period, force),
+        _ => stop_jar(installer, force),
+    }
+}
+
+/// 关闭运行在 `run_port` 端口上的进程
+fn stop_port(run_port: u32, grace_period: u64, force: bool) -> Result<(), InstallerError> {
+    match process::get_port_owner(run_port) {
+        PortOwner::Process(x) => {
+            progressln!("> [INFO]: 端口 {} 运行在 {} 进程上", run_port, x);
+            if !is_started_by_installer(run_port, x) {
+                if !force {
+                    progressln!("> [ERROR]: 进程 {} 不是 installer 启动的，为防止误关其他应用，没有关闭此进程", x);
+                    progressln!("> [INFO]: 确定要关闭此进程，请添加 --force 选项");
+                    return Err(InstallerError::Process(format!("端口 {} 上运行的进程 {} 不是 installer 启动的", run_port, x)));
+                }
+                progressln!("> [WARN]: 进程 {} 不是 installer 启动的，使用了 --force 选项，依然关闭此进程", x);
+            }
+            // 先删除进程记录再关闭进程，守护进程据此判断 APP 是被有意停止的，不会重启 APP
+            let record = RunRecord::load(run_port);
+            RunRecord::remove(run_port);
+            if let Err(e) = process::terminate(x, run_port, Duration::from_secs(grace_period)) {
+                // 进程可能依然在运行，恢复进程记录
+                if let Some(record) = record {
+                    if let Err(e) = record.save(run_port) {
+                        progressln!("> [WARN]: 恢复端口 {} 的进程记录失败：{}", run_port, e);
+                    }
+                }
+                return Err(e);
+            }
+            progressln!("> [INFO]: 端口 {} 已关闭", run_port);
+        }
+        PortOwner::Unknown => {
+            progressln!("> [ERROR]: 端口 {} 已被占用，但无法确定占用端口的进程，请使用有权限查看此进程的用户执行命令", run_port);
+            return Err(InstallerError::Process(format!("无法确定占用端口 {} 的进程", run_port)));
+        }
+        PortOwner::Free => {
+            RunRecord::remove(run_port);
+            progressln!("> [INFO]: 端口 {} 未使用", run_port);
+        }
+    }
+
+    Ok(())
+}
+
+/// 在代理后面运行 APP：先在内部端口上运行 spring boot jar，然后在 APP 的端口上运行代理。
+/// 
+/// 优先使用上一次运行时使用的内部端口，如果内部端口上已运行了 installer 启动的 APP，则不再重新运行。
+fn start_behind_proxy(installer: &Installer,
+    prod_spring_boot_jar_path: &Path,
+    prod_jdk_path: &Path) -> Result<(), InstallerError> {
+    let backend_port = get_run_port(installer);
+
+    match process::get_port_owner(backend_port) {
+        PortOwner::Free => {
+            progressln!("> [INFO]: 在内部端口 {} 上启动项目", backend_port);
+            start_jar_on_port(installer, prod_spring_boot_jar_path, prod_jdk_path, backend_port)?;
+        },
+        PortOwner::Process(pid) if is_started_by_installer(backend_port, pid) => {
+            progressln!("> [INFO]: 项目已运行在内部端口 {} 上", backend_port);
+        },
+        PortOwner::Process(pid) => {
+            return Err(InstallerError::Process(format!("内部端口 {} 已被进程 {} 占用，该进程不是 installer 启动的", backend_port, pid)));
+        },
+        PortOwner::Unknown => {
+            return Err(InstallerError::Process(format!("内部端口 {} 已被占用，但无法确定占用端口的进程", backend_port)));
+        }
+    }
+
+    ensure_proxy_running(installer, backend_port)
+}
+
+/// 将代理转发到 `backend_port`，如果 APP 端口上没有运行代理，则启动代理
+fn ensure_proxy_running(installer: &Installer, backend_port: u32) -> Result<(), InstallerError> {
+    proxy::write_backend_port(installer.app_run_port, backend_port)?;
+
+    match process::get_port_owner(installer.app_run_port) {
+        PortOwner::Free => start_proxy(installer),
+        PortOwner::Process(pid) if is_started_by_installer(installer.app_run_port, pid) => Ok(()),
+        PortOwner::Process(pid) => {
+            Err(InstallerError::Process(format!("端口 {} 已被进程 {} 占用，该进程不是 installer 启动的", installer.app_run_port, pid)))
+        },
+        PortOwner::Unknown => {
+            Err(InstallerError::Process(format!("端口 {} 已被占用，但无法确定占用端口的进程", installer.app_run_port)))
         }
+    }
+}
+
+/// 蓝绿部署，不停机地将 APP 切换到 `prod_spring_boot_jar_path` 和 `prod_jdk_path` 指定的版本。
+/// 
+/// 1. 在空闲的内部端口上运行新版本；
+/// 2. 等待新版本通过健康检查，如果没有配置健康检查，则检查端口能否建立 TCP 连接；
+/// 3. 将代理切换到新版本的端口上，之后的新连接都会转发到新版本；
+/// 4. 停止旧版本。
+/// 
+/// 如果新版本没有通过健康检查，则停止新版本，旧版本继续运行。
+/// 代理切换到新版本后，新版本已开始接收请求，此时停止旧版本失败只打印警告，不再返回错误。
+/// 如果之前没有使用代理，即旧版本直接运行在 APP 的端口上，则在停止旧版本和启动代理之间会短暂停机，
+/// 此时只有 `force` 为 `true` 时才会关闭不是 installer 启动的旧版本。
+fn switch_blue_green(installer: &Installer,
+    prod_spring_boot_jar_path: &Path,
+    prod_jdk_path: &Path,
+    force: bool) -> Result<(), InstallerError> {
+    let old_port = proxy::read_backend_port(installer.app_run_port);
+    let new_port = if old_port == Some(installer.blue_green_ports[0]) {
+        installer.blue_green_ports[1]
+    } else {
+        installer.blue_green_ports[0]
+    };
+
+    // 清理上次部署失败时残留在空闲端口上的进程
+    match process::get_port_owner(new_port) {
+        PortOwner::Free => {},
+        PortOwner::Process(pid) if is_started_by_installer(new_port, pid) => {
+            stop_port(new_port, installer.grace_period, false)?;
+        },
+        PortOwner::Process(pid) => {
+            return Err(InstallerError::Process(format!("内部端口 {} 已被进程 {} 占用，该进程不是 installer 启动的", new_port, pid)));
+        },
+        PortOwner::Unknown => {
+            return Err(InstallerError::Process(format!("内部端口 {} 已被占用，但无法确定占用端口的进程", new_port)));
+        }
+    }
+
+    progress!("> [INFO]: 在内部端口 {} 上启动新版本...", new_port);
+    let mut child = start_jar_on_port(installer, prod_spring_boot_jar_path, prod_jdk_path, new_port)?;
+    progressln!("完成");
+
+    progress!("> [INFO]: 正在检查新版本的健康状态...");
+    let default_health_check = HealthCheck::default();
+    let health_check = installer.health_check.as_ref().unwrap_or(&default_health_check);
+    if let Err(e) = health_check::wait_until_healthy(new_port, health_check, &mut child) {
+        progressln!("失败");
+        RunRecord::remove(new_port);
+        if let Err(e) = process::terminate(child.id(), new_port, Duration::from_secs(installer.grace_period)) {
+            progressln!("> [WARN]: 停止新版本失败：{}", e);
+        }
+        return Err(InstallerError::Process(format!("新版本没有通过健康检查，已停止新版本，{}", e)));
+    }
+    progressln!("通过");
+
+    match old_port {
+        Some(old_port) => {
+            proxy::write_backend_port(installer.app_run_port, new_port)?;
+            progressln!("> [INFO]: 已将 {} 端口切换到内部端口 {}", installer.app_run_port, new_port);
+            if let Err(e) = stop_port(old_port, installer.grace_period, false) {
+                progressln!("> [WARN]: 停止内部端口 {} 上的旧版本失败，请手动停止：{}", old_port, e);
+            }
+        },
         None => {
-            println!("> [INFO]: 端口 {} 未使用", run_port);
+            progressln!("> [WARN]: 之前没有使用代理，停止旧版本并启动代理时会短暂停机");
+            stop_port(installer.app_run_port, installer.grace_period, force)?;
+            ensure_proxy_running(installer, new_port)?;
+            progressln!("> [INFO]: 已将 {} 端口切换到内部端口 {}", installer.app_run_port, new_port);
+        }
+    }
+
+    Ok(())
+}
+
+/// 在后台启动 APP 端口上的代理，并等待代理开始监听端口。
+/// 
+/// 代理的进程信息记录在 APP 端口对应的记录文件中，所以可以像 APP 一样使用 `stop_jar` 停止。
+fn start_proxy(installer: &Installer) -> Result<(), InstallerError> {
+    let log = app_log::open_proxy(&installer.app_name, installer.app_run_port)?;
+    let mut child = proxy::spawn(installer.app_run_port, installer.proxy_hold_timeout, log).map_err(|e| {
+        InstallerError::Process(format!("在 {} 端口上启动代理失败，{}", installer.app_run_port, e))
+    })?;
+
+    let started = Instant::now();
+    while process::get_port_owner(installer.app_run_port) != PortOwner::Process(child.id()) {
+        if let Ok(Some(status)) = child.try_wait() {
+            return Err(InstallerError::Process(format!("{} 端口上的代理已退出，{}", installer.app_run_port, status)));
+        }
+        if started.elapsed() >= PROXY_START_TIMEOUT {
+            return Err(InstallerError::Process(format!("{} 端口上的代理没有在 {} 秒内启动", installer.app_run_port, PROXY_START_TIMEOUT.as_secs())));
         }
+        thread::sleep(Duration::from_millis(100));
     }
+
+    if let Err(e) = RunRecord::new(child.id()).save(installer.app_run_port) {
+        progressln!("> [WARN]: 保存进程 {} 的信息失败：{}", child.id(), e);
+    }
+    Ok(())
 }
 
 /// 确认 JDK 是否已成功解压到 prod 文件夹。
@@ -480,61 +1455,59 @@ fn stop_jar(run_port: u32) {
 /// 有两条检查路径，一是先检查下载文件夹，然后检查 prod 文件夹；
 /// 二是先检查 prod 文件夹，然后检查下载文件夹。
 /// 这里选用第一条检查路径。
-fn ensure_jdk_exists(
+/// 
+/// 如果 `jdk_file_sha256` 有值，则在解压前校验 JDK 压缩文件的 SHA-256 校验和，
+/// 校验失败时会删除压缩文件并返回错误。
+pub(crate) fn ensure_jdk_exists(
     root_url: &str,
     jdk_name: &str,
     jdk_version: &str,
-    jdk_file_name: &str) -> Result<PathBuf, Box<dyn std::error::Error>>  {
+    jdk_file_name: &str,
+    jdk_file_sha256: Option<&str>) -> Result<PathBuf, InstallerError>  {
     // 1. 检查 JDK 是否已下载
     let download_jdk_path = Path::new(config::ROOT_PATH_APP)
         .join(jdk_name)
         .join(jdk_version)
         .join(jdk_file_name);
+    // 多个 installer 使用同一个 JDK 时，只下载和解压一次
+    let download_lock = DOWNLOAD_LOCKS.get(&download_jdk_path);
+    let _guard = download_lock.lock().unwrap_or_else(PoisonError::into_inner);
     if !download_jdk_path.exists() {
         client::download(
             root_url,
             jdk_name,
             jdk_version,
-            jdk_file_name);
+            jdk_file_name)?;
     } else {
-        println!("> [INFO]: 文件已存在");
+        progressln!("> [INFO]: 文件已存在");
     }
 
     // 2. 检查 prod 中是否有 JDK
     let prod_jdk_path = &get_prod_jdk_path(jdk_name, jdk_version);
-    // 当已解压的文件夹已存在时，校验是否全部解压
-    // 如果没有全部解压完成，则删除之前解压的文件，重新解压
-    let zipping_status_file_name = &format!("{}_zipping", jdk_file_name);
-    let zipping_status_path = &prod_jdk_path.parent().unwrap().join(zipping_status_file_name);
+    let prod_jdk_parent_path = &get_prod_jdk_parent_path(jdk_name, jdk_version);
+    // 之前的版本在解压前会创建标识解压状态的文件，如果此文件存在，说明没有全部解压完成，
+    // 则删除之前解压的文件，重新解压
+    let zipping_status_path = &get_zipping_status_path(jdk_name, jdk_version, jdk_file_name);
+    if zipping_status_path.exists() {
+        fs::remove_dir_all(prod_jdk_parent_path)?;
+    }
+    // 先解压到临时文件夹，解压完成后才重命名，所以文件夹存在就说明已全部解压
     if prod_jdk_path.exists() {
-        if zipping_status_path.exists() {
-            // 文件未完全解压完成
-            fs::remove_dir_all(prod_jdk_path)?;
-            fs::remove_file(zipping_status_path)?;
-        } else {
-            // 确认文件解压完成
-            println!("> [INFO]: 文件已解压");
-            return Ok(prod_jdk_path.to_path_buf());
-        }
+        progressln!("> [INFO]: 文件已解压");
+        return Ok(prod_jdk_path.to_path_buf());
     }
 
-    print!("> [INFO]: 正在解压 JDK...");
-    // 强制输出
-    io::stdout().flush()?;
+    verify_downloaded_file(&download_jdk_path, jdk_file_sha256)?;
+
+    progress!("> [INFO]: 正在解压 JDK...");
     let started = Instant::now();
-    // 文件解压前，创建一个标识解压状态的文件
-    fs::create_dir_all(&prod_jdk_path.parent().unwrap())?;
-    File::create(zipping_status_path)?;
 
-    zip::unzip_to(
-        download_jdk_path.to_str().unwrap(), 
-        prod_jdk_path.parent().unwrap().to_str().unwrap()
+    zip::unzip_to_atomically(
+        &download_jdk_path.to_string_lossy(), 
+        &prod_jdk_parent_path.to_string_lossy()
     )?;
 
-    // 文件解压完成后，删除标识解压状态的文件
-    fs::remove_file(zipping_status_path)?;
-
-    println!("完成！耗时 {}", HumanDuration(started.elapsed()));
+    progressln!("完成！耗时 {}", HumanDuration(started.elapsed()));
 
     Ok(prod_jdk_path.to_path_buf())
 }
@@ -544,25 +1517,31 @@ fn ensure_jdk_exists(
 /// 有两条检查路径，一是先检查下载文件夹，然后检查 prod 文件夹；
 /// 二是先检查 prod 文件夹，然后检查下载文件夹。
 /// 这里选用第一条检查路径。
-fn ensure_spring_boot_jar_exists (
+/// 
+/// 如果 `app_file_sha256` 有值，则在复制前校验 Spring Boot Jar 的 SHA-256 校验和，
+/// 校验失败时会删除已下载的文件并返回错误。
+pub(crate) fn ensure_spring_boot_jar_exists (
     root_url: &str,
     app_name: &str,
     app_version: &str,
-    app_file_name: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    app_file_name: &str,
+    app_file_sha256: Option<&str>) -> Result<PathBuf, InstallerError> {
     
     // 1. 检查 Spring Boot Jar 是否已下载
     let download_spring_boot_jar_path = Path::new(config::ROOT_PATH_APP)
         .join(app_name)
         .join(app_version)
         .join(app_file_name);
+    let download_lock = DOWNLOAD_LOCKS.get(&download_spring_boot_jar_path);
+    let _guard = download_lock.lock().unwrap_or_else(PoisonError::into_inner);
     if !download_spring_boot_jar_path.exists() {
         client::download(
             root_url,
             app_name,
             app_version,
-            app_file_name);
+            app_file_name)?;
     } else {
-        println!("> [INFO]: 文件已存在");
+        progressln!("> [INFO]: 文件已存在");
     }
     // 2. 检查 prod 下是否有 Spring Boot Jar
     let prod_spring_boot_jar_path = get_prod_spring_boot_jar_path(
@@ -570,14 +1549,36 @@ fn ensure_spring_boot_jar_exists (
         app_version, 
         app_file_name);
     if !prod_spring_boot_jar_path.exists() {
+        verify_downloaded_file(&download_spring_boot_jar_path, app_file_sha256)?;
         // 复制文件
-        fs::create_dir_all(prod_spring_boot_jar_path.parent().unwrap())?;
+        fs::create_dir_all(Path::new(config::ROOT_PATH_PROD).join(app_name).join(app_version))?;
         fs::copy(download_spring_boot_jar_path, &prod_spring_boot_jar_path)?;
     }
 
     Ok(prod_spring_boot_jar_path)
 }
 
+/// 校验下载的文件。
+/// 
+/// 如果文件不存在，说明下载失败；如果 Block Lang 平台提供了 SHA-256 校验和，则校验文件的完整性。
+fn verify_downloaded_file(file_path: &Path, expected_sha256: Option<&str>) -> Result<(), InstallerError> {
+    if !file_path.exists() {
+        return Err(InstallerError::NotFound(format!("文件 {} 下载失败", file_path.display())));
+    }
+
+    if let Some(expected) = expected_sha256 {
+        progress!("> [INFO]: 正在校验文件...");
+        checksum::verify_sha256(file_path, expected).map_err(|e| {
+            progressln!("失败");
+            progressln!("> [ERROR]: {}", e);
+            e
+        })?;
+        progressln!("完成");
+    }
+
+    Ok(())
+}
+
 /// 获取 prod 文件夹中 Spring boot jar 的路径。
 fn get_prod_spring_boot_jar_path(app_name: &str,
     app_version: &str,
@@ -590,10 +1591,59 @@ fn get_prod_spring_boot_jar_path(app_name: &str,
 
 /// 获取 prod 文件夹中 JDK 的路径。
 fn get_prod_jdk_path(jdk_name: &str,
+    jdk_version: &str) -> PathBuf {
+    get_prod_jdk_parent_path(jdk_name, jdk_version)
+        // 注意，因为 jdk 的命名规范是 jdk-11.0.1
+        .join(format!("jdk-{}", jdk_version))
+}
+
+/// 启动 installer 时清理之前解压 JDK 时异常退出留下的临时文件夹，清理失败不影响执行命令
+pub fn clean_up_stale_temp_dirs() {
+    if let Err(e) = remove_stale_jdk_temp_dirs() {
+        progressln!("> [WARN]: 清理临时文件夹失败，{}", e);
+    }
+}
+
+/// 删除 prod 文件夹中解压 JDK 时留下的临时文件夹。
+/// 
+/// 如果 installer 在解压 JDK 的过程中异常退出，会留下解压了一部分的临时文件夹。
+/// 正在解压的其他 installer 的临时文件夹不会被删除。
+fn remove_stale_jdk_temp_dirs() -> Result<(), InstallerError> {
+    let prod_path = Path::new(config::ROOT_PATH_PROD);
+    if !prod_path.is_dir() {
+        return Ok(());
+    }
+
+    for entry in fs::read_dir(prod_path)? {
+        for removed in zip::remove_stale_temp_dirs(&entry?.path())? {
+            progressln!("> [INFO]: 已删除没有解压完成的临时文件夹 {}", removed.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// 判断 `prod` 中的 JDK 是否已全部解压。
+/// 
+/// JDK 先解压到临时文件夹，解压完成后才重命名，所以文件夹存在就说明已全部解压；
+/// 但之前的版本直接解压到 `prod` 中，此时还需确认没有标识解压状态的文件。
+fn is_jdk_extracted(jdk_name: &str, jdk_version: &str, jdk_file_name: &str) -> bool {
+    get_prod_jdk_path(jdk_name, jdk_version).exists()
+        && !get_zipping_status_path(jdk_name, jdk_version, jdk_file_name).exists()
+}
+
+/// 之前的版本在解压 JDK 时创建的标识解压状态的文件
+fn get_zipping_status_path(jdk_name: &str,
+    jdk_version: &str,
+    jdk_file_name: &str) -> PathBuf {
+    get_prod_jdk_parent_path(jdk_name, jdk_version)
+        .join(format!("{}_zipping", jdk_file_name))
+}
+
+/// 获取 prod 文件夹中存放 JDK 解压文件的路径。
+fn get_prod_jdk_parent_path(jdk_name: &str,
     jdk_version: &str) -> PathBuf {
     Path::new(config::ROOT_PATH_PROD)
         .join(jdk_name)
         .join(jdk_version)
-        // 注意，因为 jdk 的命名规范是 jdk-11.0.1
-        .join(format!("jdk-{}", jdk_version))
 }
\ No newline at end of file
@@ -3,8 +3,38 @@
 
 pub const ROOT_PATH_APP: &str = "apps";
 pub const ROOT_PATH_PROD: &str = "prod";
+/// 存放 installer 启动的进程信息
+pub const ROOT_PATH_RUN: &str = "run";
+/// 存放 APP 的标准输出和标准错误
+pub const ROOT_PATH_LOGS: &str = "logs";
+/// 存放每个 installer 的 Spring Boot 配置文件
+pub const ROOT_PATH_CONF: &str = "conf";
+/// 存放注销 installer 时备份的文件
+pub const ROOT_PATH_BACKUP: &str = "backup";
 pub const INSTALLER_CONFIG_FILE_NAME: &str = "installer_config.toml";
 pub const DOWNLOAD_CONFIG_FILE_NAME: &str = "download_config.toml";
+/// Installer REST 服务的默认端口
+pub const DEFAULT_SERVER_PORT: u32 = 9090;
+/// Installer REST 服务默认只监听本机地址，需要从其他主机访问时再通过 `--host` 选项指定
+pub const DEFAULT_SERVER_HOST: &str = "127.0.0.1";
+/// 停止 APP 时，等待 APP 正常退出的默认秒数，超时后强制关闭
+pub const DEFAULT_GRACE_PERIOD: u64 = 30;
+/// 每个 installer 最多保留的历史版本数
+pub const MAX_HISTORY_LEN: usize = 5;
+/// HTTP 健康检查的默认路径
+pub const DEFAULT_HEALTH_CHECK_PATH: &str = "/actuator/health";
+/// 等待 APP 通过健康检查的默认秒数
+pub const DEFAULT_HEALTH_CHECK_TIMEOUT: u64 = 60;
+/// APP 日志文件超过此大小后轮转，默认为 10 MB
+pub const MAX_LOG_FILE_SIZE: u64 = 10 * 1024 * 1024;
+/// 每个 APP 最多保留的历史日志文件数
+pub const MAX_LOG_FILES: usize = 5;
+/// 后端 APP 没有运行时，代理保持连接并等待 APP 启动的默认秒数，超时后拒绝连接
+pub const DEFAULT_PROXY_HOLD_TIMEOUT: u64 = 30;
+/// 解压文件时，允许解压出的最大总字节数，默认为 2 GB
+pub const MAX_EXTRACT_SIZE: u64 = 2 * 1024 * 1024 * 1024;
+/// 解压文件时，允许的最大文件数
+pub const MAX_EXTRACT_ENTRIES: usize = 100_000;
 
 // 存放 REST API 区
 pub const REST_API_INSTALLERS: &str = "installers";
@@ -0,0 +1,412 @@
+use std::process::Child;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::command;
+use crate::error::InstallerError;
+use crate::installer_config::{Installer, InstallerConfig};
+use crate::run_record::{PortLock, RunRecord};
+use crate::util::process;
+
+/// 检查 APP 运行状态的时间间隔
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// 第一次重启前的等待时间，之后每次重启都翻倍
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+/// 两次重启之间的最长等待时间
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// APP 持续运行超过此时间后，认为已稳定运行，重置等待时间
+const STABLE_DURATION: Duration = Duration::from_secs(60);
+
+/// 重启 APP 前的等待时间，从 `MIN_BACKOFF` 开始，每次重启都翻倍，最长为 `MAX_BACKOFF`
+#[derive(Debug, PartialEq)]
+struct Backoff {
+    current: Duration,
+}
+
+impl Backoff {
+
+    fn new() -> Self {
+        Backoff { current: MIN_BACKOFF }
+    }
+
+    /// 返回本次重启前要等待的时间，并将下一次的等待时间翻倍
+    fn next(&mut self) -> Duration {
+        let current = self.current;
+        self.current = (self.current * 2).min(MAX_BACKOFF);
+        current
+    }
+
+    /// APP 已运行 `uptime`，如果超过 `STABLE_DURATION`，则认为已稳定运行，重置等待时间
+    fn reset_if_stable(&mut self, uptime: Duration) {
+        if uptime >= STABLE_DURATION {
+            self.current = MIN_BACKOFF;
+        }
+    }
+}
+
+/// 守护进程中 APP 的状态
+enum AppState {
+    /// 守护进程启动的 APP 正在运行
+    Running(Child),
+    /// 其他命令(如 `run`、`update`)启动的 APP 正在运行，根据 run 文件夹中的进程记录检查是否还在运行
+    Adopted(u32),
+    /// APP 已被 `stop`、`update` 等命令有意停止，不重启，等到其他命令重新运行 APP 后再继续托管
+    Stopped,
+    /// APP 意外退出或启动失败，到时间后重启。`pid` 是意外退出的进程 id，启动失败时为 `None`
+    Restarting { restart_at: Instant, pid: Option<u32> },
+}
+
+/// 由守护进程托管的 APP
+struct SupervisedApp {
+    app_run_port: u32,
+    /// JVM 的运行端口，使用代理时是内部端口，否则是 `app_run_port`
+    run_port: u32,
+    state: AppState,
+    started_at: Instant,
+    backoff: Backoff,
+}
+
+/// 以守护进程的方式运行 `installer_config.toml` 中注册的所有 APP。
+///
+/// 守护进程会持有每个 APP 的进程，当 APP 意外退出后，会等待一段时间后重启 APP，
+/// 等待时间从 1 秒开始，每次重启翻倍，最长为 60 秒。APP 稳定运行 60 秒后重置等待时间。
+/// 每次重启后都会在 `installer_config.toml` 中累加 `restart_count`。
+/// APP 运行过程中，日志文件超过最大值时会轮转日志文件。
+///
+/// 守护进程根据 run 文件夹中的进程记录区分 APP 是意外退出还是被有意停止：
+/// `stop`、`update` 等命令停止 APP 前会先删除进程记录，所以进程退出后，如果进程记录依然指向此进程，
+/// 则是意外退出，需要重启；否则是被有意停止，守护进程不重启，等到其他命令重新运行 APP 并写入新的进程记录后，
+/// 再继续托管新进程。
+///
+/// 注意：
+///
+/// 1. 如果守护进程启动时 APP 已由 installer 启动，则直接托管此进程；如果端口被其他进程占用，则等到端口释放后再启动 APP；
+/// 2. 重启前会重新读取 `installer_config.toml`，因此会使用最新升级的版本，
+///    如果 installer 已被注销，则不再托管该 APP；
+/// 3. 如果 installer 使用代理，则 APP 运行在内部端口上，守护进程重启 APP 期间，代理会保持新建立的连接。
+pub fn start() -> Result<(), InstallerError> {
+    println!("开始以守护进程的方式运行所有项目");
+
+    let installer_config = InstallerConfig::new()?;
+    let installers = &installer_config.get_data().installers;
+    if installers.is_empty() {
+        println!("> [INFO]: 没有找到 installer。请先执行 `blocklang-installer register` 注册 installer");
+        return Ok(());
+    }
+
+    let mut apps: Vec<SupervisedApp> = installers.iter().map(start_app).collect();
+
+    println!();
+    println!("> [INFO]: 共托管 {} 个项目，按 CTRL + C 退出", apps.len());
+
+    loop {
+        thread::sleep(CHECK_INTERVAL);
+
+        // 每次检查前都重新读取配置信息，以使用最新的版本和运行端口
+        let installer_config = match InstallerConfig::new() {
+            Ok(installer_config) => installer_config,
+            Err(e) => {
+                println!("> [ERROR]: 读取 installer 配置信息失败，{}", e);
+                continue;
+            }
+        };
+        apps.retain(|app| {
+            let registered = installer_config.get_by_port(app.app_run_port).is_some();
+            if !registered {
+                println!("> [INFO]: 端口 {} 上的 installer 已注销，不再托管此项目", app.app_run_port);
+            }
+            registered
+        });
+        if apps.is_empty() {
+            println!("> [INFO]: 没有需要托管的项目，守护进程退出");
+            return Ok(());
+        }
+
+        for app in apps.iter_mut() {
+            check_app(app, &installer_config);
+        }
+    }
+}
+
+/// 守护进程启动时托管 APP。
+///
+/// 如果 APP 已由 installer 启动，则直接托管；否则启动 APP，端口被占用或启动失败时，稍后再重试。
+fn start_app(installer: &Installer) -> SupervisedApp {
+    println!();
+    println!("===== 开始启动 {} 端口上的项目 {}-{} =====",
+        installer.app_run_port,
+        installer.app_name,
+        installer.app_version);
+
+    // 使用代理时，检查内部端口，代理在 APP 重启期间会保持连接
+    let run_port = command::get_run_port(installer);
+    let mut app = SupervisedApp {
+        app_run_port: installer.app_run_port,
+        run_port,
+        state: AppState::Stopped,
+        started_at: Instant::now(),
+        backoff: Backoff::new(),
+    };
+
+    if let Some(pid) = find_running_app(run_port) {
+        println!("> [INFO]: 项目已运行在进程 {} 上，开始托管此进程", pid);
+        app.state = AppState::Adopted(pid);
+    } else if !process::get_port_owner(run_port).is_free() {
+        println!("> [WARN]: 端口 {} 已被其他进程占用，该进程不是 installer 启动的，等端口释放后再启动项目", run_port);
+        app.state = AppState::Restarting { restart_at: Instant::now() + app.backoff.next(), pid: None };
+    } else {
+        match spawn_app(installer) {
+            Ok(child) => app.state = AppState::Running(child),
+            Err(e) => {
+                let backoff = app.backoff.next();
+                println!("> [ERROR]: 启动失败，{}，将在 {} 秒后重试", e, backoff.as_secs());
+                app.state = AppState::Restarting { restart_at: Instant::now() + backoff, pid: None };
+            }
+        }
+    }
+
+    app
+}
+
+/// 检查 APP 的运行状态，如果 APP 意外退出，则按计划重启 APP
+fn check_app(app: &mut SupervisedApp, installer_config: &InstallerConfig) {
+    if follow_blue_green_switch(app, installer_config) {
+        return;
+    }
+
+    let exited_pid = match &mut app.state {
+        AppState::Running(child) => match child.try_wait() {
+            Ok(None) => {
+                app.on_running();
+                return;
+            },
+            Ok(Some(status)) => {
+                println!("> [INFO]: 端口 {} 上的项目已退出，{}", app.app_run_port, status);
+                child.id()
+            },
+            Err(e) => {
+                println!("> [WARN]: 无法获取端口 {} 上项目的运行状态，{}", app.app_run_port, e);
+                child.id()
+            },
+        },
+        AppState::Adopted(pid) => {
+            let pid = *pid;
+            if find_running_app(app.run_port) == Some(pid) {
+                app.on_running();
+                return;
+            }
+            println!("> [INFO]: 端口 {} 上的项目已退出", app.app_run_port);
+            pid
+        },
+        AppState::Stopped => {
+            // 等待其他命令重新运行 APP，使用代理时蓝绿部署会切换内部端口
+            if let Some(installer) = installer_config.get_by_port(app.app_run_port) {
+                let run_port = command::get_run_port(installer);
+                if let Some(pid) = find_running_app(run_port) {
+                    println!("> [INFO]: 端口 {} 上的项目已运行在进程 {} 上，继续托管此进程", app.app_run_port, pid);
+                    app.adopt(run_port, pid);
+                }
+            }
+            return;
+        },
+        AppState::Restarting { restart_at, pid } => {
+            if Instant::now() >= *restart_at {
+                let pid = *pid;
+                restart_app(app, pid, installer_config);
+            }
+            return;
+        },
+    };
+
+    // stop、update 等命令停止 APP 前会先删除或替换进程记录
+    if is_stopped_on_purpose(app.run_port, Some(exited_pid)) {
+        println!("> [INFO]: 端口 {} 上的项目已被有意停止，不再重启，等项目重新运行后继续托管", app.app_run_port);
+        app.state = AppState::Stopped;
+        return;
+    }
+
+    let backoff = app.backoff.next();
+    println!("> [WARN]: 端口 {} 上的项目意外退出，将在 {} 秒后重启", app.app_run_port, backoff.as_secs());
+    app.state = AppState::Restarting { restart_at: Instant::now() + backoff, pid: Some(exited_pid) };
+}
+
+/// 蓝绿部署已将代理切换到另一个内部端口时，改为托管新端口上的 APP，并返回 `true`。
+///
+/// 蓝绿部署在切换后停止旧版本，停止失败时旧版本依然在运行，所以不能只依赖旧版本退出来发现切换。
+fn follow_blue_green_switch(app: &mut SupervisedApp, installer_config: &InstallerConfig) -> bool {
+    if let AppState::Stopped | AppState::Restarting { .. } = app.state {
+        return false;
+    }
+
+    let run_port = match installer_config.get_by_port(app.app_run_port) {
+        Some(installer) => command::get_run_port(installer),
+        None => return false,
+    };
+    if run_port == app.run_port {
+        return false;
+    }
+
+    let pid = match find_running_app(run_port) {
+        Some(pid) => pid,
+        None => return false,
+    };
+
+    println!("> [INFO]: 端口 {} 上的项目已切换到内部端口 {}，开始托管进程 {}", app.app_run_port, run_port, pid);
+    // 由守护进程启动的旧版本退出时需要回收，否则会成为僵尸进程
+    if let AppState::Running(mut child) = std::mem::replace(&mut app.state, AppState::Stopped) {
+        thread::spawn(move || child.wait());
+    }
+    app.adopt(run_port, pid);
+    true
+}
+
+fn restart_app(app: &mut SupervisedApp, exited_pid: Option<u32>, installer_config: &InstallerConfig) {
+    let installer = match installer_config.get_by_port(app.app_run_port) {
+        Some(installer) => installer,
+        None => return,
+    };
+
+    // 等待重启期间，可能已执行了 `run`、`update` 或 `stop` 命令
+    let run_port = command::get_run_port(installer);
+    if let Some(pid) = find_running_app(run_port) {
+        println!("> [INFO]: 端口 {} 上的项目已运行在进程 {} 上，继续托管此进程", app.app_run_port, pid);
+        app.adopt(run_port, pid);
+        return;
+    }
+    if exited_pid.is_some() && is_stopped_on_purpose(app.run_port, exited_pid) {
+        println!("> [INFO]: 端口 {} 上的项目已被有意停止，不再重启，等项目重新运行后继续托管", app.app_run_port);
+        app.state = AppState::Stopped;
+        return;
+    }
+
+    // `update`、`watch` 等命令正在操作此 APP 时，等命令执行完成后再检查
+    let _lock = match PortLock::try_acquire(app.app_run_port) {
+        Ok(Some(lock)) => Some(lock),
+        Ok(None) => {
+            println!("> [INFO]: 端口 {} 上的项目正在被其他命令操作，将在 {} 秒后重试", app.app_run_port, CHECK_INTERVAL.as_secs());
+            app.state = AppState::Restarting { restart_at: Instant::now() + CHECK_INTERVAL, pid: exited_pid };
+            return;
+        },
+        Err(e) => {
+            println!("> [WARN]: 获取端口 {} 上的操作锁失败，{}", app.app_run_port, e);
+            None
+        },
+    };
+
+    // 如果端口已被其他进程占用，则暂不重启
+    let owner = process::get_port_owner(run_port);
+    if !owner.is_free() {
+        let backoff = app.backoff.next();
+        match owner.pid() {
+            Some(pid) => println!("> [INFO]: 端口 {} 已被进程 {} 占用，将在 {} 秒后重试", run_port, pid, backoff.as_secs()),
+            None => println!("> [INFO]: 端口 {} 已被占用，将在 {} 秒后重试", run_port, backoff.as_secs()),
+        }
+        app.state = AppState::Restarting { restart_at: Instant::now() + backoff, pid: exited_pid };
+        return;
+    }
+
+    println!("> [INFO]: 开始重启端口 {} 上的项目 {}-{}",
+        installer.app_run_port,
+        installer.app_name,
+        installer.app_version);
+
+    match spawn_app(installer) {
+        Ok(child) => {
+            app.run_port = run_port;
+            app.state = AppState::Running(child);
+            app.started_at = Instant::now();
+
+            // 持有文件锁重新读取配置文件后再累加重启次数，防止覆盖检查期间其他命令的修改
+            match InstallerConfig::modify(|installer_config| installer_config.increase_restart_count(app.app_run_port)) {
+                Ok(restart_count) => println!("> [INFO]: 重启完成，共重启 {} 次", restart_count),
+                Err(e) => println!("> [WARN]: 重启完成，但保存重启次数失败，{}", e),
+            }
+        },
+        Err(e) => {
+            let backoff = app.backoff.next();
+            println!("> [ERROR]: 重启失败，{}，将在 {} 秒后重试", e, backoff.as_secs());
+            app.state = AppState::Restarting { restart_at: Instant::now() + backoff, pid: exited_pid };
+        }
+    }
+}
+
+impl SupervisedApp {
+
+    /// APP 正在运行时，稳定运行后重置等待时间
+    fn on_running(&mut self) {
+        self.backoff.reset_if_stable(self.started_at.elapsed());
+    }
+
+    /// 托管其他命令启动的 APP 进程
+    fn adopt(&mut self, run_port: u32, pid: u32) {
+        self.run_port = run_port;
+        self.state = AppState::Adopted(pid);
+        self.started_at = Instant::now();
+    }
+}
+
+/// 根据 run 文件夹中的进程记录，查找 installer 在 `run_port` 端口上启动且依然在运行的 APP 进程
+fn find_running_app(run_port: u32) -> Option<u32> {
+    RunRecord::load(run_port)
+        .filter(|record| record.matches(record.pid))
+        .map(|record| record.pid)
+}
+
+/// 进程 `pid` 退出后，如果 `run_port` 上的进程记录已被删除或指向其他进程，说明 APP 是被有意停止的
+fn is_stopped_on_purpose(run_port: u32, pid: Option<u32>) -> bool {
+    RunRecord::load(run_port).map(|record| record.pid) != pid
+}
+
+/// 确保 Spring Boot jar 和 JDK 已安装到 prod 文件夹，然后运行 APP
+fn spawn_app(installer: &Installer) -> Result<Child, InstallerError> {
+    let prod_spring_boot_jar_path = command::ensure_spring_boot_jar_exists(
+        &installer.url,
+        &installer.app_name,
+        &installer.app_version,
+        &installer.app_file_name,
+        installer.app_file_sha256.as_deref())?;
+
+    let prod_jdk_path = command::ensure_jdk_exists(
+        &installer.url,
+        &installer.jdk_name,
+        &installer.jdk_version,
+        &installer.jdk_file_name,
+        installer.jdk_file_sha256.as_deref())?;
+
+    let child = command::start_jar(installer, &prod_spring_boot_jar_path, &prod_jdk_path)?;
+    println!("> [INFO]: 项目已运行在进程 {} 上", child.id());
+
+    Ok(child)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::time::Duration;
+    use super::{Backoff, MAX_BACKOFF, MIN_BACKOFF, STABLE_DURATION};
+
+    #[test]
+    fn backoff_double_until_max() {
+        let mut backoff = Backoff::new();
+        let schedule: Vec<u64> = (0..9).map(|_| backoff.next().as_secs()).collect();
+
+        assert_eq!(vec![1, 2, 4, 8, 16, 32, 60, 60, 60], schedule);
+        assert_eq!(MAX_BACKOFF, backoff.next());
+    }
+
+    #[test]
+    fn backoff_reset_after_stable_run() {
+        let mut backoff = Backoff::new();
+        backoff.next();
+        backoff.next();
+        backoff.next();
+
+        // 没有稳定运行时，不重置等待时间
+        backoff.reset_if_stable(STABLE_DURATION - Duration::from_secs(1));
+        assert_eq!(Duration::from_secs(8), backoff.next());
+
+        backoff.reset_if_stable(STABLE_DURATION);
+        assert_eq!(MIN_BACKOFF, backoff.next());
+        assert_eq!(Duration::from_secs(2), backoff.next());
+    }
+}
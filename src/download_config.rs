@@ -1,9 +1,16 @@
 use crate::config::DOWNLOAD_CONFIG_FILE_NAME;
+use crate::error::InstallerError;
+use crate::util::file;
 use std::fs::File;
-use std::io::prelude::*;
+use std::io::{self, prelude::*};
+use std::path::Path;
+use std::sync::{Mutex, PoisonError};
 use serde_derive::{Deserialize, Serialize};
 use toml;
 
+/// 并发下载多个文件时，防止同时修改下载配置文件，导致覆盖其他线程的修改
+static DOWNLOAD_CONFIG_LOCK: Mutex<()> = Mutex::new(());
+
 /// 记录断点续传的配置信息
 pub struct DownloadConfig {
     file_name: String,
@@ -23,41 +30,44 @@ pub struct FileMd5Info {
     pub md5: String,
 }
 
-impl Default for DownloadConfig {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
 impl DownloadConfig {
     
-    pub fn new() -> Self {
+    pub fn new() -> Result<Self, InstallerError> {
         Self::from(DOWNLOAD_CONFIG_FILE_NAME)
     }
 
-    pub fn from(file_name: &str) -> Self {
-        File::open(file_name).map(|mut file| {
+    /// 持有全局锁读取默认的配置文件，并使用 `f` 修改配置，
+    /// 保证并发下载时读取和保存之间不会有其他线程修改配置文件。
+    pub fn modify<F>(f: F) -> Result<(), InstallerError> where F: FnOnce(&mut DownloadConfig) -> Result<(), InstallerError> {
+        Self::modify_in(DOWNLOAD_CONFIG_FILE_NAME, f)
+    }
+
+    fn modify_in<F>(file_name: &str, f: F) -> Result<(), InstallerError> where F: FnOnce(&mut DownloadConfig) -> Result<(), InstallerError> {
+        let _guard = DOWNLOAD_CONFIG_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut download_config = Self::from(file_name)?;
+        f(&mut download_config)
+    }
+
+    /// 读取配置文件，如果文件不存在或者不是预期的 toml 格式，则创建默认的配置文件
+    pub fn from(file_name: &str) -> Result<Self, InstallerError> {
+        let content = File::open(file_name).map(|mut file| {
             // 如果文件存在，则读取文件内容
             let mut content = String::new();
             file.read_to_string(&mut content).unwrap_or(0);
             content
-        }).map(|content| {
-            toml::from_str::<DownloadData>(&content).unwrap_or_else(|_| {
-                // 不是预期的 toml 格式，则创建默认设置
-                Self::create_default_config(file_name).data
-            })
-        }).map(|data| {
-                DownloadConfig {
+        });
+
+        match content.map(|content| toml::from_str::<DownloadData>(&content)) {
+            Ok(Ok(data)) => Ok(DownloadConfig {
                 file_name: file_name.to_string(),
                 data,
-            }
-        }).unwrap_or_else(|_|{
-            // 如果文件不存在，则创建一个文件，并返回文件的内容
-            Self::create_default_config(file_name)
-        })
+            }),
+            // 如果文件不存在或不是预期的 toml 格式，则创建默认设置
+            _ => Self::create_default_config(file_name),
+        }
     }
 
-    pub fn put(&mut self, app_name: &str, app_version:  &str, md5_value:  &str) {
+    pub fn put(&mut self, app_name: &str, app_version:  &str, md5_value:  &str) -> Result<(), InstallerError> {
         let file_md5_info = FileMd5Info {
             name: app_name.to_string(),
             version: app_version.to_string(),
@@ -74,7 +84,7 @@ impl DownloadConfig {
 
         files.push(file_md5_info);
 
-        self.save();
+        self.save()
     }
 
     pub fn get(self, app_name: &str, app_version: &str) -> Option<FileMd5Info> {
@@ -83,7 +93,7 @@ impl DownloadConfig {
         files.into_iter().find(|file| file.name == app_name && file.version == app_version)
     }
 
-    pub fn remove(&mut self, app_name: &str, app_version: &str) {
+    pub fn remove(&mut self, app_name: &str, app_version: &str) -> Result<(), InstallerError> {
         let files = &mut self.data.files;
         match files.iter().position(|file| file.name == app_name && file.version == app_version) {
             None => {},
@@ -92,11 +102,11 @@ impl DownloadConfig {
             }
         }
 
-        self.save();
+        self.save()
     }
 
     /// 创建一个默认的配置
-    fn create_default_config(file_name: &str) -> Self {
+    fn create_default_config(file_name: &str) -> Result<Self, InstallerError> {
         let data = DownloadData {
             files: Vec::<FileMd5Info>::new(),
         };
@@ -105,16 +115,18 @@ impl DownloadConfig {
             data,
         };
 
-        config.save();
+        config.save()?;
 
-        config
+        Ok(config)
     }
 
-    fn save(&self) {
-        let toml_content = toml::to_vec(&self.data).unwrap();
+    /// 先写入临时文件再重命名，防止其他进程读到写了一半的文件
+    fn save(&self) -> Result<(), InstallerError> {
+        let toml_content = toml::to_vec(&self.data)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
 
-        let mut file = File::create(&self.file_name).expect("failed to create download_config.toml file");
-        file.write_all(toml_content.as_slice()).expect("failed to save download_config.toml content");
+        file::write_atomically(Path::new(&self.file_name), &toml_content)?;
+        Ok(())
     }
 }
 
@@ -124,6 +136,7 @@ mod tests {
     use std::path::Path;
     use std::fs::{self, File};
     use std::io::prelude::*;
+    use std::thread;
     use super::{DownloadConfig, DownloadData, FileMd5Info};
 
     #[test]
@@ -132,7 +145,7 @@ mod tests {
 
         assert!(!Path::new(file_name).exists());
 
-        let download_config = DownloadConfig::from(file_name);
+        let download_config = DownloadConfig::from(file_name)?;
         assert_eq!(file_name, download_config.file_name);
         assert_eq!(DownloadData {
             files: Vec::<FileMd5Info>::new(),
@@ -160,7 +173,7 @@ mod tests {
 
         assert!(Path::new(file_name).exists());
 
-        let download_config = DownloadConfig::from(file_name);
+        let download_config = DownloadConfig::from(file_name)?;
         assert_eq!(file_name, download_config.file_name);
         assert_eq!(DownloadData {
             files: Vec::<FileMd5Info>::new(),
@@ -193,7 +206,7 @@ mod tests {
 
         assert!(Path::new(file_name).exists());
 
-        let download_config = DownloadConfig::from(file_name);
+        let download_config = DownloadConfig::from(file_name)?;
         assert_eq!(file_name, download_config.file_name);
         assert_eq!(DownloadData {
             files: Vec::<FileMd5Info>::new(),
@@ -228,7 +241,7 @@ mod tests {
 
         assert!(Path::new(file_name).exists());
 
-        let download_config = DownloadConfig::from(file_name);
+        let download_config = DownloadConfig::from(file_name)?;
         assert_eq!(file_name, download_config.file_name);
         assert_eq!(DownloadData {
             files: Vec::<FileMd5Info>::new(),
@@ -252,9 +265,9 @@ mod tests {
     #[test]
     fn put_one_file_success() -> Result<(), Box<dyn std::error::Error>> {
         let config_file_name = "put_one_file_success.toml";
-        let mut download_config = DownloadConfig::from(config_file_name);
+        let mut download_config = DownloadConfig::from(config_file_name)?;
 
-        download_config.put("app_name", "app_version", "md5_value");
+        download_config.put("app_name", "app_version", "md5_value")?;
 
         // 断言存在 toml 文件
         assert!(Path::new(config_file_name).exists());
@@ -277,10 +290,10 @@ mod tests {
     #[test]
     fn put_one_if_exists_then_override() -> Result<(), Box<dyn std::error::Error>> {
         let config_file_name = "put_one_if_exists_then_override.toml";
-        let mut download_config = DownloadConfig::from(config_file_name);
+        let mut download_config = DownloadConfig::from(config_file_name)?;
 
-        download_config.put("app_name", "app_version", "md5_value");
-        download_config.put("app_name", "app_version", "md5_value_1");
+        download_config.put("app_name", "app_version", "md5_value")?;
+        download_config.put("app_name", "app_version", "md5_value_1")?;
 
         // 读取文件中的内容，并比较部分内容
         let mut file = File::open(config_file_name)?;
@@ -311,7 +324,7 @@ mod tests {
         let mut file = File::create(config_file_name).unwrap();
         file.write_all(content).unwrap();
 
-        let download_config = DownloadConfig::from(config_file_name);
+        let download_config = DownloadConfig::from(config_file_name)?;
         let file_md5_info = download_config.get("name_1", "version_1").unwrap();
         assert_eq!("m5d_1", file_md5_info.md5);
 
@@ -332,8 +345,8 @@ mod tests {
         let mut file = File::create(config_file_name).unwrap();
         file.write_all(content).unwrap();
 
-        let mut download_config = DownloadConfig::from(config_file_name);
-        download_config.remove("name_1", "version_1");
+        let mut download_config = DownloadConfig::from(config_file_name)?;
+        download_config.remove("name_1", "version_1")?;
         
         let mut file = File::open(config_file_name)?;
         let mut content = String::new();
@@ -346,4 +359,32 @@ mod tests {
         fs::remove_file(config_file_name)?;
         Ok(())
     }
+
+    #[test]
+    fn modify_concurrently_keep_all_files() -> Result<(), Box<dyn std::error::Error>> {
+        let config_file_name = "modify_concurrently_keep_all_files.toml";
+
+        // 多个线程同时修改时，不能覆盖其他线程的修改
+        thread::scope(|scope| {
+            for index in 0..8 {
+                scope.spawn(move || {
+                    DownloadConfig::modify_in(config_file_name, |download_config| {
+                        download_config.put("name", &format!("version_{}", index), "md5")
+                    }).unwrap();
+                });
+            }
+        });
+
+        let download_config = DownloadConfig::from(config_file_name)?;
+        assert_eq!(8, download_config.data.files.len());
+        // 保存时使用的临时文件已被重命名
+        assert!(fs::read_dir(".")?.filter_map(Result::ok).all(|entry| {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            !(file_name.starts_with(config_file_name) && file_name.ends_with(".tmp"))
+        }));
+
+        // 删除 download_config.toml 文件
+        fs::remove_file(config_file_name)?;
+        Ok(())
+    }
 }
\ No newline at end of file
@@ -0,0 +1,181 @@
+use std::fmt;
+use std::io;
+
+/// installer 命令执行失败时返回的错误。
+///
+/// 每种错误都对应一个进程退出码，脚本可根据退出码判断失败的原因：
+///
+/// | 错误          | 退出码 | 说明                                          |
+/// | ------------ | ----- | -------------------------------------------- |
+/// | `Network`    | 2     | 无法访问 Block Lang 平台，或返回的数据无效          |
+/// | `Validation` | 3     | 未通过数据有效性校验，如 Block Lang 平台返回 422    |
+/// | `NotFound`   | 4     | 没有找到 installer 或要下载的文件                 |
+/// | `Io`         | 5     | 读写文件出错                                   |
+/// | `Extraction` | 6     | 解压文件出错，或下载的文件未通过校验                 |
+/// | `Process`    | 7     | 启动或停止 APP 出错，或端口已被其他进程占用          |
+#[derive(Debug)]
+pub enum InstallerError {
+    Network(String),
+    Validation(String),
+    NotFound(String),
+    Io(io::Error),
+    Extraction(String),
+    Process(String),
+}
+
+impl InstallerError {
+
+    /// 获取错误对应的进程退出码
+    pub fn exit_code(&self) -> i32 {
+        match *self {
+            InstallerError::Network(_) => 2,
+            InstallerError::Validation(_) => 3,
+            InstallerError::NotFound(_) => 4,
+            InstallerError::Io(_) => 5,
+            InstallerError::Extraction(_) => 6,
+            InstallerError::Process(_) => 7,
+        }
+    }
+}
+
+impl fmt::Display for InstallerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            InstallerError::Network(ref msg) => write!(f, "网络错误：{}", msg),
+            InstallerError::Validation(ref msg) => write!(f, "校验失败：{}", msg),
+            InstallerError::NotFound(ref msg) => write!(f, "没有找到：{}", msg),
+            InstallerError::Io(ref e) => write!(f, "读写文件出错：{}", e),
+            InstallerError::Extraction(ref msg) => write!(f, "解压文件出错：{}", msg),
+            InstallerError::Process(ref msg) => write!(f, "进程错误：{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for InstallerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match *self {
+            InstallerError::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// 解压文件时返回的错误
+#[derive(Debug)]
+pub enum ExtractError {
+    Io(io::Error),
+    Archive(String),
+    /// 文件的路径不在目标文件夹中，如 `../../etc/passwd`
+    UnsafePath(String),
+    /// 符号链接或硬链接指向目标文件夹之外
+    UnsafeLink(String),
+    /// 解压出的总字节数超过上限
+    TooLarge(u64),
+    /// 压缩文件中的文件数超过上限
+    TooManyEntries(usize),
+}
+
+impl fmt::Display for ExtractError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ExtractError::Io(ref e) => write!(f, "{}", e),
+            ExtractError::Archive(ref msg) => write!(f, "{}", msg),
+            ExtractError::UnsafePath(ref path) => write!(f, "文件 {} 的路径不在目标文件夹中", path),
+            ExtractError::UnsafeLink(ref path) => write!(f, "链接 {} 指向目标文件夹之外", path),
+            ExtractError::TooLarge(max_size) => write!(f, "解压出的文件超过 {} 字节", max_size),
+            ExtractError::TooManyEntries(max_entries) => write!(f, "压缩文件中的文件超过 {} 个", max_entries),
+        }
+    }
+}
+
+impl std::error::Error for ExtractError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match *self {
+            ExtractError::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ExtractError {
+    fn from(e: io::Error) -> Self {
+        ExtractError::Io(e)
+    }
+}
+
+impl From<zip::result::ZipError> for ExtractError {
+    fn from(e: zip::result::ZipError) -> Self {
+        match e {
+            zip::result::ZipError::Io(e) => ExtractError::Io(e),
+            e => ExtractError::Archive(e.to_string()),
+        }
+    }
+}
+
+impl From<ExtractError> for InstallerError {
+    fn from(e: ExtractError) -> Self {
+        match e {
+            ExtractError::Io(e) => InstallerError::Io(e),
+            e => InstallerError::Extraction(e.to_string()),
+        }
+    }
+}
+
+impl From<io::Error> for InstallerError {
+    fn from(e: io::Error) -> Self {
+        InstallerError::Io(e)
+    }
+}
+
+impl From<reqwest::Error> for InstallerError {
+    fn from(e: reqwest::Error) -> Self {
+        InstallerError::Network(e.to_string())
+    }
+}
+
+impl From<zip::result::ZipError> for InstallerError {
+    fn from(e: zip::result::ZipError) -> Self {
+        InstallerError::Extraction(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::collections::HashSet;
+    use std::io;
+    use super::{ExtractError, InstallerError};
+
+    #[test]
+    fn exit_code_is_distinct() {
+        let errors = [
+            InstallerError::Network("a".to_string()),
+            InstallerError::Validation("a".to_string()),
+            InstallerError::NotFound("a".to_string()),
+            InstallerError::Io(io::Error::from(io::ErrorKind::Other)),
+            InstallerError::Extraction("a".to_string()),
+            InstallerError::Process("a".to_string()),
+        ];
+
+        let codes: HashSet<i32> = errors.iter().map(|e| e.exit_code()).collect();
+        assert_eq!(errors.len(), codes.len());
+        // 0 表示成功，1 和 101 分别被参数错误和 panic 使用
+        assert!(!codes.contains(&0));
+        assert!(!codes.contains(&1));
+        assert!(!codes.contains(&101));
+    }
+
+    #[test]
+    fn from_extract_error() {
+        let e: InstallerError = ExtractError::UnsafePath("../a".to_string()).into();
+        assert_eq!(6, e.exit_code());
+        let e: InstallerError = ExtractError::Io(io::Error::from(io::ErrorKind::NotFound)).into();
+        assert_eq!(5, e.exit_code());
+    }
+
+    #[test]
+    fn from_io_error() {
+        let e: InstallerError = io::Error::from(io::ErrorKind::NotFound).into();
+        assert_eq!(5, e.exit_code());
+    }
+}
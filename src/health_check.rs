@@ -0,0 +1,104 @@
+use std::net::{SocketAddr, TcpStream};
+use std::process::Child;
+use std::thread;
+use std::time::{Duration, Instant};
+use reqwest::{Client, StatusCode};
+
+use crate::error::InstallerError;
+use crate::installer_config::{HealthCheck, HealthCheckType};
+
+/// 两次健康检查之间的时间间隔
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// 单次健康检查的超时时间
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// 等待运行在 `app_run_port` 端口上的 APP 通过健康检查。
+///
+/// 每隔 1 秒检查一次，直到通过检查，或超过 `health_check.timeout` 秒。
+/// 如果在等待过程中 `child` 进程已退出，则不再等待，直接返回错误。
+pub fn wait_until_healthy(app_run_port: u32,
+    health_check: &HealthCheck,
+    child: &mut Child) -> Result<(), InstallerError> {
+
+    let timeout = Duration::from_secs(health_check.timeout);
+    let started = Instant::now();
+
+    loop {
+        if let Ok(Some(status)) = child.try_wait() {
+            return Err(InstallerError::Process(format!("端口 {} 上的项目已退出，{}", app_run_port, status)));
+        }
+
+        if is_healthy(app_run_port, health_check) {
+            return Ok(());
+        }
+
+        if started.elapsed() >= timeout {
+            return Err(InstallerError::Process(format!("端口 {} 上的项目在 {} 秒内没有通过健康检查",
+                app_run_port,
+                health_check.timeout)));
+        }
+
+        thread::sleep(CHECK_INTERVAL);
+    }
+}
+
+/// 检查一次运行在 `app_run_port` 端口上的 APP 是否正常运行
+pub fn is_healthy(app_run_port: u32, health_check: &HealthCheck) -> bool {
+    match health_check.check_type {
+        HealthCheckType::Tcp => check_tcp(app_run_port),
+        HealthCheckType::Http => check_http(app_run_port, &health_check.path),
+    }
+}
+
+fn check_tcp(app_run_port: u32) -> bool {
+    let addr = SocketAddr::from(([127, 0, 0, 1], app_run_port as u16));
+    TcpStream::connect_timeout(&addr, REQUEST_TIMEOUT).is_ok()
+}
+
+fn check_http(app_run_port: u32, path: &str) -> bool {
+    let client = match Client::builder().timeout(REQUEST_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(_) => return false,
+    };
+
+    client.get(&get_health_check_url(app_run_port, path))
+        .send()
+        .map(|response| response.status() == StatusCode::OK)
+        .unwrap_or(false)
+}
+
+fn get_health_check_url(app_run_port: u32, path: &str) -> String {
+    format!("http://127.0.0.1:{}/{}", app_run_port, path.trim_start_matches('/'))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::process::Command;
+    use crate::installer_config::{HealthCheck, HealthCheckType};
+    use super::{get_health_check_url, wait_until_healthy};
+
+    #[test]
+    fn get_health_check_url_success() {
+        assert_eq!("http://127.0.0.1:80/actuator/health", get_health_check_url(80, "/actuator/health"));
+        assert_eq!("http://127.0.0.1:8080/health", get_health_check_url(8080, "health"));
+    }
+
+    #[test]
+    fn wait_until_healthy_process_exited() {
+        let health_check = HealthCheck {
+            check_type: HealthCheckType::Tcp,
+            path: String::new(),
+            timeout: 60,
+        };
+        // 一个会立即退出的进程
+        let mut child = if cfg!(target_os = "windows") {
+            Command::new("cmd").args(["/C", "exit"]).spawn().unwrap()
+        } else {
+            Command::new("true").spawn().unwrap()
+        };
+        child.wait().unwrap();
+
+        assert!(wait_until_healthy(1, &health_check, &mut child).is_err());
+    }
+}
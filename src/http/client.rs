@@ -12,29 +12,35 @@ use indicatif::{HumanDuration, ProgressBar, ProgressStyle};
 use crate::util::{net, os};
 use crate::config::{self, REST_API_INSTALLERS, REST_API_APPS};
 use crate::download_config::DownloadConfig;
+use crate::error::InstallerError;
+use crate::progressln;
 
 
 /// 先显示字段级错误，然后显示全局错误
-fn print_errors(errors: serde_json::Value, mut writer: impl std::io::Write) {
-    let error_map = errors["errors"].as_object().unwrap();
-    let mut num = 0;
-    for(key, value) in error_map.iter() {
-        if key != "globalErrors" {
-            for error_msg in value.as_array().unwrap().iter() {
-                num += 1;
-                writeln!(writer, "> [ERROR]: {}. {}", num, error_msg.as_str().unwrap()).unwrap();
-            }
-        }
-    }
-
+/// 
+/// 忽略不是预期格式的错误信息，如果没有显示任何错误信息，则返回 `false`。
+fn print_errors(errors: &serde_json::Value, mut writer: impl std::io::Write) -> bool {
+    let error_map = match errors["errors"].as_object() {
+        Some(error_map) => error_map,
+        None => return false,
+    };
+
+    let field_errors = error_map.iter()
+        .filter(|(key, _)| key.as_str() != "globalErrors")
+        .map(|(_, value)| value);
     // 最后打印 globalErrors
-    if let Some(global_errors) = errors["errors"]["globalErrors"].as_array() {
-        for error_msg in global_errors.iter() {
-            num += 1;
-            writeln!(writer, "> [ERROR]: {}. {}", num, error_msg.as_str().unwrap()).unwrap();
-        }
+    let error_msgs: Vec<&str> = field_errors.chain(error_map.get("globalErrors"))
+        .filter_map(|value| value.as_array())
+        .flatten()
+        .filter_map(|error_msg| error_msg.as_str())
+        .collect();
+
+    for (index, error_msg) in error_msgs.iter().enumerate() {
+        // 输出错误信息失败时，已无法再提示用户，因此忽略
+        let _ = writeln!(writer, "> [ERROR]: {}. {}", index + 1, error_msg);
     }
-  
+
+    !error_msgs.is_empty()
 }
 
 /// 软件安装信息
@@ -53,6 +59,12 @@ pub struct InstallerInfo {
     pub jdk_name: String,
     pub jdk_version: String,
     pub jdk_file_name: String,
+    /// Spring Boot jar 的 SHA-256 校验和，为空时不校验
+    #[serde(default)]
+    pub app_file_sha256: Option<String>,
+    /// JDK 压缩文件的 SHA-256 校验和，为空时不校验
+    #[serde(default)]
+    pub jdk_file_sha256: Option<String>,
 }
 
 /// 使用 Block Lang 提供的项目注册 token，向 Block Lang 平台注册部署服务器信息。
@@ -66,11 +78,12 @@ pub fn register_installer(
     root_url: &str, 
     registration_token: &str, 
     app_run_port: u32,  
-    server_token: &str) -> Result<InstallerInfo, Box<dyn std::error::Error>> {
+    server_token: &str) -> Result<InstallerInfo, InstallerError> {
 
     let url = &format!("{}/{}", root_url, REST_API_INSTALLERS);
     
-    let interface_addr = net::get_interface_address().expect("获取不到能联网的有线网络");
+    let interface_addr = net::get_interface_address()
+        .ok_or_else(|| InstallerError::Network("获取不到能联网的有线网络".to_string()))?;
     let os_info = os::get_os_info();
 
     let app_run_port = app_run_port.to_string();
@@ -92,7 +105,7 @@ pub fn register_installer(
         .send()
         .map_err(|err| {
             eprintln!("> [ERROR]: 无法访问 {}, 可能是 url 输入有误", url);
-            Box::from(err)
+            InstallerError::from(err)
         })
         .and_then(|mut response| {
             match response.status() {
@@ -104,7 +117,7 @@ pub fn register_installer(
                         },
                         Err(e) => {
                             eprintln!("> [ERROR]: 从 {} 未能获取有效的安装器数据", url);
-                            Err(Box::from(e))
+                            Err(InstallerError::from(e))
                         }
                     }
                 }
@@ -113,33 +126,31 @@ pub fn register_installer(
                     eprintln!("> [ERROR]: 请修复以下问题后再安装：");
 
                     match response.json::<serde_json::Value>() {
-                        Ok(errors) => {
-                            print_errors(errors, &mut std::io::stderr());
-                        },
-                        Err(_) => {
+                        Ok(errors) if print_errors(&errors, &mut std::io::stderr()) => {},
+                        _ => {
                             eprintln!("> [ERROR]: 从 {} 未能获取有效错误信息", url);
                         }
                     };
 
-                    Err(Box::from("未通过数据有效性校验"))
+                    Err(InstallerError::Validation("未通过数据有效性校验".to_string()))
                 }
                 s => {
                     eprintln!("> [ERROR]: 从 {} 未能获取有效数据, 可能是 url 输入有误", url);
-                    Err(Box::from(format!("未知错误，状态码是 {:?}", s)))
+                    Err(InstallerError::Network(format!("未知错误，状态码是 {:?}", s)))
                 }
             }
         })
 }
 
 /// 向 Block Lang 平台注销指定的 installer
-pub fn unregister_installer(root_url: &str, installer_token: &str) -> Result<(), Box<dyn std::error::Error>> {
+pub fn unregister_installer(root_url: &str, installer_token: &str) -> Result<(), InstallerError> {
     let url = &format!("{}/{}/{}", root_url, REST_API_INSTALLERS, installer_token);
     let client = Client::new();
     client.delete(url)
         .send()
         .map_err(|err| {
             eprintln!("> [ERROR]: 无法访问 {}", url);
-            Box::from(err)
+            InstallerError::from(err)
         })
         .and_then(|response| {
             match response.status() {
@@ -148,12 +159,12 @@ pub fn unregister_installer(root_url: &str, installer_token: &str) -> Result<(),
                     Ok(())
                 },
                 StatusCode::NOT_FOUND => {
-                    println!("> [WARN]: 根据installer token 没有找到注册器信息");
-                    Err(Box::from("根据installer token 没有找到注册器信息"))
+                    progressln!("> [WARN]: 根据installer token 没有找到注册器信息");
+                    Err(InstallerError::NotFound("根据installer token 没有找到注册器信息".to_string()))
                 }
                 s => {
                     eprintln!("> [ERROR]: 返回的状态码无效，url 为 {}, 状态码为：{}", url, s);
-                    Err(Box::from(format!("未知错误，状态码是 {:?}", s)))
+                    Err(InstallerError::Network(format!("未知错误，状态码是 {:?}", s)))
                 }
             }
         })
@@ -166,11 +177,12 @@ pub fn unregister_installer(root_url: &str, installer_token: &str) -> Result<(),
 /// 注意：连接建立后，Block Lang 平台默认打开连接，但是如果遇到盗用 token 的情况，
 /// 可以在 Block Lang 平台关闭该连接。
 /// TODO: 不能再调用同一个方法，待修复，需要重新设计 update
-pub fn update_installer(root_url: &str, token: &str) -> Result<InstallerInfo, Box<dyn std::error::Error>> {
+pub fn update_installer(root_url: &str, token: &str) -> Result<InstallerInfo, InstallerError> {
     let url = &format!("{}/{}", root_url, REST_API_INSTALLERS);
 
     let mut json_data = HashMap::new();
-    let interface_addr = net::get_interface_address().expect("获取不到能联网的有线网络");
+    let interface_addr = net::get_interface_address()
+        .ok_or_else(|| InstallerError::Network("获取不到能联网的有线网络".to_string()))?;
     let os_info = os::get_os_info();
     
     json_data.insert("installerToken", token);
@@ -187,7 +199,7 @@ pub fn update_installer(root_url: &str, token: &str) -> Result<InstallerInfo, Bo
         .send()
         .map_err(|err| {
             eprintln!("> [ERROR]: 无法访问 {}", url);
-            Box::from(err)
+            InstallerError::from(err)
         })
         .and_then(|mut response| {
             match response.status() {
@@ -199,7 +211,7 @@ pub fn update_installer(root_url: &str, token: &str) -> Result<InstallerInfo, Bo
                         },
                         Err(e) => {
                             eprintln!("> [ERROR]: 从 {} 未能获取有效的安装器数据", url);
-                            Err(Box::from(e))
+                            Err(InstallerError::from(e))
                         }
                     }
                 }
@@ -208,19 +220,17 @@ pub fn update_installer(root_url: &str, token: &str) -> Result<InstallerInfo, Bo
                     eprintln!("> [ERROR]: 请修复以下问题后再升级：");
 
                     match response.json::<serde_json::Value>() {
-                        Ok(errors) => {
-                            print_errors(errors, &mut std::io::stderr());
-                        },
-                        Err(_) => {
+                        Ok(errors) if print_errors(&errors, &mut std::io::stderr()) => {},
+                        _ => {
                             eprintln!("> [ERROR]: 从 {} 未能获取有效错误信息", url);
                         }
                     };
 
-                    Err(Box::from("未通过数据有效性校验"))
+                    Err(InstallerError::Validation("未通过数据有效性校验".to_string()))
                 }
                 s => {
                     eprintln!("> [ERROR]: 从 {} 未能获取有效数据, 可能是 url 输入有误", url);
-                    Err(Box::from(format!("未知错误，状态码是 {:?}", s)))
+                    Err(InstallerError::Network(format!("未知错误，状态码是 {:?}", s)))
                 }
             }
         })
@@ -248,7 +258,7 @@ impl<R: Read> Read for DownloadProgress<R> {
 /// 
 /// 如果在指定的文件夹下找到对应的文件，则中断下载，直接使用已存在文件。
 /// 
-/// 下载完成后，会返回新下载文件的完整路径。
+/// 下载完成后，会返回新下载文件的完整路径；下载失败时返回错误。
 /// 
 /// 应用服务器的目录结构为
 /// 
@@ -262,30 +272,31 @@ impl<R: Read> Read for DownloadProgress<R> {
 /// ```no_run
 /// use installer::http::client::download;
 /// 
-/// fn main() {
-///     download("https://blocklang.com", "app", "0.1.0", "app-0.1.0.zip").unwrap();
+/// fn main() -> Result<(), installer::error::InstallerError> {
+///     download("https://blocklang.com", "app", "0.1.0", "app-0.1.0.zip")?;
+///     Ok(())
 /// }
 /// ```
 pub fn download(
     root_url: &str,
     app_name: &str, 
     app_version: &str, 
-    app_file_name: &str) -> Option<String> {
+    app_file_name: &str) -> Result<String, InstallerError> {
     
     let saved_dir_path = &format!("{}/{}/{}", 
         config::ROOT_PATH_APP, 
         app_name, 
         app_version);
 
-    fs::create_dir_all(saved_dir_path).expect("在创建存储下载文件的目录结构时出错");
+    fs::create_dir_all(saved_dir_path)?;
 
     let saved_file_path = &format!("{}/{}", saved_dir_path, app_file_name);
 
     let path = Path::new(saved_file_path);
     // 如果文件已存在，则直接返回文件名
     if path.exists() {
-        println!("> 文件已存在");
-        return Some(saved_file_path.to_string());
+        progressln!("> 文件已存在");
+        return Ok(saved_file_path.to_string());
     }
 
     // 在下载过程中，将文件命名后面添加 .part
@@ -296,12 +307,17 @@ pub fn download(
     let mut headers = HeaderMap::new();
 
     if saved_file_part_path.exists() { // 已下载部分内容，进行断点续传
-        downloaded_size = saved_file_part_path.metadata().unwrap().len();
-        headers.insert(header::RANGE, HeaderValue::from_str(&format!("bytes={}-", downloaded_size)).unwrap());
+        downloaded_size = saved_file_part_path.metadata()?.len();
+        if let Ok(range) = HeaderValue::from_str(&format!("bytes={}-", downloaded_size)) {
+            headers.insert(header::RANGE, range);
+        }
 
-        let download_config = DownloadConfig::new();
+        let download_config = DownloadConfig::new()?;
         if let Some(file_md5_info) = download_config.get(app_name, app_version) {
-            headers.insert(header::IF_RANGE, HeaderValue::from_str(&file_md5_info.md5).unwrap());
+            // 如果缓存的 etag 不是有效的请求头，则不传 IF_RANGE，此时服务器会返回完整的文件
+            if let Ok(if_range) = HeaderValue::from_str(&file_md5_info.md5) {
+                headers.insert(header::IF_RANGE, if_range);
+            }
         }
         
     } else {
@@ -321,8 +337,8 @@ pub fn download(
     let client = Client::new();
     match client.get(url).headers(headers).send() {
         Err(e) => {
-            println!("> [ERROR]: 下载失败，出现了其他错误，状态码: {:?}", e);
-            None
+            progressln!("> [ERROR]: 下载失败，出现了其他错误，状态码: {:?}", e);
+            Err(InstallerError::from(e))
         },
         Ok(response) => {
             match response.status() {
@@ -344,8 +360,9 @@ pub fn download(
                     // 在开始下载前，缓存 etag 的值
                     if !etag.trim().is_empty() {
                         // 去掉外围的双引号
-                        let mut download_config = DownloadConfig::new();
-                        download_config.put(app_name, app_version, etag.trim().trim_matches('"'));
+                        DownloadConfig::modify(|download_config| {
+                            download_config.put(app_name, app_version, etag.trim().trim_matches('"'))
+                        })?;
                     }
 
                     let pb = ProgressBar::new(total_size);
@@ -362,22 +379,21 @@ pub fn download(
                     // 下载整个文件
                     // 如果文件已存在，说明文件被改动过，需删除之前下载过的文件，重新下载
                     // 直接使用 File::create 就可删除之前下载过的内容
-                    let mut file = File::create(saved_file_part_path).unwrap();
+                    let mut file = File::create(saved_file_part_path)?;
 
                     let started = Instant::now();
-                    copy(&mut source, &mut file).unwrap();
+                    copy(&mut source, &mut file)?;
                     source.progress_bar.finish_and_clear();
                    
                     // 下载完成后，将文件名中的 .part 去掉
-                    fs::rename(saved_file_part_path, saved_file_path).unwrap();
+                    fs::rename(saved_file_part_path, saved_file_path)?;
 
                     // 下载完成后，清除 download_config 配置项
-                    let mut download_config = DownloadConfig::new();
-                    download_config.remove(app_name, app_version);
+                    DownloadConfig::modify(|download_config| download_config.remove(app_name, app_version))?;
 
-                     println!("> [INFO]: 下载完成，耗时 {}", HumanDuration(started.elapsed()));
+                     progressln!("> [INFO]: 下载完成，耗时 {}", HumanDuration(started.elapsed()));
 
-                    Some(saved_file_path.to_string())
+                    Ok(saved_file_path.to_string())
                 }
                 StatusCode::PARTIAL_CONTENT => {
                     // 断点续传
@@ -394,14 +410,15 @@ pub fn download(
                         .headers()
                         .get(header::CONTENT_RANGE)
                         .and_then(|ct_range| ct_range.to_str().ok())
-                        .and_then(|ct_range| ct_range.split('/').collect::<Vec<_>>()[1].parse::<u64>().ok())
+                        .and_then(|ct_range| ct_range.split('/').nth(1))
+                        .and_then(|total| total.parse::<u64>().ok())
                         .unwrap_or(0);
 
-                    println!("{:?}", response
+                    progressln!("{:?}", response
                         .headers()
                         .get(header::CONTENT_RANGE));
 
-                    println!("{}", total_size);
+                    progressln!("{}", total_size);
                     
                     let accept_ranges = response
                         .headers()
@@ -428,27 +445,27 @@ pub fn download(
                     let mut dest = fs::OpenOptions::new()
                         .create(true)
                         .append(true)
-                        .open(&saved_file_part_path).unwrap();
+                        .open(&saved_file_part_path)?;
                     
                     let started = Instant::now();
-                    copy(&mut source, &mut dest).unwrap();
+                    copy(&mut source, &mut dest)?;
                     source.progress_bar.finish_and_clear();
                     // 下载完成后，将文件名中的 .part 去掉
-                    fs::rename(saved_file_part_path, saved_file_path).unwrap();
-                    println!("> [INFO]: 下载完成，耗时 {}", HumanDuration(started.elapsed()));
-                    Some(saved_file_path.to_string())
+                    fs::rename(saved_file_part_path, saved_file_path)?;
+                    progressln!("> [INFO]: 下载完成，耗时 {}", HumanDuration(started.elapsed()));
+                    Ok(saved_file_path.to_string())
                 }
                 StatusCode::NOT_FOUND => {
-                    println!("> [ERROR]: 下载失败，没有找到要下载的文件，状态码: 404");
-                    println!("> [ERROR]: 下载地址: {}", response.url().as_str());
+                    progressln!("> [ERROR]: 下载失败，没有找到要下载的文件，状态码: 404");
+                    progressln!("> [ERROR]: 下载地址: {}", response.url().as_str());
 
-                    None
+                    Err(InstallerError::NotFound(format!("没有找到要下载的文件 {}", app_file_name)))
                 }
                 s => {
-                    println!("> [ERROR]: 下载失败，状态码: {:?}", s);
-                    println!("> [ERROR]: 下载地址: {}", response.url().as_str());
+                    progressln!("> [ERROR]: 下载失败，状态码: {:?}", s);
+                    progressln!("> [ERROR]: 下载地址: {}", response.url().as_str());
 
-                    None
+                    Err(InstallerError::Network(format!("下载失败，状态码是 {:?}", s)))
                 }
             }
         }
@@ -596,7 +613,7 @@ mod tests {
 
     #[test]
     fn download_fail() {
-        assert_eq!(None, download(&get_root_url(), "app", "0.1.0", "app-0.1.0.zip"));
+        assert!(download(&get_root_url(), "app", "0.1.0", "app-0.1.0.zip").is_err());
     }
 
     #[test]
@@ -643,7 +660,7 @@ mod tests {
             }}"#;
         let v: serde_json::Value = serde_json::from_str(data)?;
         let mut actual = Vec::new();
-        print_errors(v, &mut actual);
+        print_errors(&v, &mut actual);
         assert_eq!(String::from_utf8(actual).unwrap(), String::from("> [ERROR]: 1. first global error\n> [ERROR]: 2. second global error\n"));
         Ok(())
     }
@@ -655,7 +672,7 @@ mod tests {
             }}"#;
         let v: serde_json::Value = serde_json::from_str(data)?;
         let mut actual = Vec::new();
-        print_errors(v, &mut actual);
+        print_errors(&v, &mut actual);
         assert_eq!(String::from_utf8(actual).unwrap(), String::from("> [ERROR]: 1. first field1 error\n> [ERROR]: 2. second field1 error\n"));
         Ok(())
     }
@@ -668,8 +685,25 @@ mod tests {
             }}"#;
         let v: serde_json::Value = serde_json::from_str(data)?;
         let mut actual = Vec::new();
-        print_errors(v, &mut actual);
+        print_errors(&v, &mut actual);
         assert_eq!(String::from_utf8(actual).unwrap(), String::from("> [ERROR]: 1. first field1 error\n> [ERROR]: 2. second field1 error\n> [ERROR]: 3. first global error\n> [ERROR]: 4. second global error\n"));
         Ok(())
     }
+
+    #[test]
+    fn print_errors_invalid_format() -> Result<(), Box<dyn std::error::Error>> {
+        let mut actual = Vec::new();
+        assert!(!print_errors(&serde_json::from_str(r#"{"errors": "not an object"}"#)?, &mut actual));
+        assert!(!print_errors(&serde_json::from_str(r#"{"message": "no errors"}"#)?, &mut actual));
+        assert!(actual.is_empty());
+
+        // 忽略不是字符串的错误信息
+        let data = r#"{"errors": {
+                "globalErrors": ["first global error", 2],
+                "field1Errors": "not an array"
+            }}"#;
+        assert!(print_errors(&serde_json::from_str(data)?, &mut actual));
+        assert_eq!(String::from_utf8(actual)?, String::from("> [ERROR]: 1. first global error\n"));
+        Ok(())
+    }
 }
\ No newline at end of file
@@ -0,0 +1,228 @@
+use std::io::Cursor;
+use serde_json::json;
+use tiny_http::{Server, Request, Response, Method, Header};
+
+use crate::command;
+use crate::config::REST_API_APPS;
+use crate::error::InstallerError;
+use crate::installer_config::{Installer, InstallerConfig};
+use crate::util::process;
+
+/// 在请求头中传入 installer token，用于校验调用方是否有权限操作此 installer
+const HEADER_INSTALLER_TOKEN: &str = "X-Installer-Token";
+/// 端口上未注册 installer 或 installer token 无效时，都返回此信息，不暴露端口是否已注册
+const MESSAGE_UNAUTHORIZED: &str = "installer token 无效";
+
+/// Installer REST 服务支持的操作
+#[derive(Debug, PartialEq)]
+enum Route {
+    /// 获取 APP 的运行状态
+    Status(u32),
+    /// 运行 APP
+    Run(u32),
+    /// 停止 APP
+    Stop(u32),
+    /// 升级 APP
+    Update(u32),
+}
+
+impl Route {
+    fn app_run_port(&self) -> u32 {
+        match *self {
+            Route::Status(port) | Route::Run(port) | Route::Stop(port) | Route::Update(port) => port,
+        }
+    }
+}
+
+/// 启动 Installer REST 服务，Block Lang 平台或其他工具可通过此服务运行、停止或升级 APP。
+///
+/// 支持的服务有：
+///
+/// * `GET /apps/{port}` 获取运行在 `port` 端口上的 APP 信息
+/// * `POST /apps/{port}/run` 运行 APP
+/// * `POST /apps/{port}/stop` 停止 APP
+/// * `POST /apps/{port}/update` 升级 APP
+///
+/// 在请求头 `X-Installer-Token` 中需要传入对应 installer 的 installer token。
+/// 服务默认只监听本机地址 `127.0.0.1`，需要从其他主机访问时再指定 `host`，如 `0.0.0.0`。
+///
+/// 注意：请求是逐个处理的，因为这些操作都会读写 `installer_config.toml` 和 `prod` 文件夹，
+/// 不能同时处理同一个 APP。
+///
+/// # Examples
+///
+/// ```no_run
+/// use installer::http::server::start;
+///
+/// fn main() -> Result<(), installer::error::InstallerError> {
+///     start("127.0.0.1", 9090)?;
+///     Ok(())
+/// }
+/// ```
+pub fn start(host: &str, port: u32) -> Result<(), InstallerError> {
+    let server = Server::http(format!("{}:{}", host, port)).map_err(|e| {
+        eprintln!("> [ERROR]: 无法在 {}:{} 上启动 Installer REST 服务", host, port);
+        InstallerError::Process(e.to_string())
+    })?;
+
+    println!("> [INFO]: Installer REST 服务已运行在 {}:{} 上，按 CTRL + C 退出", host, port);
+
+    for request in server.incoming_requests() {
+        let response = handle_request(&request);
+        if let Err(e) = request.respond(response) {
+            eprintln!("> [ERROR]: 返回响应时出错：{}", e);
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_request(request: &Request) -> Response<Cursor<Vec<u8>>> {
+    let route = match parse_route(request.method(), request.url()) {
+        Some(route) => route,
+        None => return json_response(404, json!({"message": "不支持的服务"})),
+    };
+
+    let app_run_port = route.app_run_port();
+    // 每次请求时都重新读取配置文件，以获取最新的 installer 信息
+    let installer_config = match InstallerConfig::new() {
+        Ok(installer_config) => installer_config,
+        Err(e) => return json_response(500, json!({"message": e.to_string()})),
+    };
+    // 先校验 installer token，端口上未注册 installer 时也返回 401，以免调用方借此探测哪些端口已注册
+    let token = get_header(request, HEADER_INSTALLER_TOKEN).unwrap_or("");
+    let installer = match installer_config.get_by_port(app_run_port) {
+        Some(installer) if constant_time_eq(token.as_bytes(), installer.installer_token.as_bytes()) => installer,
+        _ => return json_response(401, json!({"message": MESSAGE_UNAUTHORIZED})),
+    };
+
+    println!();
+    println!("> [INFO]: 收到请求 {} {}", request.method(), request.url());
+
+    let result = match route {
+        Route::Status(_) => Ok(()),
+        Route::Run(_) => command::run_app(installer),
+        // 通过 REST 服务不能关闭非 installer 启动的进程
+        Route::Stop(_) => command::stop_jar(installer, false),
+        Route::Update(_) => command::update_app(installer, false),
+    };
+
+    match result {
+        Ok(_) => {
+            // 操作完成后，重新读取配置信息，因为升级后版本号会变化
+            match InstallerConfig::new().as_ref().map(|config| config.get_by_port(app_run_port)) {
+                Ok(Some(installer)) => json_response(200, app_info(installer)),
+                Err(e) => json_response(500, json!({"message": e.to_string()})),
+                Ok(None) => json_response(404, json!({
+                    "message": format!("端口 {} 上未注册 installer", app_run_port)
+                })),
+            }
+        },
+        Err(e) => {
+            let status_code = match e {
+                InstallerError::NotFound(_) => 404,
+                InstallerError::Validation(_) => 422,
+                _ => 500,
+            };
+            json_response(status_code, json!({"message": e.to_string()}))
+        },
+    }
+}
+
+/// 解析请求的方法和路径，如 `POST /apps/80/run`
+fn parse_route(method: &Method, url: &str) -> Option<Route> {
+    // 忽略查询参数
+    let path = url.split('?').next().unwrap_or("");
+    let parts: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+
+    if parts.is_empty() || parts[0] != REST_API_APPS {
+        return None;
+    }
+
+    let app_run_port = parts.get(1).and_then(|port| port.parse::<u32>().ok())?;
+
+    match (method, parts.get(2), parts.len()) {
+        (Method::Get, None, 2) => Some(Route::Status(app_run_port)),
+        (Method::Post, Some(&"run"), 3) => Some(Route::Run(app_run_port)),
+        (Method::Post, Some(&"stop"), 3) => Some(Route::Stop(app_run_port)),
+        (Method::Post, Some(&"update"), 3) => Some(Route::Update(app_run_port)),
+        _ => None,
+    }
+}
+
+/// 比较两个字节串是否相同，比较所用的时间只与字节串的长度有关，与内容无关，
+/// 以免调用方根据响应时间逐个字节地猜出 installer token。
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+fn get_header<'a>(request: &'a Request, name: &'static str) -> Option<&'a str> {
+    request.headers()
+        .iter()
+        .find(|header| header.field.equiv(name))
+        .map(|header| header.value.as_str())
+}
+
+fn app_info(installer: &Installer) -> serde_json::Value {
+    json!({
+        "appName": installer.app_name,
+        "appVersion": installer.app_version,
+        "appRunPort": installer.app_run_port,
+        "jdkName": installer.jdk_name,
+        "jdkVersion": installer.jdk_version,
+        "processId": process::get_port_owner(installer.app_run_port).pid(),
+    })
+}
+
+fn json_response(status_code: u16, body: serde_json::Value) -> Response<Cursor<Vec<u8>>> {
+    let content_type = "Content-Type: application/json; charset=utf-8".parse::<Header>().unwrap();
+    Response::from_string(body.to_string())
+        .with_status_code(status_code)
+        .with_header(content_type)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use tiny_http::Method;
+    use super::{constant_time_eq, parse_route, Route};
+
+    #[test]
+    fn parse_route_status() {
+        assert_eq!(Some(Route::Status(80)), parse_route(&Method::Get, "/apps/80"));
+    }
+
+    #[test]
+    fn parse_route_run_stop_update() {
+        assert_eq!(Some(Route::Run(80)), parse_route(&Method::Post, "/apps/80/run"));
+        assert_eq!(Some(Route::Stop(8080)), parse_route(&Method::Post, "/apps/8080/stop"));
+        assert_eq!(Some(Route::Update(80)), parse_route(&Method::Post, "/apps/80/update?force=true"));
+    }
+
+    #[test]
+    fn parse_route_method_not_match() {
+        assert_eq!(None, parse_route(&Method::Get, "/apps/80/run"));
+        assert_eq!(None, parse_route(&Method::Post, "/apps/80"));
+    }
+
+    #[test]
+    fn parse_route_invalid_path() {
+        assert_eq!(None, parse_route(&Method::Get, "/"));
+        assert_eq!(None, parse_route(&Method::Get, "/installers/80"));
+        assert_eq!(None, parse_route(&Method::Post, "/apps/not-a-port/run"));
+        assert_eq!(None, parse_route(&Method::Post, "/apps/80/restart"));
+        assert_eq!(None, parse_route(&Method::Post, "/apps/80/run/1"));
+    }
+
+    #[test]
+    fn constant_time_eq_success() {
+        assert!(constant_time_eq(b"token", b"token"));
+        assert!(constant_time_eq(b"", b""));
+        assert!(!constant_time_eq(b"token", b"tokem"));
+        assert!(!constant_time_eq(b"token", b"token1"));
+        assert!(!constant_time_eq(b"", b"token"));
+    }
+}
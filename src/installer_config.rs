@@ -1,11 +1,44 @@
-use std::fs::File;
-use std::io::prelude::*;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
 use serde_derive::{Deserialize, Serialize};
 use toml;
 
+use crate::error::InstallerError;
 use crate::http::client::InstallerInfo;
+use crate::util::file::{self, FileLock};
 use crate::util::net;
-use crate::config::INSTALLER_CONFIG_FILE_NAME;
+use crate::watch;
+use crate::config::{
+    INSTALLER_CONFIG_FILE_NAME,
+    DEFAULT_GRACE_PERIOD,
+    MAX_HISTORY_LEN,
+    DEFAULT_HEALTH_CHECK_PATH,
+    DEFAULT_HEALTH_CHECK_TIMEOUT,
+    DEFAULT_PROXY_HOLD_TIMEOUT};
+
+/// 一次选择多个 installer 的方式，选择单个 installer 时使用端口号
+#[derive(Debug, Clone, PartialEq)]
+pub enum Selector {
+    /// 所有 installer
+    All,
+    /// 部署此 APP 的 installer，如 `@user/project`
+    App(String),
+    /// 有此标签的 installer，如 `env=staging`
+    Tag(String),
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Selector::All => write!(f, "所有项目"),
+            Selector::App(app_name) => write!(f, "项目 {}", app_name),
+            Selector::Tag(tag) => write!(f, "标签为 {} 的项目", tag),
+        }
+    }
+}
 
 pub struct InstallerConfig {
     file_name: String,
@@ -18,6 +51,9 @@ pub struct InstallerData {
     /// 此 token 一旦生成就不能修改，目前使用的是 MAC 地址。
     pub server_token: String,
     pub installers: Vec<Installer>,
+    /// 所有 installer 默认使用的自动升级配置，installer 中的 `auto_update` 会覆盖此配置
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto_update: Option<AutoUpdate>,
 }
 
 /// 注意，虽然 `InstallerInfo` 的字段和 Installer 的字段一样，
@@ -37,46 +73,373 @@ pub struct Installer {
     pub jdk_name: String,
     pub jdk_version: String,
     pub jdk_file_name: String,
+    /// Spring Boot jar 的 SHA-256 校验和，为空时不校验
+    #[serde(default)]
+    pub app_file_sha256: Option<String>,
+    /// JDK 压缩文件的 SHA-256 校验和，为空时不校验
+    #[serde(default)]
+    pub jdk_file_sha256: Option<String>,
+    /// 守护进程重启 APP 的次数
+    #[serde(default)]
+    pub restart_count: u32,
+    /// 停止 APP 时，等待 APP 正常退出的秒数，超时后强制关闭 APP
+    #[serde(default = "default_grace_period")]
+    pub grace_period: u64,
+    /// JVM 参数，如 `-Xmx512m`，放在 `-jar` 之前
+    #[serde(default)]
+    pub jvm_args: Vec<String>,
+    /// Spring Boot 程序参数，如 `--spring.profiles.active=prod`，放在 `--server.port` 之后
+    #[serde(default)]
+    pub app_args: Vec<String>,
+    /// 蓝绿部署时 APP 轮流使用的两个内部端口，为空时不使用蓝绿部署。
+    /// 
+    /// 使用蓝绿部署时，`app_run_port` 由 installer 的 TCP 代理占用，代理将请求转发到其中一个内部端口上运行的 APP。
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub blue_green_ports: Vec<u32>,
+    /// APP 的内部端口，设置后 `app_run_port` 由 installer 的 TCP 代理占用，代理将请求转发到此端口上运行的 APP。
+    /// 
+    /// 重启 APP 时，代理会保持新建立的连接，等待 APP 启动。如果设置了 `blue_green_ports`，则忽略此端口。
+    #[serde(default)]
+    pub internal_port: Option<u32>,
+    /// 后端 APP 没有运行时，代理保持连接并等待 APP 启动的秒数，超时后拒绝连接，为 0 时立即拒绝连接
+    #[serde(default = "default_proxy_hold_timeout")]
+    pub proxy_hold_timeout: u64,
+    /// 自定义标签，如 `env=staging`，可通过 `--tag` 选项选择有此标签的 installer
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// 升级后检查新版本是否正常运行，为空时不检查
+    #[serde(default)]
+    pub health_check: Option<HealthCheck>,
+    /// 执行 `watch` 命令时，定时检查并升级 APP 的配置，为空时使用全局配置
+    #[serde(default)]
+    pub auto_update: Option<AutoUpdate>,
+    /// 运行 APP 时设置的环境变量
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// 之前部署过的版本，按部署的先后顺序排列，最后一个是上一个版本，用于回滚。
+    /// 
+    /// 注意，toml 中的表必须放在最后，为空时不保存，否则空数组会出现在表之后。
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<DeployedVersion>,
+}
+
+/// 部署过的 Spring Boot jar 和 JDK 版本
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct DeployedVersion {
+    pub app_name: String,
+    pub app_version: String,
+    pub app_file_name: String,
+    pub jdk_name: String,
+    pub jdk_version: String,
+    pub jdk_file_name: String,
+    #[serde(default)]
+    pub app_file_sha256: Option<String>,
+    #[serde(default)]
+    pub jdk_file_sha256: Option<String>,
 }
 
-impl Default for InstallerConfig {
+impl Installer {
+
+    /// 获取当前部署的版本
+    pub fn deployed_version(&self) -> DeployedVersion {
+        DeployedVersion {
+            app_name: self.app_name.clone(),
+            app_version: self.app_version.clone(),
+            app_file_name: self.app_file_name.clone(),
+            jdk_name: self.jdk_name.clone(),
+            jdk_version: self.jdk_version.clone(),
+            jdk_file_name: self.jdk_file_name.clone(),
+            app_file_sha256: self.app_file_sha256.clone(),
+            jdk_file_sha256: self.jdk_file_sha256.clone(),
+        }
+    }
+
+    /// 获取上一个部署的版本，如果没有则返回 `None`
+    pub fn previous_version(&self) -> Option<&DeployedVersion> {
+        self.history.last()
+    }
+
+    /// 是否使用蓝绿部署
+    pub fn is_blue_green(&self) -> bool {
+        self.blue_green_ports.len() == 2
+    }
+
+    /// 是否在 `app_run_port` 上运行 TCP 代理，即使用蓝绿部署或设置了内部端口
+    pub fn uses_proxy(&self) -> bool {
+        !self.backend_ports().is_empty()
+    }
+
+    /// 获取代理后面 APP 可使用的内部端口，不使用代理时返回空数组
+    pub fn backend_ports(&self) -> Vec<u32> {
+        if self.is_blue_green() {
+            self.blue_green_ports.clone()
+        } else {
+            self.internal_port.into_iter().collect()
+        }
+    }
+
+    /// 设置运行 APP 时使用的配置项，支持的配置项有：
+    /// 
+    /// * `jvm_args` JVM 参数，多个参数之间用空格分隔，含空格的参数可以像 shell 中一样用引号括起来
+    /// * `app_args` Spring Boot 程序参数，分隔方式与 `jvm_args` 相同，不能包含 `--server.port`
+    /// * `env.<NAME>` 名为 `NAME` 的环境变量
+    /// * `blue_green_ports` 蓝绿部署使用的两个内部端口，用空格分隔，不能与 `app_run_port` 相同
+    /// * `internal_port` 在代理后面运行 APP 时使用的内部端口，不能与 `app_run_port` 相同
+    /// * `proxy_hold_timeout` 后端 APP 没有运行时，代理保持连接的秒数
+    /// * `auto_update.interval` 自动升级时检查更新的时间间隔，如 `6h`
+    /// * `auto_update.window` 自动升级的维护窗口，如 `02:00-04:00`，需先设置 `auto_update.interval`
+    /// * `tags` 自定义标签，多个标签之间用空格分隔，如 `env=staging team=payments`
+    /// 
+    /// 如果 `value` 为空，则清空参数和标签、删除环境变量、不再使用蓝绿部署或内部端口，`proxy_hold_timeout` 恢复默认值，
+    /// 不再自动升级或不限制升级的时间。
+    pub fn set_option(&mut self, key: &str, value: &str) -> Result<(), InstallerError> {
+        let args: Vec<String> = value.split_whitespace().map(String::from).collect();
+        match key {
+            "jvm_args" => {
+                self.jvm_args = split_args(key, value)?;
+            },
+            "app_args" => {
+                let args = split_args(key, value)?;
+                if args.iter().any(|arg| arg.starts_with("--server.port")) {
+                    return Err(InstallerError::Validation("app_args 中不能设置 --server.port，运行端口由 installer 指定".to_string()));
+                }
+                self.app_args = args;
+            },
+            "blue_green_ports" => {
+                let ports = args.iter()
+                    .map(|port| parse_port(port))
+                    .collect::<Result<Vec<u32>, _>>()?;
+                let is_valid = ports.is_empty() || (ports.len() == 2
+                    && ports[0] != ports[1]
+                    && !ports.contains(&self.app_run_port));
+                if !is_valid {
+                    return Err(InstallerError::Validation(format!("blue_green_ports 必须是两个不同的端口，且不能是 {}", self.app_run_port)));
+                }
+                self.blue_green_ports = ports;
+            },
+            "internal_port" => {
+                let port = match value.trim() {
+                    "" => None,
+                    port => Some(parse_port(port)?),
+                };
+                if port == Some(self.app_run_port) {
+                    return Err(InstallerError::Validation(format!("internal_port 不能是 {}", self.app_run_port)));
+                }
+                self.internal_port = port;
+            },
+            "proxy_hold_timeout" => {
+                self.proxy_hold_timeout = match value.trim() {
+                    "" => DEFAULT_PROXY_HOLD_TIMEOUT,
+                    timeout => timeout.parse().map_err(|_| {
+                        InstallerError::Validation(format!("无效的秒数 {}", timeout))
+                    })?,
+                };
+            },
+            "tags" => {
+                self.tags = args;
+            },
+            "auto_update.interval" | "auto_update.window" => {
+                set_auto_update(&mut self.auto_update, key, value)?;
+            },
+            _ if key.starts_with("env.") => {
+                let name = &key["env.".len()..];
+                if name.is_empty() || name.contains('=') {
+                    return Err(InstallerError::Validation(format!("无效的环境变量名 {}", name)));
+                }
+                if value.is_empty() {
+                    self.env.remove(name);
+                } else {
+                    self.env.insert(name.to_string(), value.to_string());
+                }
+            },
+            _ => {
+                return Err(InstallerError::Validation(format!("不支持的配置项 {}，只支持 jvm_args、app_args、blue_green_ports、internal_port、proxy_hold_timeout、tags、auto_update.interval、auto_update.window 和 env.<NAME>", key)));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// 设置自动升级配置中的 `auto_update.interval` 或 `auto_update.window`，值为空时不再自动升级或不限制升级的时间
+fn set_auto_update(auto_update: &mut Option<AutoUpdate>, key: &str, value: &str) -> Result<(), InstallerError> {
+    match key {
+        "auto_update.interval" => {
+            if value.trim().is_empty() {
+                *auto_update = None;
+            } else {
+                watch::parse_interval(value)?;
+                let window = auto_update.take().and_then(|auto_update| auto_update.window);
+                *auto_update = Some(AutoUpdate {
+                    interval: value.trim().to_string(),
+                    window,
+                });
+            }
+        },
+        "auto_update.window" => {
+            let auto_update = auto_update.as_mut().ok_or_else(|| {
+                InstallerError::Validation("请先设置 auto_update.interval".to_string())
+            })?;
+            if value.trim().is_empty() {
+                auto_update.window = None;
+            } else {
+                watch::MaintenanceWindow::parse(value)?;
+                auto_update.window = Some(value.trim().to_string());
+            }
+        },
+        _ => {
+            return Err(InstallerError::Validation(format!("不支持的全局配置项 {}，只支持 auto_update.interval 和 auto_update.window", key)));
+        }
+    }
+
+    Ok(())
+}
+
+fn default_grace_period() -> u64 {
+    DEFAULT_GRACE_PERIOD
+}
+
+fn default_proxy_hold_timeout() -> u64 {
+    DEFAULT_PROXY_HOLD_TIMEOUT
+}
+
+/// 按 shell 的规则分隔参数，如 `-Dname="a b" -Xmx512m` 分隔为 `-Dname=a b` 和 `-Xmx512m`
+fn split_args(key: &str, value: &str) -> Result<Vec<String>, InstallerError> {
+    shell_words::split(value).map_err(|_| {
+        InstallerError::Validation(format!("{} 中的引号没有闭合：{}", key, value))
+    })
+}
+
+/// 解析端口号，端口号必须在 1 到 65535 之间
+fn parse_port(port: &str) -> Result<u32, InstallerError> {
+    match port.parse::<u32>() {
+        Ok(port) if port > 0 && port <= 65535 => Ok(port),
+        _ => Err(InstallerError::Validation(format!("无效的端口号 {}", port))),
+    }
+}
+
+/// 自动升级的配置
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct AutoUpdate {
+    /// 检查更新的时间间隔，如 `30m`、`6h`、`1d`
+    pub interval: String,
+    /// 维护窗口，如 `02:00-04:00`，只在此时间段内升级 APP，使用服务器的本地时间；为空时任何时间都可升级
+    #[serde(default)]
+    pub window: Option<String>,
+}
+
+/// 健康检查的方式
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthCheckType {
+    /// 端口能建立 TCP 连接
+    Tcp,
+    /// 向 `path` 发送 HTTP GET 请求，返回 200 状态码
+    Http,
+}
+
+/// APP 的健康检查配置，如：
+/// 
+/// ```toml
+/// [installers.health_check]
+/// type = "http"
+/// path = "/actuator/health"
+/// timeout = 60
+/// ```
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct HealthCheck {
+    #[serde(rename = "type")]
+    pub check_type: HealthCheckType,
+    /// HTTP 健康检查的路径，只在 `type` 为 `http` 时有效
+    #[serde(default = "default_health_check_path")]
+    pub path: String,
+    /// 等待 APP 通过健康检查的秒数，超时后认为 APP 没有正常运行
+    #[serde(default = "default_health_check_timeout")]
+    pub timeout: u64,
+}
+
+fn default_health_check_path() -> String {
+    DEFAULT_HEALTH_CHECK_PATH.to_string()
+}
+
+fn default_health_check_timeout() -> u64 {
+    DEFAULT_HEALTH_CHECK_TIMEOUT
+}
+
+impl Default for HealthCheck {
+    /// 默认检查端口能否建立 TCP 连接
     fn default() -> Self {
-        Self::new()
+        HealthCheck {
+            check_type: HealthCheckType::Tcp,
+            path: default_health_check_path(),
+            timeout: default_health_check_timeout(),
+        }
     }
 }
 
 impl InstallerConfig {
 
     // 使用默认的配置文件
-    pub fn new() -> Self {
+    pub fn new() -> Result<Self, InstallerError> {
         Self::from(INSTALLER_CONFIG_FILE_NAME)
     }
 
-    pub fn from(file_name: &str) -> Self {
-        File::open(file_name).map(|mut file| {
-            // 如果文件存在，则读取文件内容
-            let mut content = String::new();
-            file.read_to_string(&mut content).unwrap_or(0);
-            content
-        }).map(|content| {
-            toml::from_str::<InstallerData>(&content).unwrap_or_else(|_| {
-                // 不是预期的 toml 格式，则创建默认设置
-                Self::create_default_config(file_name).data
-            })
-        }).map(|data| {
-                InstallerConfig {
-                file_name: file_name.to_string(),
-                data,
-            }
-        }).unwrap_or_else(|_|{
-            // 如果文件不存在，则创建一个文件，并返回文件的内容
-            Self::create_default_config(file_name)
+    /// 读取默认的配置文件，但不创建或修改配置文件，详见 `InstallerConfig::load_from`
+    pub fn load() -> Result<Option<Self>, InstallerError> {
+        Self::load_from(INSTALLER_CONFIG_FILE_NAME)
+    }
+
+    /// 读取配置文件，如果文件不存在则返回 `None`，用于 `update --dry-run` 等不能修改配置文件的命令。
+    /// 
+    /// 与 `from` 不同，文件不存在时不会创建默认的配置文件。
+    pub fn load_from(file_name: &str) -> Result<Option<Self>, InstallerError> {
+        Ok(Self::read(file_name)?.map(|data| InstallerConfig {
+            file_name: file_name.to_string(),
+            data,
+        }))
+    }
+
+    /// 持有文件锁读取默认的配置文件，并使用 `f` 修改配置，
+    /// 保证读取和保存之间不会有其他进程或线程修改配置文件，如守护进程、`watch` 命令或同时升级的其他 APP。
+    pub fn modify<F, R>(f: F) -> Result<R, InstallerError> where F: FnOnce(&mut InstallerConfig) -> Result<R, InstallerError> {
+        Self::modify_in(INSTALLER_CONFIG_FILE_NAME, f)
+    }
+
+    fn modify_in<F, R>(file_name: &str, f: F) -> Result<R, InstallerError> where F: FnOnce(&mut InstallerConfig) -> Result<R, InstallerError> {
+        let _lock = FileLock::acquire(Path::new(&format!("{}.lock", file_name)))?;
+        let mut installer_config = Self::from(file_name)?;
+        f(&mut installer_config)
+    }
+
+    /// 读取配置文件，如果文件不存在或者内容为空，则创建默认的配置文件。
+    /// 
+    /// 如果文件不是预期的 toml 格式，则返回错误，不能使用默认配置覆盖已注册的 installer。
+    pub fn from(file_name: &str) -> Result<Self, InstallerError> {
+        match Self::load_from(file_name)? {
+            Some(installer_config) => Ok(installer_config),
+            None => Self::create_default_config(file_name),
+        }
+    }
+
+    /// 读取配置文件中的数据，如果文件不存在或者内容为空，则返回 `None`
+    fn read(file_name: &str) -> Result<Option<InstallerData>, InstallerError> {
+        let content = match fs::read_to_string(file_name) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(InstallerError::Io(e)),
+        };
+        if content.trim().is_empty() {
+            return Ok(None);
+        }
+
+        toml::from_str::<InstallerData>(&content).map(Some).map_err(|e| {
+            InstallerError::Validation(format!("{} 不是有效的配置文件，{}", file_name, e))
         })
     }
 
-    pub fn add(&mut self, installer_info: InstallerInfo) {
+    pub fn add(&mut self, installer_info: InstallerInfo) -> Result<(), InstallerError> {
         let installer_config = Installer {
-            url: installer_info.url.unwrap(),
+            url: installer_info.url.unwrap_or_default(),
             installer_token: installer_info.installer_token,
             app_name: installer_info.app_name,
             app_version: installer_info.app_version,
@@ -85,17 +448,44 @@ impl InstallerConfig {
             jdk_name: installer_info.jdk_name,
             jdk_version: installer_info.jdk_version,
             jdk_file_name: installer_info.jdk_file_name,
+            app_file_sha256: installer_info.app_file_sha256,
+            jdk_file_sha256: installer_info.jdk_file_sha256,
+            restart_count: 0,
+            grace_period: DEFAULT_GRACE_PERIOD,
+            jvm_args: Vec::new(),
+            app_args: Vec::new(),
+            blue_green_ports: Vec::new(),
+            internal_port: None,
+            proxy_hold_timeout: DEFAULT_PROXY_HOLD_TIMEOUT,
+            tags: Vec::new(),
+            health_check: None,
+            auto_update: None,
+            env: BTreeMap::new(),
+            history: Vec::new(),
         };
 
         self.data.installers.push(installer_config);
-        self.save();
+        self.save()
     }
 
-    pub fn update(&mut self, app_run_port: u32, installer_info: InstallerInfo) {
+    /// 更新 installer 信息。
+    /// 
+    /// 如果 Spring Boot jar 或 JDK 的版本有变化，则将之前的版本存到 `history` 中，
+    /// 最多保留 `MAX_HISTORY_LEN` 个历史版本。
+    pub fn update(&mut self, app_run_port: u32, installer_info: InstallerInfo) -> Result<(), InstallerError> {
        if let Some(mut elem) = self.data.installers.iter_mut().find(|elem| {
             elem.app_run_port == app_run_port
        }) {
-			elem.url = installer_info.url.unwrap();
+            if elem.app_version != installer_info.app_version || elem.jdk_version != installer_info.jdk_version {
+                let deployed_version = elem.deployed_version();
+                elem.history.push(deployed_version);
+                if elem.history.len() > MAX_HISTORY_LEN {
+                    let len = elem.history.len();
+                    elem.history.drain(..len - MAX_HISTORY_LEN);
+                }
+            }
+
+			elem.url = installer_info.url.unwrap_or_default();
             elem.installer_token = installer_info.installer_token;
             elem.app_name = installer_info.app_name;
             elem.app_version = installer_info.app_version;
@@ -103,8 +493,37 @@ impl InstallerConfig {
             elem.jdk_name = installer_info.jdk_name;
             elem.jdk_version = installer_info.jdk_version;
             elem.jdk_file_name = installer_info.jdk_file_name;
-            self.save();
+            elem.app_file_sha256 = installer_info.app_file_sha256;
+            elem.jdk_file_sha256 = installer_info.jdk_file_sha256;
+            self.save()?;
 		}
+        Ok(())
+    }
+
+    /// 回滚到上一个部署的版本，并从 `history` 中删除上一个版本。
+    /// 
+    /// 回滚后当前版本不会存到 `history` 中。如果没有上一个版本，则返回 `None`，否则返回回滚后的版本。
+    pub fn rollback(&mut self, app_run_port: u32) -> Result<Option<DeployedVersion>, InstallerError> {
+        let elem = match self.data.installers.iter_mut().find(|elem| elem.app_run_port == app_run_port) {
+            Some(elem) => elem,
+            None => return Ok(None),
+        };
+        let previous = match elem.history.pop() {
+            Some(previous) => previous,
+            None => return Ok(None),
+        };
+
+        elem.app_name = previous.app_name.clone();
+        elem.app_version = previous.app_version.clone();
+        elem.app_file_name = previous.app_file_name.clone();
+        elem.jdk_name = previous.jdk_name.clone();
+        elem.jdk_version = previous.jdk_version.clone();
+        elem.jdk_file_name = previous.jdk_file_name.clone();
+        elem.app_file_sha256 = previous.app_file_sha256.clone();
+        elem.jdk_file_sha256 = previous.jdk_file_sha256.clone();
+
+        self.save()?;
+        Ok(Some(previous))
     }
 
     /// 注意，一台主机上的一个端口上只能部署一个应用，所以可以根据 port 唯一定义一个 installer
@@ -118,7 +537,61 @@ impl InstallerConfig {
         })
     }
 
-    pub fn remove_by_installer_token(&mut self, installer_token: &str) {
+    /// 获取部署 `app_name` 的 installer，一个 APP 可以部署在多个端口上
+    pub fn get_by_app_name(&self, app_name: &str) -> Vec<&Installer> {
+        self.data.installers.iter()
+            .filter(|installer| installer.app_name == app_name)
+            .collect()
+    }
+
+    /// 获取有 `tag` 标签的 installer
+    pub fn get_by_tag(&self, tag: &str) -> Vec<&Installer> {
+        self.data.installers.iter()
+            .filter(|installer| installer.tags.iter().any(|elem| elem == tag))
+            .collect()
+    }
+
+    /// 获取 `selector` 选择的 installer
+    pub fn select(&self, selector: &Selector) -> Vec<&Installer> {
+        match selector {
+            Selector::All => self.data.installers.iter().collect(),
+            Selector::App(app_name) => self.get_by_app_name(app_name),
+            Selector::Tag(tag) => self.get_by_tag(tag),
+        }
+    }
+
+    /// 设置端口上的 installer 运行 APP 时使用的配置项，详见 `Installer::set_option`
+    pub fn set_option(&mut self, app_run_port: u32, key: &str, value: &str) -> Result<(), InstallerError> {
+        let installer = self.data.installers.iter_mut()
+            .find(|elem| elem.app_run_port == app_run_port)
+            .ok_or_else(|| InstallerError::NotFound(format!("端口 {} 上未注册 installer", app_run_port)))?;
+
+        installer.set_option(key, value)?;
+        self.save()
+    }
+
+    /// 设置所有 installer 默认使用的配置项，只支持 `auto_update.interval` 和 `auto_update.window`，
+    /// installer 中的配置会覆盖此配置
+    pub fn set_global_option(&mut self, key: &str, value: &str) -> Result<(), InstallerError> {
+        set_auto_update(&mut self.data.auto_update, key, value)?;
+        self.save()
+    }
+
+    /// 累加 APP 的重启次数，并返回累加后的重启次数
+    pub fn increase_restart_count(&mut self, app_run_port: u32) -> Result<u32, InstallerError> {
+        let restart_count = match self.data.installers.iter_mut().find(|elem| elem.app_run_port == app_run_port) {
+            Some(installer) => {
+                installer.restart_count += 1;
+                installer.restart_count
+            },
+            None => return Ok(0),
+        };
+
+        self.save()?;
+        Ok(restart_count)
+    }
+
+    pub fn remove_by_installer_token(&mut self, installer_token: &str) -> Result<(), InstallerError> {
         let installers = &mut self.data.installers;
 
         match installers.iter().position(|item| item.installer_token == installer_token) {
@@ -128,13 +601,13 @@ impl InstallerConfig {
             }
         };
 
-        self.save();
+        self.save()
     }
 
     /// 删除所有 installer
     /// 可通过函数来判断每一个 installer 是否可以删除，如果返回 true，则删除；如果返回 false 则不删除
     /// 在每一个操作中删除配置信息，因此可以不需要统一删除
-    pub fn remove_all<F>(&mut self, mut f: F) where F: FnMut(&Installer) -> bool {
+    pub fn remove_all<F>(&mut self, mut f: F) -> Result<(), InstallerError> where F: FnMut(&Installer) -> bool {
         let installers = &mut self.data.installers;
 
         installers.retain(|installer| {
@@ -142,35 +615,55 @@ impl InstallerConfig {
         });
 
         // 因为在 f 函数中删除了配置信息，所以这里可以不在执行 save 操作
-        self.save();
+        self.save()
     }
 
     pub fn get_data(&self) -> &InstallerData {
         &self.data
     }
 
-    /// 创建一个默认的配置
-    fn create_default_config(file_name: &str) -> Self {
-        let net_interface = net::get_interface_address().unwrap();
+    /// 获取 installer 的自动升级配置，如果 installer 中没有配置，则使用全局配置
+    pub fn get_auto_update<'a>(&'a self, installer: &'a Installer) -> Option<&'a AutoUpdate> {
+        installer.auto_update.as_ref().or(self.data.auto_update.as_ref())
+    }
+
+    /// 创建一个默认的配置。
+    /// 
+    /// 如果其他进程已同时创建了配置文件，则读取此配置文件，不覆盖其他进程的修改。
+    fn create_default_config(file_name: &str) -> Result<Self, InstallerError> {
+        let net_interface = net::get_interface_address()
+            .ok_or_else(|| InstallerError::Network("无法获取本机网卡的 MAC 地址".to_string()))?;
         let data = InstallerData {
             server_token: net_interface.mac_address,
-            installers: Vec::<Installer>::new()
+            installers: Vec::<Installer>::new(),
+            auto_update: None,
         };
-        let installer_config = InstallerConfig {
-            file_name: file_name.to_string(),
-            data,
+        let toml_content = toml::to_vec(&data)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        // 内容为空的文件中没有注册 installer，可以直接覆盖
+        let result = if Path::new(file_name).exists() {
+            file::write_atomically(Path::new(file_name), &toml_content)
+        } else {
+            file::create_atomically(Path::new(file_name), &toml_content)
         };
-
-        installer_config.save();
-
-        installer_config
+        match result {
+            Ok(()) => Ok(InstallerConfig {
+                file_name: file_name.to_string(),
+                data,
+            }),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Self::from(file_name),
+            Err(e) => Err(InstallerError::Io(e)),
+        }
     }
 
-    fn save(&self) {
-        let toml_content = toml::to_vec(&self.data).unwrap();
+    /// 先写入临时文件再重命名，防止守护进程等读到写了一半的文件
+    fn save(&self) -> Result<(), InstallerError> {
+        let toml_content = toml::to_vec(&self.data)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
 
-        let mut file = File::create(&self.file_name).expect("failed to create installer_config.toml file");
-        file.write_all(toml_content.as_slice()).expect("failed to save installer_config.toml content");
+        file::write_atomically(Path::new(&self.file_name), &toml_content)?;
+        Ok(())
     }
 }
 
@@ -180,10 +673,12 @@ mod tests {
     use std::path::Path;
     use std::fs::{self, File};
     use std::io::prelude::*;
+    use std::thread;
 
     use crate::util::net;
     use crate::http::client::InstallerInfo;
-    use super::{InstallerConfig, InstallerData, Installer};
+    use crate::config::MAX_HISTORY_LEN;
+    use super::{InstallerConfig, InstallerData, Installer, HealthCheck, HealthCheckType, AutoUpdate, Selector};
 
     /// 注意，测试用例中的 config file name 不能相同，
     /// 因为用例中有删除 config file 的代码，
@@ -196,11 +691,12 @@ mod tests {
 
         let mac_address = net::get_interface_address().unwrap().mac_address;
 
-        let installer_config = InstallerConfig::from(file_name);
+        let installer_config = InstallerConfig::from(file_name)?;
         assert_eq!(file_name, installer_config.file_name);
         assert_eq!(InstallerData {
             server_token: mac_address,
             installers: Vec::<Installer>::new(),
+            auto_update: None,
         }, installer_config.data);
         
         assert!(Path::new(file_name).exists());
@@ -227,11 +723,12 @@ mod tests {
 
         assert!(Path::new(file_name).exists());
 
-        let installer_config = InstallerConfig::from(file_name);
+        let installer_config = InstallerConfig::from(file_name)?;
         assert_eq!(file_name, installer_config.file_name);
         assert_eq!(InstallerData {
             server_token: net::get_interface_address().unwrap().mac_address,
             installers: Vec::<Installer>::new(),
+            auto_update: None,
         }, installer_config.data);
 
         assert!(Path::new(file_name).exists());
@@ -264,11 +761,12 @@ mod tests {
 
         assert!(Path::new(file_name).exists());
 
-        let installer_config = InstallerConfig::from(file_name);
+        let installer_config = InstallerConfig::from(file_name)?;
         assert_eq!(file_name, installer_config.file_name);
         assert_eq!(InstallerData {
             server_token: "11".to_string(),
             installers: Vec::<Installer>::new(),
+            auto_update: None,
         }, installer_config.data);
 
         assert!(Path::new(file_name).exists());
@@ -299,24 +797,9 @@ mod tests {
 
         assert!(Path::new(file_name).exists());
 
-        let installer_config = InstallerConfig::from(file_name);
-        assert_eq!(file_name, installer_config.file_name);
-        assert_eq!(InstallerData {
-            server_token: net::get_interface_address().unwrap().mac_address,
-            installers: Vec::<Installer>::new(),
-        }, installer_config.data);
-
-        assert!(Path::new(file_name).exists());
-
-        // 判断文件中的内容
-        let mut file = File::open(file_name)?;
-        let mut content = String::new();
-        file.read_to_string(&mut content)?;
-
-        let mac_address = net::get_interface_address().unwrap().mac_address;
-        let server_token_part = &format!("server_token = \"{}\"", mac_address);
-        assert!(content.contains(server_token_part));
-        assert!(!content.contains("[[installers]]"));
+        // 不是预期的 toml 格式时返回错误，不能使用默认配置覆盖文件
+        assert!(InstallerConfig::from(file_name).is_err());
+        assert_eq!(toml_content, fs::read_to_string(file_name)?);
 
         // 删除 installer_config.toml 文件
         fs::remove_file(file_name)?;
@@ -326,7 +809,7 @@ mod tests {
     #[test]
     fn add_a_installer_success() -> Result<(), Box<dyn std::error::Error>> {
         let file_name = "add_a_installer_success.toml";
-        let mut installer_config = InstallerConfig::from(file_name);
+        let mut installer_config = InstallerConfig::from(file_name)?;
 
         let installer_info = InstallerInfo {
             url: Some("1".to_string()),
@@ -338,8 +821,10 @@ mod tests {
             jdk_name: "7".to_string(),
             jdk_version: "8".to_string(),
             jdk_file_name: "9".to_string(),
+            app_file_sha256: None,
+            jdk_file_sha256: None,
         };
-        installer_config.add(installer_info);
+        installer_config.add(installer_info)?;
 
         assert_eq!(1, installer_config.get_data().installers.len());
 
@@ -361,16 +846,388 @@ mod tests {
         assert!(content.contains("jdk_name = \"7\""));
         assert!(content.contains("jdk_version = \"8\""));
         assert!(content.contains("jdk_file_name = \"9\""));
+        assert!(content.contains("grace_period = 30"));
         
         // 删除 installer_config.toml 文件
         fs::remove_file(file_name)?;
         Ok(())
     }
 
+    #[test]
+    fn from_config_file_installer_without_grace_period() -> Result<(), Box<dyn std::error::Error>> {
+        let file_name = "from_config_file_installer_without_grace_period.toml";
+        // 旧版的配置文件中没有 grace_period 字段
+        let toml_content = r#"
+        server_token = "11"
+
+        [[installers]]
+        url = "1"
+        installer_token = "2"
+        app_name = "3"
+        app_version = "4"
+        app_file_name = "5"
+        app_run_port = 6
+        jdk_name = "7"
+        jdk_version = "8"
+        jdk_file_name = "9"
+        "#;
+        let mut file = File::create(file_name)?;
+        file.write_all(toml_content.as_bytes()).expect("failed to save installer_config.toml content");
+
+        let installer_config = InstallerConfig::from(file_name)?;
+        let installer = installer_config.get_by_port(6).unwrap();
+        assert_eq!(0, installer.restart_count);
+        assert_eq!(30, installer.grace_period);
+
+        // 删除 installer_config.toml 文件
+        fs::remove_file(file_name)?;
+        Ok(())
+    }
+
+    #[test]
+    fn from_config_file_installer_with_health_check() -> Result<(), Box<dyn std::error::Error>> {
+        let file_name = "from_config_file_installer_with_health_check.toml";
+        let toml_content = r#"
+        server_token = "11"
+
+        [[installers]]
+        url = "1"
+        installer_token = "2"
+        app_name = "3"
+        app_version = "4"
+        app_file_name = "5"
+        app_run_port = 6
+        jdk_name = "7"
+        jdk_version = "8"
+        jdk_file_name = "9"
+
+        [installers.health_check]
+        type = "http"
+        "#;
+        let mut file = File::create(file_name)?;
+        file.write_all(toml_content.as_bytes()).expect("failed to save installer_config.toml content");
+
+        let mut installer_config = InstallerConfig::from(file_name)?;
+        let health_check = HealthCheck {
+            check_type: HealthCheckType::Http,
+            path: "/actuator/health".to_string(),
+            timeout: 60,
+        };
+        assert_eq!(Some(&health_check), installer_config.get_by_port(6).unwrap().health_check.as_ref());
+
+        // 升级后依然保留健康检查配置
+        installer_config.update(6, new_installer_info("0.2.0"))?;
+        let installer_config = InstallerConfig::from(file_name)?;
+        assert_eq!(Some(&health_check), installer_config.get_by_port(6).unwrap().health_check.as_ref());
+
+        // 删除 installer_config.toml 文件
+        fs::remove_file(file_name)?;
+        Ok(())
+    }
+
+    #[test]
+    fn set_option_success() -> Result<(), Box<dyn std::error::Error>> {
+        let file_name = "set_option_success.toml";
+        let mut installer_config = InstallerConfig::from(file_name)?;
+        installer_config.add(new_installer_info("0.1.0"))?;
+
+        installer_config.set_option(6, "jvm_args", r#"-Xmx512m  -XX:+UseG1GC "-Dapp.name=demo app""#)?;
+        installer_config.set_option(6, "app_args", "--spring.profiles.active=prod")?;
+        installer_config.set_option(6, "env.JAVA_TOOL_OPTIONS", "-Dfile.encoding=UTF-8")?;
+        installer_config.set_option(6, "env.TZ", "Asia/Shanghai")?;
+        // 值为空时删除环境变量
+        installer_config.set_option(6, "env.TZ", "")?;
+        installer_config.set_option(6, "blue_green_ports", "18080 18081")?;
+        installer_config.set_option(6, "internal_port", "18082")?;
+        installer_config.set_option(6, "proxy_hold_timeout", "0")?;
+        installer_config.set_option(6, "tags", "env=staging  team=payments")?;
+
+        // 从配置文件中读取
+        let installer_config = InstallerConfig::from(file_name)?;
+        let installer = installer_config.get_by_port(6).unwrap();
+        assert_eq!(vec!["-Xmx512m", "-XX:+UseG1GC", "-Dapp.name=demo app"], installer.jvm_args);
+        assert_eq!(vec!["--spring.profiles.active=prod"], installer.app_args);
+        assert_eq!(1, installer.env.len());
+        assert_eq!("-Dfile.encoding=UTF-8", installer.env["JAVA_TOOL_OPTIONS"]);
+        assert_eq!(vec![18080, 18081], installer.blue_green_ports);
+        assert!(installer.is_blue_green());
+        assert_eq!(Some(18082), installer.internal_port);
+        assert_eq!(0, installer.proxy_hold_timeout);
+        assert_eq!(vec!["env=staging", "team=payments"], installer.tags);
+        // 使用蓝绿部署时，忽略内部端口
+        assert_eq!(vec![18080, 18081], installer.backend_ports());
+
+        // 删除 installer_config.toml 文件
+        fs::remove_file(file_name)?;
+        Ok(())
+    }
+
+    #[test]
+    fn backend_ports_success() -> Result<(), Box<dyn std::error::Error>> {
+        let file_name = "backend_ports_success.toml";
+        let mut installer_config = InstallerConfig::from(file_name)?;
+        installer_config.add(new_installer_info("0.1.0"))?;
+        assert!(!installer_config.get_by_port(6).unwrap().uses_proxy());
+
+        installer_config.set_option(6, "internal_port", "18082")?;
+        let installer = installer_config.get_by_port(6).unwrap();
+        assert!(installer.uses_proxy());
+        assert!(!installer.is_blue_green());
+        assert_eq!(vec![18082], installer.backend_ports());
+
+        installer_config.set_option(6, "internal_port", "")?;
+        assert!(!installer_config.get_by_port(6).unwrap().uses_proxy());
+
+        // 删除 installer_config.toml 文件
+        fs::remove_file(file_name)?;
+        Ok(())
+    }
+
+    #[test]
+    fn set_auto_update_success() -> Result<(), Box<dyn std::error::Error>> {
+        let file_name = "set_auto_update_success.toml";
+        let mut installer_config = InstallerConfig::from(file_name)?;
+        installer_config.add(new_installer_info("0.1.0"))?;
+
+        // 必须先设置时间间隔
+        assert!(installer_config.set_option(6, "auto_update.window", "02:00-04:00").is_err());
+        assert!(installer_config.set_option(6, "auto_update.interval", "6x").is_err());
+
+        installer_config.set_option(6, "auto_update.interval", "6h")?;
+        installer_config.set_option(6, "auto_update.window", "02:00-04:00")?;
+        assert!(installer_config.set_option(6, "auto_update.window", "02:00").is_err());
+        // 修改时间间隔时，保留维护窗口
+        installer_config.set_option(6, "auto_update.interval", "1d")?;
+
+        // 从配置文件中读取
+        let installer_config = InstallerConfig::from(file_name)?;
+        let installer = installer_config.get_by_port(6).unwrap();
+        assert_eq!(Some(&AutoUpdate {
+            interval: "1d".to_string(),
+            window: Some("02:00-04:00".to_string()),
+        }), installer_config.get_auto_update(installer));
+
+        // 删除 installer_config.toml 文件
+        fs::remove_file(file_name)?;
+        Ok(())
+    }
+
+    #[test]
+    fn load_from_not_create_file() -> Result<(), Box<dyn std::error::Error>> {
+        let file_name = "load_from_not_create_file.toml";
+        assert!(InstallerConfig::load_from(file_name)?.is_none());
+        assert!(!Path::new(file_name).exists());
+
+        let mut installer_config = InstallerConfig::from(file_name)?;
+        installer_config.add(new_installer_info("0.1.0"))?;
+        let installer_config = InstallerConfig::load_from(file_name)?.unwrap();
+        assert!(installer_config.get_by_port(6).is_some());
+
+        // 不是预期的 toml 格式时返回错误，不覆盖配置文件
+        fs::write(file_name, "installers = 1")?;
+        assert!(InstallerConfig::load_from(file_name).is_err());
+        assert_eq!("installers = 1", fs::read_to_string(file_name)?);
+
+        // 删除 installer_config.toml 文件
+        fs::remove_file(file_name)?;
+        Ok(())
+    }
+
+    #[test]
+    fn modify_concurrently_keep_all_installers() -> Result<(), Box<dyn std::error::Error>> {
+        let file_name = "modify_concurrently_keep_all_installers.toml";
+
+        // 多个线程同时修改时，不能覆盖其他线程的修改
+        thread::scope(|scope| {
+            for port in 1..=8 {
+                scope.spawn(move || {
+                    InstallerConfig::modify_in(file_name, |installer_config| {
+                        installer_config.add(InstallerInfo {
+                            app_run_port: port,
+                            ..new_installer_info("0.1.0")
+                        })
+                    }).unwrap();
+                });
+            }
+        });
+
+        let installer_config = InstallerConfig::from(file_name)?;
+        assert_eq!(8, installer_config.get_data().installers.len());
+
+        // 删除 installer_config.toml 文件和锁文件
+        fs::remove_file(file_name)?;
+        fs::remove_file(format!("{}.lock", file_name))?;
+        Ok(())
+    }
+
+    #[test]
+    fn set_global_auto_update_success() -> Result<(), Box<dyn std::error::Error>> {
+        let file_name = "set_global_auto_update_success.toml";
+        let mut installer_config = InstallerConfig::from(file_name)?;
+        installer_config.add(new_installer_info("0.1.0"))?;
+
+        assert!(installer_config.set_global_option("jvm_args", "-Xmx512m").is_err());
+        installer_config.set_global_option("auto_update.interval", "6h")?;
+
+        // 从配置文件中读取，installer 中没有配置时使用全局配置
+        let mut installer_config = InstallerConfig::from(file_name)?;
+        assert_eq!(Some(&AutoUpdate {
+            interval: "6h".to_string(),
+            window: None,
+        }), installer_config.get_data().auto_update.as_ref());
+        let installer = installer_config.get_by_port(6).unwrap();
+        assert_eq!("6h", installer_config.get_auto_update(installer).unwrap().interval);
+
+        installer_config.set_global_option("auto_update.interval", "")?;
+        assert_eq!(None, installer_config.get_data().auto_update);
+
+        // 删除 installer_config.toml 文件
+        fs::remove_file(file_name)?;
+        Ok(())
+    }
+
+    #[test]
+    fn get_auto_update_from_global_config() -> Result<(), Box<dyn std::error::Error>> {
+        let file_name = "get_auto_update_from_global_config.toml";
+        let toml_content = r#"
+        server_token = "1"
+
+        [[installers]]
+        url = "2"
+        installer_token = "3"
+        app_name = "4"
+        app_version = "5"
+        app_file_name = "6"
+        app_run_port = 7
+        jdk_name = "8"
+        jdk_version = "9"
+        jdk_file_name = "10"
+
+        [[installers]]
+        url = "2"
+        installer_token = "3"
+        app_name = "4"
+        app_version = "5"
+        app_file_name = "6"
+        app_run_port = 8
+        jdk_name = "8"
+        jdk_version = "9"
+        jdk_file_name = "10"
+
+        [installers.auto_update]
+        interval = "30m"
+
+        [auto_update]
+        interval = "6h"
+        window = "02:00-04:00"
+        "#;
+        File::create(file_name)?.write_all(toml_content.as_bytes())?;
+
+        let installer_config = InstallerConfig::from(file_name)?;
+        // 使用全局配置
+        let installer = installer_config.get_by_port(7).unwrap();
+        assert_eq!("6h", installer_config.get_auto_update(installer).unwrap().interval);
+        // installer 中的配置覆盖全局配置
+        let installer = installer_config.get_by_port(8).unwrap();
+        let auto_update = installer_config.get_auto_update(installer).unwrap();
+        assert_eq!("30m", auto_update.interval);
+        assert_eq!(None, auto_update.window);
+
+        // 删除 installer_config.toml 文件
+        fs::remove_file(file_name)?;
+        Ok(())
+    }
+
+    #[test]
+    fn select_success() -> Result<(), Box<dyn std::error::Error>> {
+        let file_name = "select_success.toml";
+        let toml_content = r#"
+        server_token = "1"
+
+        [[installers]]
+        url = "2"
+        installer_token = "3"
+        app_name = "@user/a"
+        app_version = "5"
+        app_file_name = "6"
+        app_run_port = 7
+        jdk_name = "8"
+        jdk_version = "9"
+        jdk_file_name = "10"
+        tags = ["env=staging", "team=payments"]
+
+        [[installers]]
+        url = "2"
+        installer_token = "3"
+        app_name = "@user/a"
+        app_version = "5"
+        app_file_name = "6"
+        app_run_port = 8
+        jdk_name = "8"
+        jdk_version = "9"
+        jdk_file_name = "10"
+        tags = ["env=prod", "team=payments"]
+
+        [[installers]]
+        url = "2"
+        installer_token = "3"
+        app_name = "@user/b"
+        app_version = "5"
+        app_file_name = "6"
+        app_run_port = 9
+        jdk_name = "8"
+        jdk_version = "9"
+        jdk_file_name = "10"
+        "#;
+        File::create(file_name)?.write_all(toml_content.as_bytes())?;
+
+        let installer_config = InstallerConfig::from(file_name)?;
+        let ports = |selector: Selector| -> Vec<u32> {
+            installer_config.select(&selector).iter().map(|installer| installer.app_run_port).collect()
+        };
+        assert_eq!(vec![7, 8, 9], ports(Selector::All));
+        assert_eq!(vec![7, 8], ports(Selector::App("@user/a".to_string())));
+        assert_eq!(vec![9], ports(Selector::App("@user/b".to_string())));
+        assert!(ports(Selector::App("@user/c".to_string())).is_empty());
+        assert_eq!(vec![7, 8], ports(Selector::Tag("team=payments".to_string())));
+        assert_eq!(vec![8], ports(Selector::Tag("env=prod".to_string())));
+        // 标签需完全匹配
+        assert!(ports(Selector::Tag("env".to_string())).is_empty());
+
+        // 删除 installer_config.toml 文件
+        fs::remove_file(file_name)?;
+        Ok(())
+    }
+
+    #[test]
+    fn set_option_invalid() -> Result<(), Box<dyn std::error::Error>> {
+        let file_name = "set_option_invalid.toml";
+        let mut installer_config = InstallerConfig::from(file_name)?;
+        installer_config.add(new_installer_info("0.1.0"))?;
+
+        assert!(installer_config.set_option(6, "not_exist", "1").is_err());
+        assert!(installer_config.set_option(6, "app_args", "--server.port=8080").is_err());
+        assert!(installer_config.set_option(6, "jvm_args", r#"-Dapp.name="demo"#).is_err());
+        assert!(installer_config.set_option(6, "env.", "1").is_err());
+        assert!(installer_config.set_option(6, "blue_green_ports", "18080").is_err());
+        assert!(installer_config.set_option(6, "blue_green_ports", "18080 18080").is_err());
+        assert!(installer_config.set_option(6, "blue_green_ports", "18080 6").is_err());
+        assert!(installer_config.set_option(6, "blue_green_ports", "18080 a").is_err());
+        assert!(installer_config.set_option(6, "blue_green_ports", "18080 65536").is_err());
+        assert!(installer_config.set_option(6, "internal_port", "6").is_err());
+        assert!(installer_config.set_option(6, "internal_port", "0").is_err());
+        assert!(installer_config.set_option(6, "proxy_hold_timeout", "-1").is_err());
+        assert!(installer_config.set_option(8080, "jvm_args", "-Xmx512m").is_err());
+
+        // 删除 installer_config.toml 文件
+        fs::remove_file(file_name)?;
+        Ok(())
+    }
+
     #[test]
     fn update_a_installer_success() -> Result<(), Box<dyn std::error::Error>> {
         let file_name = "update_a_installer_success.toml";
-        let mut installer_config = InstallerConfig::from(file_name);
+        let mut installer_config = InstallerConfig::from(file_name)?;
 
         let installer_info = InstallerInfo {
             url: Some("1".to_string()),
@@ -382,8 +1239,10 @@ mod tests {
             jdk_name: "7".to_string(),
             jdk_version: "8".to_string(),
             jdk_file_name: "9".to_string(),
+            app_file_sha256: None,
+            jdk_file_sha256: None,
         };
-        installer_config.add(installer_info);
+        installer_config.add(installer_info)?;
 
         let updated_installer_info = InstallerInfo {
             url: Some("11".to_string()),
@@ -395,8 +1254,10 @@ mod tests {
             jdk_name: "77".to_string(),
             jdk_version: "88".to_string(),
             jdk_file_name: "99".to_string(),
+            app_file_sha256: None,
+            jdk_file_sha256: None,
         };
-        installer_config.update(6, updated_installer_info);
+        installer_config.update(6, updated_installer_info)?;
 
         // 判断文件中的内容
         let mut file = File::open(file_name)?;
@@ -410,12 +1271,18 @@ mod tests {
 
         assert!(!content.contains("url = \"1\""));
         assert!(!content.contains("installer_token = \"2\""));
-        assert!(!content.contains("app_name = \"3\""));
-        assert!(!content.contains("app_version = \"4\""));
-        assert!(!content.contains("app_file_name = \"5\""));
-        assert!(!content.contains("jdk_name = \"7\""));
-        assert!(!content.contains("jdk_version = \"8\""));
-        assert!(!content.contains("jdk_file_name = \"9\""));
+
+        // 之前的版本存到了 history 中
+        assert!(content.contains("[[installers.history]]"));
+        let installer = installer_config.get_by_port(6).unwrap();
+        assert_eq!(1, installer.history.len());
+        let previous = installer.previous_version().unwrap();
+        assert_eq!("3", previous.app_name);
+        assert_eq!("4", previous.app_version);
+        assert_eq!("5", previous.app_file_name);
+        assert_eq!("7", previous.jdk_name);
+        assert_eq!("8", previous.jdk_version);
+        assert_eq!("9", previous.jdk_file_name);
 
         assert!(content.contains("url = \"11\""));
         assert!(content.contains("installer_token = \"22\""));
@@ -432,10 +1299,85 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn update_and_rollback_success() -> Result<(), Box<dyn std::error::Error>> {
+        let file_name = "update_and_rollback_success.toml";
+        let mut installer_config = InstallerConfig::from(file_name)?;
+
+        installer_config.add(new_installer_info("0.1.0"))?;
+        // 版本没有变化时，不记录历史版本
+        installer_config.update(6, new_installer_info("0.1.0"))?;
+        assert!(installer_config.get_by_port(6).unwrap().history.is_empty());
+
+        installer_config.update(6, new_installer_info("0.2.0"))?;
+        installer_config.update(6, new_installer_info("0.3.0"))?;
+
+        // 从配置文件中读取
+        let mut installer_config = InstallerConfig::from(file_name)?;
+        let installer = installer_config.get_by_port(6).unwrap();
+        assert_eq!("0.3.0", installer.app_version);
+        assert_eq!(2, installer.history.len());
+
+        let previous = installer_config.rollback(6)?.unwrap();
+        assert_eq!("0.2.0", previous.app_version);
+        let installer = installer_config.get_by_port(6).unwrap();
+        assert_eq!("0.2.0", installer.app_version);
+        assert_eq!("app-0.2.0.jar", installer.app_file_name);
+        assert_eq!(1, installer.history.len());
+
+        installer_config.rollback(6)?.unwrap();
+        assert_eq!("0.1.0", installer_config.get_by_port(6).unwrap().app_version);
+
+        // 没有可回滚的版本
+        assert_eq!(None, installer_config.rollback(6)?);
+        assert_eq!(None, installer_config.rollback(8080)?);
+
+        // 删除 installer_config.toml 文件
+        fs::remove_file(file_name)?;
+        Ok(())
+    }
+
+    #[test]
+    fn update_keep_max_history_len() -> Result<(), Box<dyn std::error::Error>> {
+        let file_name = "update_keep_max_history_len.toml";
+        let mut installer_config = InstallerConfig::from(file_name)?;
+
+        installer_config.add(new_installer_info("0.1.0"))?;
+        for minor in 2..10 {
+            installer_config.update(6, new_installer_info(&format!("0.{}.0", minor)))?;
+        }
+
+        let installer = installer_config.get_by_port(6).unwrap();
+        assert_eq!(MAX_HISTORY_LEN, installer.history.len());
+        // 删除最早的版本
+        assert_eq!("0.4.0", installer.history[0].app_version);
+        assert_eq!("0.8.0", installer.previous_version().unwrap().app_version);
+
+        // 删除 installer_config.toml 文件
+        fs::remove_file(file_name)?;
+        Ok(())
+    }
+
+    fn new_installer_info(app_version: &str) -> InstallerInfo {
+        InstallerInfo {
+            url: Some("1".to_string()),
+            installer_token: "2".to_string(),
+            app_name: "app".to_string(),
+            app_version: app_version.to_string(),
+            app_file_name: format!("app-{}.jar", app_version),
+            app_run_port: 6_u32,
+            jdk_name: "jdk".to_string(),
+            jdk_version: "11.0.1".to_string(),
+            jdk_file_name: "jdk-11.0.1.zip".to_string(),
+            app_file_sha256: None,
+            jdk_file_sha256: None,
+        }
+    }
+
     #[test]
     fn get_by_port_not_exist() -> Result<(), Box<dyn std::error::Error>> {
         let file_name = "get_by_port_not_exist.toml";
-        let installer_config = InstallerConfig::from(file_name);
+        let installer_config = InstallerConfig::from(file_name)?;
 
         assert_eq!(None, installer_config.get_by_port(8080));
 
@@ -448,7 +1390,7 @@ mod tests {
     #[test]
     fn get_by_port_one_installer_success() -> Result<(), Box<dyn std::error::Error>> {
         let file_name = "get_by_port_one_installer_success.toml";
-        let mut installer_config = InstallerConfig::from(file_name);
+        let mut installer_config = InstallerConfig::from(file_name)?;
 
         let installer_info = InstallerInfo {
             url: Some("1".to_string()),
@@ -460,8 +1402,10 @@ mod tests {
             jdk_name: "7".to_string(),
             jdk_version: "8".to_string(),
             jdk_file_name: "9".to_string(),
+            app_file_sha256: None,
+            jdk_file_sha256: None,
         };
-        installer_config.add(installer_info);
+        installer_config.add(installer_info)?;
 
         assert_eq!("1", installer_config.get_by_port(6).unwrap().url);
 
@@ -474,7 +1418,7 @@ mod tests {
     #[test]
     fn get_by_port_two_installer_success() -> Result<(), Box<dyn std::error::Error>> {
         let file_name = "get_by_port_two_installer_success.toml";
-        let mut installer_config = InstallerConfig::from(file_name);
+        let mut installer_config = InstallerConfig::from(file_name)?;
 
         let installer_info = InstallerInfo {
             url: Some("1".to_string()),
@@ -486,8 +1430,10 @@ mod tests {
             jdk_name: "7".to_string(),
             jdk_version: "8".to_string(),
             jdk_file_name: "9".to_string(),
+            app_file_sha256: None,
+            jdk_file_sha256: None,
         };
-        installer_config.add(installer_info);
+        installer_config.add(installer_info)?;
 
         let installer_info = InstallerInfo {
             url: Some("11".to_string()),
@@ -499,8 +1445,10 @@ mod tests {
             jdk_name: "77".to_string(),
             jdk_version: "88".to_string(),
             jdk_file_name: "99".to_string(),
+            app_file_sha256: None,
+            jdk_file_sha256: None,
         };
-        installer_config.add(installer_info);
+        installer_config.add(installer_info)?;
 
         assert_eq!("11", installer_config.get_by_port(66).unwrap().url);
 
@@ -510,13 +1458,51 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn increase_restart_count_success() -> Result<(), Box<dyn std::error::Error>> {
+        let file_name = "increase_restart_count_success.toml";
+        let mut installer_config = InstallerConfig::from(file_name)?;
+
+        let installer_info = InstallerInfo {
+            url: Some("1".to_string()),
+            installer_token: "2".to_string(),
+            app_name: "3".to_string(),
+            app_version: "4".to_string(),
+            app_file_name: "5".to_string(),
+            app_run_port: 6_u32,
+            jdk_name: "7".to_string(),
+            jdk_version: "8".to_string(),
+            jdk_file_name: "9".to_string(),
+            app_file_sha256: None,
+            jdk_file_sha256: None,
+        };
+        installer_config.add(installer_info)?;
+
+        assert_eq!(0, installer_config.get_by_port(6).unwrap().restart_count);
+        assert_eq!(1, installer_config.increase_restart_count(6)?);
+        assert_eq!(2, installer_config.increase_restart_count(6)?);
+        // 端口上未注册 installer
+        assert_eq!(0, installer_config.increase_restart_count(66)?);
+
+        // 判断文件中的内容
+        let mut file = File::open(file_name)?;
+        let mut content = String::new();
+        file.read_to_string(&mut content)?;
+        assert!(content.contains("restart_count = 2"));
+
+        // 删除 installer_config.toml 文件
+        fs::remove_file(file_name)?;
+
+        Ok(())
+    }
+
     #[test]
     fn remove_by_installer_token_not_exist() -> Result<(), Box<dyn std::error::Error>> {
         let file_name = "remove_by_installer_token_not_exist.toml";
-        let mut installer_config = InstallerConfig::from(file_name);
+        let mut installer_config = InstallerConfig::from(file_name)?;
 
         assert_eq!(0, installer_config.get_data().installers.len());
-        installer_config.remove_by_installer_token("not-exist-installer-token");
+        installer_config.remove_by_installer_token("not-exist-installer-token")?;
         assert_eq!(0, installer_config.get_data().installers.len());
         // 删除 installer_config.toml 文件
         fs::remove_file(file_name)?;
@@ -527,7 +1513,7 @@ mod tests {
     #[test]
     fn remove_all_success() -> Result<(), Box<dyn std::error::Error>> {
         let file_name = "remove_all_success.toml";
-        let mut installer_config = InstallerConfig::from(file_name);
+        let mut installer_config = InstallerConfig::from(file_name)?;
 
         let installer_info = InstallerInfo {
             url: Some("1".to_string()),
@@ -539,8 +1525,10 @@ mod tests {
             jdk_name: "7".to_string(),
             jdk_version: "8".to_string(),
             jdk_file_name: "9".to_string(),
+            app_file_sha256: None,
+            jdk_file_sha256: None,
         };
-        installer_config.add(installer_info);
+        installer_config.add(installer_info)?;
 
         let installer_info = InstallerInfo {
             url: Some("11".to_string()),
@@ -552,14 +1540,16 @@ mod tests {
             jdk_name: "77".to_string(),
             jdk_version: "88".to_string(),
             jdk_file_name: "99".to_string(),
+            app_file_sha256: None,
+            jdk_file_sha256: None,
         };
-        installer_config.add(installer_info);
+        installer_config.add(installer_info)?;
 
         assert_eq!(2, installer_config.get_data().installers.len());
 
         installer_config.remove_all(|_| {
             true
-        });
+        })?;
 
         assert_eq!(0, installer_config.get_data().installers.len());
 
@@ -1,26 +1,46 @@
+use std::collections::BTreeMap;
 use std::process::{Command, Child};
 use std::path::Path;
 use std::fs;
+use std::io::{self, PipeWriter};
 
-/// 在后台运行 Spring boot jar 文件，并返回进程
+/// 运行 Spring boot jar 时使用的 JVM 参数、程序参数和环境变量
+#[derive(Debug, Default)]
+pub struct LaunchOptions<'a> {
+    /// JVM 参数，放在 `-jar` 之前
+    pub jvm_args: &'a [String],
+    /// Spring Boot 程序参数，放在 `--server.port` 之后
+    pub app_args: &'a [String],
+    /// 环境变量，注意 `PATH` 始终指向 JDK 的 bin 目录
+    pub env: Option<&'a BTreeMap<String, String>>,
+}
+
+/// 在后台运行 Spring boot jar 文件，并返回进程，启动失败时返回错误
 /// 
 /// 默认将 JDK 与要运行的 spring boot jar 放在同一个文件夹中,
 /// 其中 `jar_file_path` 指 spring boot jar 的存放路径，
-/// `jdk_path` 指 jdk 的存放路径。
+/// `jdk_path` 指 jdk 的存放路径，`options` 中是额外的 JVM 参数、程序参数和环境变量，
+/// spring boot jar 的标准输出和标准错误都会写入 `log` 中，通常是 `app_log::open` 返回的日志。
 /// 
 /// Examples
 /// 
 /// ```no_run
-/// use installer::jar::run_spring_boot;
+/// use installer::jar::{run_spring_boot, LaunchOptions};
 /// 
-/// fn main() {
-///     run_spring_boot("prod/app1/demo-0.0.1-SNAPSHOT.jar", "prod/app1/temp/jdk-11.0.1", 80);
+/// fn main() -> std::io::Result<()> {
+///     let log = installer::app_log::open("demo", 80)?;
+///     let jvm_args = vec!["-Xmx512m".to_string()];
+///     let options = LaunchOptions { jvm_args: &jvm_args, ..Default::default() };
+///     run_spring_boot("prod/app1/demo-0.0.1-SNAPSHOT.jar", "prod/app1/temp/jdk-11.0.1", 80, &options, log)?;
+///     Ok(())
 /// }
 /// ```
 pub fn run_spring_boot(
     jar_file_path: &str, 
     jdk_path: &str,
-    port: u32) -> Child {
+    port: u32,
+    options: &LaunchOptions,
+    log: PipeWriter) -> io::Result<Child> {
 
     if cfg!(target_os = "windows") {
         // 注意，在 windows 操作系统中，使用 `javaw`，不使用 `java`
@@ -30,40 +50,67 @@ pub fn run_spring_boot(
         // 所以在 linux 环境下，并不存在 `javaw` 命令。
         // 详见 https://stackoverflow.com/questions/14331406/why-javaw-is-not-found-on-my-java-installation-on-ubuntu
         Command::new("javaw")
+            .envs(options.env.into_iter().flatten())
             .env("PATH", Path::new(jdk_path).join("bin"))
+            .args(options.jvm_args)
             .arg("-jar")
             .arg(jar_file_path)
             .arg(format!("--server.port={}", port))
+            .args(options.app_args)
+            .stdout(log.try_clone()?)
+            .stderr(log)
             .spawn()
-            .expect("failed to run javaw -jar")
     } else {
         // 设置权限，初次运行时会提示没有权限
         let java_executable_path = Path::new(jdk_path).join("bin").join("java");
-        let java_executable_path = java_executable_path
-            .to_str()
-            .expect("parse jdk/bin/java path error");
 
 	    // 为 jdk/bin/java 设置可执行权限
-        set_executable_permission(java_executable_path)
-            .expect("failed to set jdk/bin/java permission");
+        set_executable_permission(&java_executable_path)?;
         // 为 spring boot jar 设置可执行权限
-        set_executable_permission(jar_file_path)
-            .expect("failed to set sping boot jar permission");
+        set_executable_permission(Path::new(jar_file_path))?;
 	
         // linux 下直接运行 java -jar 就是生成一个新的后台进程
         // 当关闭 installer 进程后，运行 java -jar 的进程依然存在
         Command::new("java")
+            .envs(options.env.into_iter().flatten())
             .env("PATH", Path::new(jdk_path).join("bin"))
+            .args(options.jvm_args)
             .arg("-jar")
             .arg(jar_file_path)
             .arg(format!("--server.port={}", port))
+            .args(options.app_args)
+            .stdout(log.try_clone()?)
+            .stderr(log)
             .spawn()
-            .expect("failed to run java -jar")
     }
 }
 
+/// 判断启动进程的完整命令 `command_line` 运行的是否是 `jar_file_path`。
+/// 
+/// 比较 `-jar` 之后的完整路径，而不是只判断命令中是否包含此路径，
+/// 防止将 `prod/app1/demo-0.0.1-SNAPSHOT.jar.bak` 等其他 jar 误认为是同一个 jar。
+/// windows 下命令中的路径可能用引号括起来。
+/// 
+/// Examples
+/// 
+/// ```
+/// use installer::jar::is_running_jar;
+/// 
+/// let jar_file_path = "prod/app1/demo-0.0.1-SNAPSHOT.jar";
+/// assert!(is_running_jar("java -Xmx512m -jar prod/app1/demo-0.0.1-SNAPSHOT.jar --server.port=80", jar_file_path));
+/// assert!(is_running_jar("javaw -jar \"prod/app1/demo-0.0.1-SNAPSHOT.jar\" --server.port=80", jar_file_path));
+/// assert!(!is_running_jar("java -jar prod/app1/demo-0.0.1-SNAPSHOT.jar.bak --server.port=80", jar_file_path));
+/// assert!(!is_running_jar("java -jar /opt/prod/app1/demo-0.0.1-SNAPSHOT.jar --server.port=80", jar_file_path));
+/// ```
+pub fn is_running_jar(command_line: &str, jar_file_path: &str) -> bool {
+    ["", "\""].iter().any(|quote| {
+        let jar_arg = format!(" -jar {0}{1}{0}", quote, jar_file_path);
+        command_line.ends_with(&jar_arg) || command_line.contains(&format!("{} ", jar_arg))
+    })
+}
+
 // 在 linux 等操作系统下，让文件具有可执行权限。
-fn set_executable_permission(path: &str) -> std::io::Result<()> {
+fn set_executable_permission(path: &Path) -> io::Result<()> {
     let mut perms = fs::metadata(path)?.permissions();
     perms.set_readonly(false);
     fs::set_permissions(path, perms)?;
@@ -78,14 +125,15 @@ fn set_executable_permission(path: &str) -> std::io::Result<()> {
 /// Examples
 /// 
 /// ```no_run
-/// use installer::jar::run_spring_boot;
-/// use installer::jar::stop_spring_boot;
+/// use installer::jar::{run_spring_boot, stop_spring_boot, LaunchOptions};
 /// 
-/// fn main() {
-///     let mut process = run_spring_boot("prod/app1/demo-0.0.1-SNAPSHOT.jar", "prod/app1/temp/jdk-11.0.1", 80);
-///     stop_spring_boot(&mut process);
+/// fn main() -> std::io::Result<()> {
+///     let log = installer::app_log::open("demo", 80)?;
+///     let options = LaunchOptions::default();
+///     let mut process = run_spring_boot("prod/app1/demo-0.0.1-SNAPSHOT.jar", "prod/app1/temp/jdk-11.0.1", 80, &options, log)?;
+///     stop_spring_boot(&mut process)
 /// }
 /// ```
-pub fn stop_spring_boot(process: &mut Child) -> std::io::Result<()> {
+pub fn stop_spring_boot(process: &mut Child) -> io::Result<()> {
     process.kill()
 }
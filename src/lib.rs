@@ -1,8 +1,17 @@
 pub mod command;
 
 pub mod config;
+pub mod error;
 pub mod installer_config;
 pub mod download_config;
 pub mod http;
 pub mod util;
-pub mod jar;
\ No newline at end of file
+pub mod jar;
+pub mod daemon;
+pub mod run_record;
+pub mod health_check;
+pub mod app_log;
+pub mod app_conf;
+pub mod proxy;
+pub mod watch;
+pub mod output;
\ No newline at end of file
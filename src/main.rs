@@ -1,34 +1,69 @@
 use std::io;
+use std::path::Path;
+use std::process::exit;
 use structopt::StructOpt;
+use structopt::clap::AppSettings;
 use url::Url;
 use url::ParseError::{EmptyHost};
 use installer::command::{
         // installer 相关命令
         register_installer, 
         list_installers,
+        status_single_app,
+        status_apps,
         unregister_single_installer,
         unregister_all_installers,
         // app 相关命令
         run_single_app, 
-        run_all_apps,
+        run_apps,
         update_single_app,
-        update_all_apps,
+        update_apps,
+        dry_run_update_single_app,
+        dry_run_update_apps,
         stop_single_app,
-        stop_all_apps};
-use installer::installer_config::InstallerConfig;
+        stop_apps,
+        restart_single_app,
+        restart_apps,
+        rollback_app,
+        print_logs,
+        set_config,
+        set_global_config,
+        clean_up_stale_temp_dirs};
+use installer::config::{DEFAULT_SERVER_HOST, DEFAULT_SERVER_PORT};
+use installer::http::server;
+use installer::daemon;
+use installer::proxy;
+use installer::app_log;
+use installer::watch;
+use installer::output::{self, OutputFormat};
+use installer::error::InstallerError;
+use installer::installer_config::{InstallerConfig, Selector};
 use installer::util::process;
 
 fn main() {
-    let args = Cli::from_args();
+    let opt = Opt::from_args();
+    output::set_format(opt.output);
 
-    match args {
+    // 启动时清理之前异常退出留下的临时文件夹，--dry-run 不修改任何文件，所以不清理
+    if !matches!(opt.command, Cli::Update { dry_run: true, .. }) {
+        clean_up_stale_temp_dirs();
+    }
+
+    match opt.command {
         // 支持多次调用 register 命令，最后的设置会覆盖之前的设置。
-        Cli::Register => {
-            ask_register_installer();
+        Cli::Register { non_interactive, url, token, port } => {
+            if non_interactive {
+                register_installer_non_interactive(url, token, port);
+            } else {
+                ask_register_installer();
+            }
         },
         Cli::List => {
             ask_list_installers();
         },
+        Cli::Status { port, app, tag } => {
+            ask_print_status(port, get_selector(true, app, tag));
+        },
         Cli::Unregister { port, all } => {
             if let Some(v) = port {
                 ask_unregister_single_installer(v);
@@ -38,47 +73,138 @@ fn main() {
                 println!("提示：请输入 --port <port> 选项注销单个 installer，或输入 --all 注销所有 installer。");
             }
         },
-        Cli::Run { port, all } => {
+        Cli::Run { port, all, app, tag, jobs } => {
             if let Some(v) = port {
                 ask_run_single_app(v);
-            } else if all {
-                ask_run_all_apps();
+            } else if let Some(selector) = get_selector(all, app, tag) {
+                ask_run_apps(&selector, jobs);
             } else {
-                println!("提示：请输入 --port <port> 选项运行单个 APP，或输入 --all 运行所有 APP。");
+                println!("提示：请输入 --port <port> 选项运行单个 APP，输入 --app 或 --tag 选项运行多个 APP，或输入 --all 运行所有 APP。");
             }
         },
-        Cli::Update { port, all } => {
+        Cli::Update { port, all, app, tag, jobs, force, dry_run } => {
             if let Some(v) = port {
-                ask_update_single_app(v);
-            } else if all {
-                ask_update_all_apps();
+                if dry_run {
+                    ask_dry_run_update_single_app(v);
+                } else {
+                    ask_update_single_app(v, force);
+                }
+            } else if let Some(selector) = get_selector(all, app, tag) {
+                if dry_run {
+                    ask_dry_run_update_apps(&selector, jobs);
+                } else {
+                    ask_update_apps(&selector, force, jobs);
+                }
             } else {
-                println!("提示：请输入 --port <port> 选项升级单个 APP，或输入 --all 升级所有 APP。");
+                println!("提示：请输入 --port <port> 选项升级单个 APP，输入 --app 或 --tag 选项升级多个 APP，或输入 --all 升级所有 APP。");
             }
         },
-        Cli::Stop { port, all } => {
+        Cli::Stop { port, all, app, tag, jobs, force } => {
             if let Some(v) = port {
-                ask_stop_single_app(v);
-            } else if all {
-                ask_stop_all_apps();
+                ask_stop_single_app(v, force);
+            } else if let Some(selector) = get_selector(all, app, tag) {
+                ask_stop_apps(&selector, force, jobs);
             } else {
-                println!("提示：请输入 --port <port> 选项停止单个 APP，或输入 --all 停止所有 APP。");
+                println!("提示：请输入 --port <port> 选项停止单个 APP，输入 --app 或 --tag 选项停止多个 APP，或输入 --all 停止所有 APP。");
             }
+        },
+        Cli::Restart { port, all, app, tag, jobs, force } => {
+            if let Some(v) = port {
+                ask_restart_single_app(v, force);
+            } else if let Some(selector) = get_selector(all, app, tag) {
+                ask_restart_apps(&selector, force, jobs);
+            } else {
+                println!("提示：请输入 --port <port> 选项重启单个 APP，输入 --app 或 --tag 选项重启多个 APP，或输入 --all 重启所有 APP。");
+            }
+        },
+        Cli::Rollback { port, force } => {
+            ask_rollback_app(port, force);
+        },
+        Cli::Logs { port, follow, lines } => {
+            ask_print_logs(port, follow, lines);
+        },
+        Cli::Config(ConfigCommand::Set { port, global, key, value }) => {
+            match port {
+                Some(port) if !global => ask_set_config(port, &key, &value),
+                _ => ask_set_global_config(&key, &value),
+            }
+        },
+        Cli::Server { host, port } => {
+            ask_start_server(&host, port);
+        },
+        Cli::Daemon => {
+            ask_start_daemon();
+        },
+        Cli::Watch => {
+            ask_start_watch();
+        },
+        Cli::Proxy { port, hold_timeout } => {
+            ask_start_proxy(port, hold_timeout);
+        },
+        Cli::LogWriter { path } => {
+            ask_write_log(&path);
         }
     }
 }
 
 #[derive(Debug, StructOpt)]
 #[structopt(name = "blocklang-installer", about = "Block Lang 安装程序")]
+struct Opt {
+    /// 输出格式，支持 table、json 和 yaml。
+    /// 
+    /// 为 json 或 yaml 时，list、status、run、stop、restart 和 update 命令在标准输出中打印 APP 的记录，执行过程打印到标准错误中。
+    #[structopt(long = "output", short = "o", global = true, default_value = "table")]
+    output: OutputFormat,
+
+    #[structopt(subcommand)]
+    command: Cli,
+}
+
+#[derive(Debug, StructOpt)]
 enum Cli {
     /// 将 installer 注册到 Block Lang 平台。
+    /// 
+    /// 默认在命令行中逐项输入注册信息；
+    /// 指定 `--non-interactive` 选项时，使用选项或环境变量中的注册信息，出错时不再提示重新输入，而是直接退出。
     #[structopt(name = "register")]
-    Register,
+    Register {
+        /// 不在命令行中输入注册信息，只使用选项或环境变量中的注册信息
+        #[structopt(long = "non-interactive")]
+        non_interactive: bool,
+
+        /// Block Lang 平台 URL，默认值为 https://blocklang.com
+        #[structopt(long = "url", env = "BLOCKLANG_URL")]
+        url: Option<String>,
+
+        /// 部署项目的注册 token，指定 `--non-interactive` 选项时必填
+        #[structopt(long = "token", env = "BLOCKLANG_TOKEN", hide_env_values = true)]
+        token: Option<String>,
+
+        /// 运行项目的端口号，默认为 80
+        #[structopt(long = "port", short = "p")]
+        port: Option<u32>,
+    },
 
     /// 显示所有在此服务器上注册的 installer。
     #[structopt(name = "list")]
     List,
 
+    /// 显示 installer 管理的 APP 的运行状态，包括进程、内存、CPU 和 JDK 的解压状态。
+    #[structopt(name = "status")]
+    Status {
+        /// 根据指定的端口号定位到 installer，只显示此 installer 管理的 APP，默认显示所有 APP
+        #[structopt(long = "port", short = "p")]
+        port: Option<u32>,
+
+        /// 显示部署此 APP 的所有 installer，如 @user/project
+        #[structopt(long = "app", conflicts_with = "port")]
+        app: Option<String>,
+
+        /// 显示有此标签的所有 installer，如 env=staging
+        #[structopt(long = "tag", conflicts_with_all = &["port", "app"])]
+        tag: Option<String>,
+    },
+
     /// 从 Block Lang 平台注销 installer。
     #[structopt(name = "unregister")]
     Unregister {
@@ -87,11 +213,11 @@ enum Cli {
         port: Option<u32>,
 
         /// 注销配置文件中的所有 installer
-        #[structopt(long = "all", short = "a")]
+        #[structopt(long = "all", short = "a", conflicts_with = "port")]
         all: bool,
     },
 
-    /// 启动 Installer REST 服务(未实现)，并运行绑定的 Spring Boot jar。
+    /// 运行绑定的 Spring Boot jar。
     #[structopt(name = "run")]
     Run {
         /// 根据指定的端口号定位到 installer，然后运行此 installer
@@ -99,8 +225,20 @@ enum Cli {
         port: Option<u32>,
 
         /// 运行配置文件中的所有 installer
-        #[structopt(long = "all", short = "a")]
+        #[structopt(long = "all", short = "a", conflicts_with = "port")]
         all: bool,        
+
+        /// 运行部署此 APP 的所有 installer，如 @user/project
+        #[structopt(long = "app", conflicts_with_all = &["port", "all"])]
+        app: Option<String>,
+
+        /// 运行有此标签的所有 installer，如 env=staging
+        #[structopt(long = "tag", conflicts_with_all = &["port", "all", "app"])]
+        tag: Option<String>,
+
+        /// 选择多个 APP 时，同时处理的 APP 数量，默认依次处理
+        #[structopt(long = "jobs", short = "j", default_value = "1", parse(try_from_str = parse_jobs))]
+        jobs: usize,
     },
 
     /// 升级并运行最新版的 Spring Boot jar。
@@ -111,11 +249,31 @@ enum Cli {
         port: Option<u32>,
 
         /// 升级配置文件中的所有 installer 管理的所有 APP
-        #[structopt(long = "all", short = "a")]
+        #[structopt(long = "all", short = "a", conflicts_with = "port")]
         all: bool,        
+
+        /// 升级部署此 APP 的所有 installer，如 @user/project
+        #[structopt(long = "app", conflicts_with_all = &["port", "all"])]
+        app: Option<String>,
+
+        /// 升级有此标签的所有 installer，如 env=staging
+        #[structopt(long = "tag", conflicts_with_all = &["port", "all", "app"])]
+        tag: Option<String>,
+
+        /// 选择多个 APP 时，同时处理的 APP 数量，默认依次处理
+        #[structopt(long = "jobs", short = "j", default_value = "1", parse(try_from_str = parse_jobs))]
+        jobs: usize,
+
+        /// 即使端口上运行的进程不是 installer 启动的，也关闭此进程并重启 APP
+        #[structopt(long = "force", short = "f")]
+        force: bool,
+
+        /// 只打印当前版本和最新版本，不下载文件、不重启 APP，也不修改配置文件
+        #[structopt(long = "dry-run")]
+        dry_run: bool,
     },
 
-    /// 停止 Installer Rest 服务，并停止运行 Spring Boot jar。
+    /// 停止运行 Spring Boot jar。
     #[structopt(name = "stop")]
     Stop {
         /// 根据指定的端口号定位到 installer，然后停止此 installer 管理的 APP
@@ -123,8 +281,124 @@ enum Cli {
         port: Option<u32>,
 
         /// 停止配置文件中的所有 installer 管理的所有 APP
-        #[structopt(long = "all", short = "a")]
+        #[structopt(long = "all", short = "a", conflicts_with = "port")]
         all: bool,        
+
+        /// 停止部署此 APP 的所有 installer，如 @user/project
+        #[structopt(long = "app", conflicts_with_all = &["port", "all"])]
+        app: Option<String>,
+
+        /// 停止有此标签的所有 installer，如 env=staging
+        #[structopt(long = "tag", conflicts_with_all = &["port", "all", "app"])]
+        tag: Option<String>,
+
+        /// 选择多个 APP 时，同时处理的 APP 数量，默认依次处理
+        #[structopt(long = "jobs", short = "j", default_value = "1", parse(try_from_str = parse_jobs))]
+        jobs: usize,
+
+        /// 即使端口上运行的进程不是 installer 启动的，也关闭此进程
+        #[structopt(long = "force", short = "f")]
+        force: bool,
+    },
+
+    /// 使用配置的 Spring Boot jar 和 JDK 重启 APP，不访问 Block Lang 平台。
+    #[structopt(name = "restart")]
+    Restart {
+        /// 根据指定的端口号定位到 installer，然后重启此 installer 管理的 APP
+        #[structopt(long = "port", short = "p")]
+        port: Option<u32>,
+
+        /// 重启配置文件中的所有 installer 管理的所有 APP
+        #[structopt(long = "all", short = "a", conflicts_with = "port")]
+        all: bool,
+
+        /// 重启部署此 APP 的所有 installer，如 @user/project
+        #[structopt(long = "app", conflicts_with_all = &["port", "all"])]
+        app: Option<String>,
+
+        /// 重启有此标签的所有 installer，如 env=staging
+        #[structopt(long = "tag", conflicts_with_all = &["port", "all", "app"])]
+        tag: Option<String>,
+
+        /// 选择多个 APP 时，同时处理的 APP 数量，默认依次处理
+        #[structopt(long = "jobs", short = "j", default_value = "1", parse(try_from_str = parse_jobs))]
+        jobs: usize,
+
+        /// 即使端口上运行的进程不是 installer 启动的，也关闭此进程并重启 APP
+        #[structopt(long = "force", short = "f")]
+        force: bool,
+    },
+
+    /// 回滚到上一个部署的 Spring Boot jar 和 JDK 版本。
+    #[structopt(name = "rollback")]
+    Rollback {
+        /// 根据指定的端口号定位到 installer，然后回滚此 installer 管理的 APP
+        #[structopt(long = "port", short = "p")]
+        port: u32,
+
+        /// 即使端口上运行的进程不是 installer 启动的，也关闭此进程并重启 APP
+        #[structopt(long = "force", short = "f")]
+        force: bool,
+    },
+
+    /// 查看 Spring Boot jar 的标准输出和标准错误。
+    #[structopt(name = "logs")]
+    Logs {
+        /// 根据指定的端口号定位到 installer，然后查看此 installer 管理的 APP 的日志
+        #[structopt(long = "port", short = "p")]
+        port: u32,
+
+        /// 持续输出新增的日志，按 CTRL + C 退出
+        #[structopt(long = "follow", short = "f")]
+        follow: bool,
+
+        /// 输出最后几行日志
+        #[structopt(long = "lines", short = "n", default_value = "100")]
+        lines: usize,
+    },
+
+    /// 修改 installer 的配置信息。
+    #[structopt(name = "config")]
+    Config(ConfigCommand),
+
+    /// 启动 Installer REST 服务，供 Block Lang 平台运行、停止或升级 APP。
+    #[structopt(name = "server")]
+    Server {
+        /// Installer REST 服务监听的地址，默认只允许本机访问，需要从其他主机访问时可指定为 0.0.0.0
+        #[structopt(long = "host", default_value = DEFAULT_SERVER_HOST)]
+        host: String,
+
+        /// Installer REST 服务的运行端口，默认为 9090
+        #[structopt(long = "port", short = "p")]
+        port: Option<u32>,
+    },
+
+    /// 以守护进程的方式运行所有 APP，APP 意外退出后会自动重启。
+    #[structopt(name = "daemon")]
+    Daemon,
+
+    /// 按 installer 中配置的 auto_update 定时检查并升级 APP。
+    #[structopt(name = "watch")]
+    Watch,
+
+    /// 在 APP 的端口上运行 TCP 代理，由 installer 在使用代理时自动启动，不需要手动执行。
+    #[structopt(name = "proxy", setting = AppSettings::Hidden)]
+    Proxy {
+        /// APP 的端口，即 installer 注册时的端口
+        #[structopt(long = "port", short = "p")]
+        port: u32,
+
+        /// APP 没有运行时，保持连接并等待 APP 启动的秒数，超时后拒绝连接，仅在无法读取 installer 的配置时使用
+        #[structopt(long = "hold-timeout", default_value = "30")]
+        hold_timeout: u64,
+    },
+
+    /// 将标准输入中的内容写入日志文件，并在日志文件过大时轮转，由 installer 在启动 APP 时自动启动，不需要手动执行。
+    #[structopt(name = "log-writer", setting = AppSettings::Hidden)]
+    LogWriter {
+        /// 日志文件的路径
+        #[structopt(long = "path")]
+        path: String,
     },
 }
 
@@ -141,25 +415,13 @@ fn ask_register_installer() {
             io_url.push_str("https://blocklang.com");
         }
 
-        match Url::parse(&io_url) {
-            Ok(value) => {
-                if value.scheme() != "http" && value.scheme() != "https" {
-                    println!("> [ERROR]: URL 必须使用 http 或 https 协议，请重新输入 URL(默认值为 https://blocklang.com)：");  
-                    continue;
-                }
-                
+        match validate_url(&io_url) {
+            Ok(_) => {
                 url = &io_url;
                 break;
             }
             Err(e) => {
-                match e {
-                    EmptyHost => {
-                        println!("> [ERROR]: URL 不能为空，请重新输入 URL(默认值为 https://blocklang.com)：");
-                    },
-                    _ => {
-                        println!("> [ERROR]: 无效的 URL，请重新输入 URL(默认值为 https://blocklang.com)：");
-                    }
-                }
+                println!("> [ERROR]: {}，请重新输入 URL(默认值为 https://blocklang.com)：", e);
                 continue;
             }
         }
@@ -170,7 +432,7 @@ fn ask_register_installer() {
     io::stdin().read_line(&mut token).unwrap();
     token = token.trim().to_string();
 
-    let installer_config = InstallerConfig::new();
+    let installer_config = InstallerConfig::new().unwrap_or_else(|e| exit_with_error("", e));
 
     // 运行端口应该在部署时来定，跟发布无关，而是跟部署环境有关
     println!("[3/3] 请输入运行项目的端口号(默认为80)");
@@ -194,45 +456,164 @@ fn ask_register_installer() {
             }
         };
 
-        // 校验端口是否已被注册
-        if let Some(installer) = installer_config.get_by_port(app_run_port) {
-            println!("> [WARN]: {} 端口下已注册 {} 项目", installer.app_run_port, installer.app_name);
-            println!("> [INFO]: 确定要在 {} 端口下重新注册项目，请：", installer.app_run_port);
-            println!("> [INFO]: 1. 先执行 `blocklang-installer unregister --port {}` 命令注销", installer.app_run_port);
-            println!("> [INFO]: 2. 再执行 `blocklang-installer register --port {}` 命令重新注册", installer.app_run_port);
+        if !validate_app_run_port(&installer_config, app_run_port) {
             println!("> [INFO]: 按 CTRL + C 退出，或重新输入端口号(默认为80)：");
             continue;
         }
 
-        // 如果端口未被 installer 注册，则再校验端口号是否被主机上其他应用占用
-        if process::get_id(app_run_port).is_some() {
-            // 端口被占用，则提醒用户
-            println!("> [INFO]: 端口 {} 已被占用，请重新输入(默认为80)：", app_run_port);
-            continue;
-        }
-
         // 前面的校验都通过了，则跳出循环
         break;
     }
     
     // 输入完成后，开始注册
-    if register_installer(&url, &token, app_run_port).is_ok() {
-        if cfg!(target_os = "windows"){
-            println!("注册成功，请执行 `blocklang-installer.exe run --port <port>` 命令运行项目。");
-        } else if cfg!(target_os = "linux") {
-            println!("注册成功，请执行 `./blocklang-installer run --port <port>` 命令运行项目。");
+    // 出错后, 不打印任何内容，只设置退出码
+    match register_installer(&url, &token, app_run_port) {
+        Ok(_) => print_register_success(),
+        Err(e) => exit(e.exit_code()),
+    }
+}
+
+#[derive(Debug, StructOpt)]
+enum ConfigCommand {
+    /// 设置运行 Spring Boot jar 时使用的 JVM 参数、程序参数或环境变量，重启 APP 后生效。
+    /// 
+    /// 支持的配置项有 jvm_args、app_args、tags 和 env.<NAME> 等，值为空时清空参数、标签或删除环境变量。
+    /// 使用 --global 选项时，设置所有 installer 默认使用的 auto_update.interval 或 auto_update.window。
+    #[structopt(name = "set", setting = AppSettings::AllowLeadingHyphen)]
+    Set {
+        /// 根据指定的端口号定位到 installer，然后修改此 installer 的配置信息
+        #[structopt(long = "port", short = "p", required_unless = "global")]
+        port: Option<u32>,
+
+        /// 修改所有 installer 默认使用的配置，installer 中的配置会覆盖此配置
+        #[structopt(long = "global", conflicts_with = "port")]
+        global: bool,
+
+        /// 配置项，如 jvm_args、app_args 或 env.JAVA_TOOL_OPTIONS
+        key: String,
+
+        /// 配置项的值，多个参数之间用空格分隔，如 "-Xmx512m -XX:+UseG1GC"
+        value: String,
+    },
+}
+
+/// 使用命令行选项或环境变量中的注册信息注册 installer，不需要用户输入，
+/// 适用于 Ansible、cloud-init 或 Dockerfile 等自动化部署场景。
+/// 
+/// 校验失败或注册失败时，不会提示用户重新输入，而是以非 0 的退出码退出程序。
+fn register_installer_non_interactive(url: Option<String>, token: Option<String>, port: Option<u32>) {
+    println!("开始往 Block Lang 平台注册主机：");
+
+    let url = url.unwrap_or_else(|| "https://blocklang.com".to_string());
+    let url = url.trim();
+    if let Err(e) = validate_url(url) {
+        exit_with_error("", InstallerError::Validation(format!("{}，请检查 --url 选项或 BLOCKLANG_URL 环境变量", e)));
+    }
+
+    let token = match token {
+        Some(ref value) if !value.trim().is_empty() => value.trim(),
+        _ => {
+            exit_with_error("", InstallerError::Validation(
+                "注册 token 不能为空，请使用 --token 选项或 BLOCKLANG_TOKEN 环境变量指定注册 token".to_string()));
         }
+    };
+
+    let app_run_port = port.unwrap_or(80);
+    let installer_config = InstallerConfig::new().unwrap_or_else(|e| exit_with_error("", e));
+    if !validate_app_run_port(&installer_config, app_run_port) {
+        exit_with_error("", InstallerError::Validation(format!("端口 {} 不可用", app_run_port)));
+    }
+
+    println!("> [INFO]: URL: {}，端口号: {}", url, app_run_port);
+    if let Err(e) = register_installer(url, token, app_run_port) {
+        exit(e.exit_code());
+    }
+
+    print_register_success();
+}
+
+/// 根据 `--app`、`--tag` 和 `--all` 选项选择多个 installer，都没有指定时返回 `None`。
+/// 
+/// 这些选项与 `--port` 互斥，由 structopt 校验，所以最多只有一个选项有值。
+fn get_selector(all: bool, app: Option<String>, tag: Option<String>) -> Option<Selector> {
+    match (app, tag) {
+        (Some(app), _) => Some(Selector::App(app)),
+        (None, Some(tag)) => Some(Selector::Tag(tag)),
+        (None, None) if all => Some(Selector::All),
+        (None, None) => None,
+    }
+}
+
+/// 解析 `--jobs` 选项，同时处理的 APP 数量必须大于 0
+fn parse_jobs(jobs: &str) -> Result<usize, String> {
+    match jobs.parse() {
+        Ok(0) => Err("--jobs 必须大于 0".to_string()),
+        Ok(jobs) => Ok(jobs),
+        Err(e) => Err(format!("--jobs 必须是正整数，{}", e)),
+    }
+}
+
+/// 校验 URL，只支持 http 和 https 协议
+fn validate_url(url: &str) -> Result<(), &'static str> {
+    match Url::parse(url) {
+        Ok(value) => {
+            if value.scheme() != "http" && value.scheme() != "https" {
+                return Err("URL 必须使用 http 或 https 协议");
+            }
+            Ok(())
+        }
+        Err(EmptyHost) => Err("URL 不能为空"),
+        Err(_) => Err("无效的 URL"),
+    }
+}
+
+/// 校验端口号是否可用，即端口没有被 installer 注册，也没有被主机上的其他应用占用。
+/// 
+/// 校验失败时会打印提示信息，并返回 `false`。
+fn validate_app_run_port(installer_config: &InstallerConfig, app_run_port: u32) -> bool {
+    // 校验端口是否已被注册
+    if let Some(installer) = installer_config.get_by_port(app_run_port) {
+        println!("> [WARN]: {} 端口下已注册 {} 项目", installer.app_run_port, installer.app_name);
+        println!("> [INFO]: 确定要在 {} 端口下重新注册项目，请：", installer.app_run_port);
+        println!("> [INFO]: 1. 先执行 `blocklang-installer unregister --port {}` 命令注销", installer.app_run_port);
+        println!("> [INFO]: 2. 再执行 `blocklang-installer register --port {}` 命令重新注册", installer.app_run_port);
+        return false;
+    }
+
+    // 如果端口未被 installer 注册，则再校验端口号是否被主机上其他应用占用
+    if !process::get_port_owner(app_run_port).is_free() {
+        // 端口被占用，则提醒用户
+        println!("> [INFO]: 端口 {} 已被占用", app_run_port);
+        return false;
     }
 
-    // 出错后, 不打印任何内容
+    true
+}
+
+fn print_register_success() {
+    if cfg!(target_os = "windows"){
+        println!("注册成功，请执行 `blocklang-installer.exe run --port <port>` 命令运行项目。");
+    } else if cfg!(target_os = "linux") {
+        println!("注册成功，请执行 `./blocklang-installer run --port <port>` 命令运行项目。");
+    }
 }
 
 fn ask_list_installers() {
     match list_installers() {
         Ok(_) => {},
         Err(e) => {
-            println!("查找 installer 清单时出错！{}", e);
-        }
+            exit_with_error("查找 installer 清单时出错！", e);
+        },
+    }
+}
+
+fn ask_print_status(port: Option<u32>, selector: Option<Selector>) {
+    let result = match (port, selector) {
+        (Some(v), _) => status_single_app(v),
+        (None, selector) => status_apps(&selector.unwrap_or(Selector::All)),
+    };
+    if let Err(e) = result {
+        exit_with_error("查看 APP 的运行状态时出错！", e);
     }
 }
 
@@ -242,7 +623,7 @@ fn ask_unregister_single_installer(app_run_port: u32) {
             // 不做任何处理
         },
         Err(e) => {
-            println!("注销单个 installer 失败！{}", e);
+            exit_with_error("注销单个 installer 失败！", e);
         },
     }
 }
@@ -253,7 +634,7 @@ fn ask_unregister_all_installers() {
             // 不做任何处理
         },
         Err(e) => {
-            println!("注销所有 installer 失败！{}", e);
+            exit_with_error("注销所有 installer 失败！", e);
         },
     }
 }
@@ -264,62 +645,176 @@ fn ask_run_single_app(app_run_port: u32) {
             // 不做任何处理
         },
         Err(e) => {
-            println!("启动单个 APP 失败！{}", e);
+            exit_with_error("启动单个 APP 失败！", e);
+        },
+    }
+}
+
+fn ask_run_apps(selector: &Selector, jobs: usize) {
+    match run_apps(selector, jobs) {
+        Ok(_) => {
+            // 不做任何处理
+        },
+        Err(e) => {
+            exit_with_error("启动 APP 失败！", e);
+        },
+    }
+}
+
+fn ask_update_single_app(app_run_port: u32, force: bool) {
+    match update_single_app(app_run_port, force) {
+        Ok(_) => {
+            // 不做任何处理
+        },
+        Err(e) => {
+            exit_with_error("升级项目失败！", e);
+        },
+    }
+}
+
+fn ask_update_apps(selector: &Selector, force: bool, jobs: usize) {
+    match update_apps(selector, force, jobs) {
+        Ok(_) => {
+            // 不做任何处理
+        },
+        Err(e) => {
+            exit_with_error("升级 APP 失败！", e);
         },
     }
 }
 
-fn ask_run_all_apps() {
-    match run_all_apps() {
+fn ask_dry_run_update_single_app(app_run_port: u32) {
+    match dry_run_update_single_app(app_run_port) {
         Ok(_) => {
             // 不做任何处理
         },
         Err(e) => {
-            println!("启动所有 APP 失败！{}", e);
+            exit_with_error("检查新版本失败！", e);
         },
     }
 }
 
-fn ask_update_single_app(app_run_port: u32) {
-    match update_single_app(app_run_port) {
+fn ask_dry_run_update_apps(selector: &Selector, jobs: usize) {
+    match dry_run_update_apps(selector, jobs) {
         Ok(_) => {
             // 不做任何处理
         },
         Err(e) => {
-            println!("升级项目失败！{}", e);
+            exit_with_error("检查新版本失败！", e);
         },
     }
 }
 
-fn ask_update_all_apps() {
-    match update_all_apps() {
+fn ask_stop_single_app(app_run_port: u32, force: bool) {
+    match stop_single_app(app_run_port, force) {
         Ok(_) => {
             // 不做任何处理
         },
         Err(e) => {
-            println!("升级所有 APP 失败！{}", e);
+            exit_with_error("停止单个 APP 失败！", e);
         },
     }
 }
 
-fn ask_stop_single_app(app_run_port: u32) {
-    match stop_single_app(app_run_port) {
+fn ask_stop_apps(selector: &Selector, force: bool, jobs: usize) {
+    match stop_apps(selector, force, jobs) {
         Ok(_) => {
             // 不做任何处理
         },
         Err(e) => {
-            println!("停止单个 APP 失败！{}", e);
+            exit_with_error("停止 APP 失败！", e);
         },
     }
 }
 
-fn ask_stop_all_apps() {
-    match stop_all_apps() {
+fn ask_restart_single_app(app_run_port: u32, force: bool) {
+    match restart_single_app(app_run_port, force) {
         Ok(_) => {
             // 不做任何处理
         },
         Err(e) => {
-            println!("停止所有 APP 失败！{}", e);
+            exit_with_error("重启项目失败！", e);
+        },
+    }
+}
+
+fn ask_restart_apps(selector: &Selector, force: bool, jobs: usize) {
+    match restart_apps(selector, force, jobs) {
+        Ok(_) => {
+            // 不做任何处理
         },
+        Err(e) => {
+            exit_with_error("重启 APP 失败！", e);
+        },
+    }
+}
+
+fn ask_rollback_app(app_run_port: u32, force: bool) {
+    match rollback_app(app_run_port, force) {
+        Ok(_) => {
+            // 不做任何处理
+        },
+        Err(e) => {
+            exit_with_error("回滚项目失败！", e);
+        },
+    }
+}
+
+fn ask_print_logs(app_run_port: u32, follow: bool, lines: usize) {
+    if let Err(e) = print_logs(app_run_port, follow, lines) {
+        exit_with_error("查看日志失败！", e);
+    }
+}
+
+fn ask_set_config(app_run_port: u32, key: &str, value: &str) {
+    if let Err(e) = set_config(app_run_port, key, value) {
+        exit_with_error("修改配置失败！", e);
+    }
+}
+
+fn ask_set_global_config(key: &str, value: &str) {
+    if let Err(e) = set_global_config(key, value) {
+        exit_with_error("修改配置失败！", e);
     }
 }
+
+fn ask_start_server(host: &str, port: Option<u32>) {
+    let port = port.unwrap_or_else(|| {
+        println!("> [INFO]: 使用默认端口 {}，可通过 --port <port> 选项指定端口", DEFAULT_SERVER_PORT);
+        DEFAULT_SERVER_PORT
+    });
+
+    if let Err(e) = server::start(host, port) {
+        exit_with_error("启动 Installer REST 服务失败！", e);
+    }
+}
+
+fn ask_start_daemon() {
+    if let Err(e) = daemon::start() {
+        exit_with_error("守护进程运行失败！", e);
+    }
+}
+
+fn ask_start_watch() {
+    if let Err(e) = watch::start() {
+        exit_with_error("定时升级失败！", e);
+    }
+}
+
+fn ask_start_proxy(port: u32, hold_timeout: u64) {
+    if let Err(e) = proxy::serve(port, hold_timeout) {
+        exit_with_error("代理运行失败！", e);
+    }
+}
+
+fn ask_write_log(path: &str) {
+    if let Err(e) = app_log::write(io::stdin(), Path::new(path)) {
+        exit_with_error("写入日志失败！", InstallerError::Io(e));
+    }
+}
+
+/// 打印错误信息，并使用错误对应的退出码退出程序，以便脚本根据退出码判断失败的原因
+fn exit_with_error(message: &str, e: InstallerError) -> ! {
+    eprintln!("> [ERROR]: {}{}", message, e);
+    exit(e.exit_code());
+}
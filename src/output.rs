@@ -0,0 +1,246 @@
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+use serde_derive::Serialize;
+
+use crate::error::InstallerError;
+use crate::installer_config::Installer;
+use crate::util::process;
+
+/// 当前命令的输出格式，由 `--output` 选项设置
+static FORMAT: AtomicU8 = AtomicU8::new(OutputFormat::Table as u8);
+
+thread_local! {
+    /// 同时处理多个 APP 时，当前线程打印的每行执行过程前添加的前缀，如 `[80] `
+    static PROGRESS_PREFIX: RefCell<Option<String>> = const { RefCell::new(None) };
+    /// 当前线程打印的执行过程是否在行首，只在行首添加前缀
+    static AT_LINE_START: Cell<bool> = const { Cell::new(true) };
+}
+
+/// 命令的输出格式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    /// 供人阅读的表格和执行过程，默认值
+    Table,
+    Json,
+    Yaml,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(OutputFormat::Table),
+            "json" => Ok(OutputFormat::Json),
+            "yaml" => Ok(OutputFormat::Yaml),
+            _ => Err(format!("不支持的输出格式 {}，只支持 table、json 和 yaml", s)),
+        }
+    }
+}
+
+/// 设置当前命令的输出格式，应在执行命令前调用
+pub fn set_format(format: OutputFormat) {
+    FORMAT.store(format as u8, Ordering::SeqCst);
+}
+
+/// 获取当前命令的输出格式
+pub fn get_format() -> OutputFormat {
+    match FORMAT.load(Ordering::SeqCst) {
+        v if v == OutputFormat::Json as u8 => OutputFormat::Json,
+        v if v == OutputFormat::Yaml as u8 => OutputFormat::Yaml,
+        _ => OutputFormat::Table,
+    }
+}
+
+/// 是否输出 json 或 yaml 格式的结构化数据
+pub fn is_structured() -> bool {
+    get_format() != OutputFormat::Table
+}
+
+/// 设置当前线程打印执行过程时每行的前缀，为 `None` 时不添加前缀。
+///
+/// 同时处理多个 APP 时，各线程的执行过程会交错打印，使用端口号作为前缀区分每行属于哪个 APP。
+pub fn set_progress_prefix(prefix: Option<String>) {
+    PROGRESS_PREFIX.with(|progress_prefix| *progress_prefix.borrow_mut() = prefix);
+    AT_LINE_START.with(|at_line_start| at_line_start.set(true));
+}
+
+/// 打印命令的执行过程。
+///
+/// 输出 json 或 yaml 时打印到标准错误，保证标准输出中只有结构化数据，否则打印到标准输出。
+/// 如果当前线程设置了前缀，则在每行前添加前缀。
+pub fn print_progress(args: fmt::Arguments) {
+    let content = PROGRESS_PREFIX.with(|prefix| {
+        prefix.borrow().as_ref().map(|prefix| AT_LINE_START.with(|at_line_start| {
+            let mut is_line_start = at_line_start.get();
+            let content = add_prefix(prefix, &args.to_string(), &mut is_line_start);
+            at_line_start.set(is_line_start);
+            content
+        }))
+    });
+
+    // 一次写入整段内容，防止与其他线程打印的内容交错
+    let content = content.unwrap_or_else(|| args.to_string());
+    if is_structured() {
+        let mut stderr = io::stderr();
+        let _ = stderr.write_all(content.as_bytes());
+        let _ = stderr.flush();
+    } else {
+        let mut stdout = io::stdout();
+        let _ = stdout.write_all(content.as_bytes());
+        let _ = stdout.flush();
+    }
+}
+
+/// 在 `text` 的每行行首添加 `prefix`，`is_line_start` 表示 `text` 是否从行首开始，处理后更新为结尾是否是换行
+fn add_prefix(prefix: &str, text: &str, is_line_start: &mut bool) -> String {
+    let mut content = String::with_capacity(text.len());
+    for line in text.split_inclusive('\n') {
+        if *is_line_start {
+            content.push_str(prefix);
+        }
+        content.push_str(line);
+        *is_line_start = line.ends_with('\n');
+    }
+    content
+}
+
+/// 同 `print!`，但输出 json 或 yaml 时打印到标准错误
+#[macro_export]
+macro_rules! progress {
+    ($($arg:tt)*) => {
+        $crate::output::print_progress(format_args!($($arg)*))
+    };
+}
+
+/// 同 `println!`，但输出 json 或 yaml 时打印到标准错误
+#[macro_export]
+macro_rules! progressln {
+    () => {
+        $crate::output::print_progress(format_args!("\n"))
+    };
+    ($($arg:tt)*) => {
+        $crate::output::print_progress(format_args!("{}\n", format_args!($($arg)*)))
+    };
+}
+
+/// APP 的结构化记录，由 installer 的配置信息和端口上的进程信息组成
+#[derive(Debug, Serialize, PartialEq)]
+pub struct AppRecord {
+    pub app_run_port: u32,
+    pub url: String,
+    pub app_name: String,
+    pub app_version: String,
+    pub jdk_name: String,
+    pub jdk_version: String,
+    pub tags: Vec<String>,
+    /// 是否有进程在监听 `app_run_port`
+    pub listening: bool,
+    /// 监听 `app_run_port` 的进程 id
+    pub pid: Option<u32>,
+    /// 同时处理多个 APP 时，此 APP 执行失败的原因
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl AppRecord {
+
+    /// 根据 installer 的配置信息和端口上当前运行的进程创建记录
+    pub fn new(installer: &Installer) -> Self {
+        let owner = process::get_port_owner(installer.app_run_port);
+        AppRecord {
+            app_run_port: installer.app_run_port,
+            url: installer.url.clone(),
+            app_name: installer.app_name.clone(),
+            app_version: installer.app_version.clone(),
+            jdk_name: installer.jdk_name.clone(),
+            jdk_version: installer.jdk_version.clone(),
+            tags: installer.tags.clone(),
+            listening: !owner.is_free(),
+            pid: owner.pid(),
+            error: None,
+        }
+    }
+}
+
+/// 将记录以 json 或 yaml 格式打印到标准输出，输出格式为 table 时不打印
+pub fn print_records<T: serde::Serialize>(records: &[T]) -> Result<(), InstallerError> {
+    if let Some(content) = render_records(get_format(), records)? {
+        println!("{}", content);
+    }
+    Ok(())
+}
+
+fn render_records<T: serde::Serialize>(format: OutputFormat, records: &[T]) -> Result<Option<String>, InstallerError> {
+    let content = match format {
+        OutputFormat::Table => return Ok(None),
+        OutputFormat::Json => serde_json::to_string_pretty(records)
+            .map_err(io::Error::other)?,
+        OutputFormat::Yaml => serde_yaml_ng::to_string(records)
+            .map_err(io::Error::other)?,
+    };
+    Ok(Some(content))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::{add_prefix, render_records, AppRecord, OutputFormat};
+
+    #[test]
+    fn output_format_from_str() {
+        assert_eq!(OutputFormat::Table, "table".parse().unwrap());
+        assert_eq!(OutputFormat::Json, "json".parse().unwrap());
+        assert_eq!(OutputFormat::Yaml, "yaml".parse().unwrap());
+        assert!("xml".parse::<OutputFormat>().is_err());
+    }
+
+    #[test]
+    fn add_prefix_at_line_start() {
+        let mut is_line_start = true;
+        assert_eq!("[80] a\n[80] b", add_prefix("[80] ", "a\nb", &mut is_line_start));
+        assert!(!is_line_start);
+        // 同一行的后续内容不再添加前缀
+        assert_eq!("完成\n", add_prefix("[80] ", "完成\n", &mut is_line_start));
+        assert!(is_line_start);
+        assert_eq!("[80] \n", add_prefix("[80] ", "\n", &mut is_line_start));
+        assert_eq!("", add_prefix("[80] ", "", &mut is_line_start));
+        assert!(is_line_start);
+    }
+
+    #[test]
+    fn render_records_success() -> Result<(), Box<dyn std::error::Error>> {
+        let records = [AppRecord {
+            app_run_port: 80,
+            url: "https://blocklang.com".to_string(),
+            app_name: "app".to_string(),
+            app_version: "0.1.0".to_string(),
+            jdk_name: "jdk".to_string(),
+            jdk_version: "11.0.2".to_string(),
+            tags: vec!["env=staging".to_string()],
+            listening: true,
+            pid: Some(1234),
+            error: None,
+        }];
+
+        assert_eq!(None, render_records(OutputFormat::Table, &records)?);
+
+        let json = render_records(OutputFormat::Json, &records)?.unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json)?;
+        assert_eq!(80, value[0]["app_run_port"]);
+        assert_eq!("0.1.0", value[0]["app_version"]);
+        assert_eq!(true, value[0]["listening"]);
+        assert_eq!(1234, value[0]["pid"]);
+        assert_eq!("env=staging", value[0]["tags"][0]);
+        assert!(value[0].get("error").is_none());
+
+        let yaml = render_records(OutputFormat::Yaml, &records)?.unwrap();
+        assert!(yaml.contains("app_run_port: 80"));
+        assert!(yaml.contains("pid: 1234"));
+
+        Ok(())
+    }
+}
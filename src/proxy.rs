@@ -0,0 +1,323 @@
+use std::convert::TryFrom;
+use std::fs;
+use std::io::{self, PipeWriter, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::ROOT_PATH_RUN;
+use crate::error::InstallerError;
+use crate::installer_config::InstallerConfig;
+use crate::progressln;
+
+/// 连接后端 APP 的超时时间
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+/// 保持连接时，两次尝试连接后端 APP 之间的时间间隔
+const RETRY_INTERVAL: Duration = Duration::from_millis(500);
+/// 代理同时转发的最大连接数，超过后新的连接直接返回 HTTP 503，每个连接会占用两个线程
+const MAX_CONNECTIONS: usize = 512;
+
+/// 获取记录后端端口的文件路径，即 `run/<app_run_port>.backend`。
+///
+/// 文件中只存放当前接收请求的 APP 的内部端口，代理每次建立连接时都会重新读取，
+/// 所以修改此文件即可将新的请求切换到另一个端口上。
+fn get_backend_path(app_run_port: u32) -> PathBuf {
+    Path::new(ROOT_PATH_RUN).join(format!("{}.backend", app_run_port))
+}
+
+/// 读取 `app_run_port` 端口上的代理当前转发到的后端端口，如果没有使用代理则返回 `None`
+pub fn read_backend_port(app_run_port: u32) -> Option<u32> {
+    fs::read_to_string(get_backend_path(app_run_port))
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// 将 `app_run_port` 端口上的代理切换到 `backend_port`。
+///
+/// 先写入临时文件再重命名，防止代理读到写了一半的文件。
+pub fn write_backend_port(app_run_port: u32, backend_port: u32) -> io::Result<()> {
+    let backend_path = get_backend_path(app_run_port);
+    if let Some(parent) = backend_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let temp_path = backend_path.with_extension("backend.tmp");
+    fs::write(&temp_path, backend_port.to_string())?;
+    fs::rename(temp_path, backend_path)
+}
+
+/// 删除 `app_run_port` 端口上代理的后端端口记录
+pub fn remove_backend_port(app_run_port: u32) {
+    let backend_path = get_backend_path(app_run_port);
+    if backend_path.exists() {
+        fs::remove_file(backend_path).unwrap_or_else(|e| {
+            progressln!("> [WARN]: 删除后端端口记录文件失败：{}", e);
+        });
+    }
+}
+
+/// 在后台启动 TCP 代理进程，即执行 `blocklang-installer proxy --port <app_run_port> --hold-timeout <hold_timeout>`。
+///
+/// 代理进程的标准输出和标准错误会写入 `log` 中，即 `app_log::open_proxy` 返回的日志。
+pub fn spawn(app_run_port: u32, hold_timeout: u64, log: PipeWriter) -> io::Result<Child> {
+    Command::new(std::env::current_exe()?)
+        .args(["proxy", "--port", &app_run_port.to_string(), "--hold-timeout", &hold_timeout.to_string()])
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log)
+        .spawn()
+}
+
+/// 在 `app_run_port` 端口上运行 TCP 代理，将每个连接转发到 `run/<app_run_port>.backend` 中记录的端口上。
+///
+/// 如果后端端口上没有 APP 在运行，如 APP 正在重启，则保持连接并每隔 0.5 秒重试一次，
+/// 超过 `proxy_hold_timeout` 秒后返回 HTTP 503 并关闭连接。`proxy_hold_timeout` 在每次建立连接时
+/// 从 installer 的配置中重新读取，读取失败时使用 `hold_timeout`。
+///
+/// 同时转发的连接数超过 `MAX_CONNECTIONS` 时，新的连接直接返回 HTTP 503。
+pub fn serve(app_run_port: u32, hold_timeout: u64) -> Result<(), InstallerError> {
+    let port = u16::try_from(app_run_port).map_err(|_| {
+        InstallerError::Validation(format!("端口 {} 超出了 0 到 65535 的范围", app_run_port))
+    })?;
+    let listener = TcpListener::bind(("0.0.0.0", port)).map_err(|e| {
+        InstallerError::Process(format!("代理无法监听 {} 端口，{}", app_run_port, e))
+    })?;
+    progressln!("> [INFO]: 代理运行在 {} 端口上", app_run_port);
+
+    let connections = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming() {
+        match stream {
+            Ok(client) => {
+                if connections.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+                    connections.fetch_sub(1, Ordering::SeqCst);
+                    progressln!("> [WARN]: {} 端口上的连接数超过了 {}，拒绝连接", app_run_port, MAX_CONNECTIONS);
+                    thread::spawn(move || reject(client, app_run_port));
+                    continue;
+                }
+
+                let connections = Arc::clone(&connections);
+                thread::spawn(move || {
+                    let hold_timeout = read_hold_timeout(app_run_port).unwrap_or(hold_timeout);
+                    if let Err(e) = forward(app_run_port, client, Duration::from_secs(hold_timeout)) {
+                        progressln!("> [WARN]: 转发 {} 端口上的连接失败，{}", app_run_port, e);
+                    }
+                    connections.fetch_sub(1, Ordering::SeqCst);
+                });
+            },
+            Err(e) => {
+                progressln!("> [WARN]: 接收 {} 端口上的连接失败，{}", app_run_port, e);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// 从 installer 的配置中读取 `app_run_port` 端口上代理保持连接的秒数，以便修改配置后不需要重启代理
+fn read_hold_timeout(app_run_port: u32) -> Option<u64> {
+    let installer_config = InstallerConfig::new().ok()?;
+    installer_config.get_by_port(app_run_port).map(|installer| installer.proxy_hold_timeout)
+}
+
+/// 将 `client` 连接转发到当前的后端端口上，直到任一方关闭连接。
+/// 
+/// 如果在 `hold_timeout` 内无法连接后端端口，则拒绝连接。
+fn forward(app_run_port: u32, client: TcpStream, hold_timeout: Duration) -> io::Result<()> {
+    let backend = match connect_backend(app_run_port, hold_timeout) {
+        Ok(backend) => backend,
+        Err(e) => {
+            reject(client, app_run_port);
+            return Err(e);
+        }
+    };
+
+    let mut client_reader = client.try_clone()?;
+    let mut backend_writer = backend.try_clone()?;
+    let upstream = thread::spawn(move || {
+        let _ = io::copy(&mut client_reader, &mut backend_writer);
+        let _ = backend_writer.shutdown(Shutdown::Write);
+    });
+
+    let mut backend_reader = backend;
+    let mut client_writer = client;
+    let _ = io::copy(&mut backend_reader, &mut client_writer);
+    let _ = client_writer.shutdown(Shutdown::Write);
+    let _ = upstream.join();
+
+    Ok(())
+}
+
+/// 连接后端端口，连接失败时每隔 `RETRY_INTERVAL` 重试一次，直到超过 `hold_timeout`。
+/// 
+/// 每次重试前都重新读取后端端口，因为蓝绿部署时可能已切换到另一个端口。
+fn connect_backend(app_run_port: u32, hold_timeout: Duration) -> io::Result<TcpStream> {
+    let started = Instant::now();
+    loop {
+        let result = match read_backend_port(app_run_port) {
+            Some(backend_port) => match u16::try_from(backend_port) {
+                Ok(backend_port) => {
+                    let addr = SocketAddr::from(([127, 0, 0, 1], backend_port));
+                    TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)
+                },
+                Err(_) => Err(io::Error::new(io::ErrorKind::InvalidData, format!("后端端口 {} 无效", backend_port))),
+            },
+            None => Err(io::Error::new(io::ErrorKind::NotFound, "没有找到后端端口")),
+        };
+
+        match result {
+            Ok(backend) => return Ok(backend),
+            Err(e) if started.elapsed() >= hold_timeout => return Err(e),
+            Err(_) => thread::sleep(RETRY_INTERVAL),
+        }
+    }
+}
+
+/// 生成拒绝连接时返回的响应，因为 APP 都是 Spring Boot web 应用，所以返回 HTTP 503
+fn service_unavailable_response(app_run_port: u32) -> String {
+    let body = format!("Service Unavailable: {} 端口上的 APP 暂时无法访问，请稍后重试", app_run_port);
+    format!("HTTP/1.1 503 Service Unavailable\r\n\
+        Content-Type: text/plain; charset=utf-8\r\n\
+        Content-Length: {}\r\n\
+        Retry-After: 10\r\n\
+        Connection: close\r\n\r\n\
+        {}", body.len(), body)
+}
+
+/// 返回 HTTP 503 后关闭连接。
+/// 
+/// 关闭前读取客户端已发送的请求，否则关闭有未读数据的连接时会发送 RST，客户端可能收不到响应。
+fn reject(mut client: TcpStream, app_run_port: u32) {
+    let _ = client.write_all(service_unavailable_response(app_run_port).as_bytes());
+    let _ = client.shutdown(Shutdown::Write);
+    let _ = client.set_read_timeout(Some(Duration::from_secs(1)));
+    let mut buf = [0_u8; 1024];
+    while let Ok(len) = client.read(&mut buf) {
+        if len == 0 {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::io::prelude::*;
+    use std::net::{Shutdown, TcpListener, TcpStream};
+    use std::thread;
+    use std::time::Duration;
+    use super::{forward, read_backend_port, remove_backend_port, service_unavailable_response, write_backend_port};
+
+    // 注意，测试用例中的端口不能相同，因为用例是并行运行的
+
+    #[test]
+    fn write_and_read_backend_port_success() -> Result<(), Box<dyn std::error::Error>> {
+        assert_eq!(None, read_backend_port(65011));
+
+        write_backend_port(65011, 18080)?;
+        assert_eq!(Some(18080), read_backend_port(65011));
+        write_backend_port(65011, 18081)?;
+        assert_eq!(Some(18081), read_backend_port(65011));
+
+        remove_backend_port(65011);
+        assert_eq!(None, read_backend_port(65011));
+        Ok(())
+    }
+
+    #[test]
+    fn forward_success() -> Result<(), Box<dyn std::error::Error>> {
+        // 一个将收到的内容原样返回的后端
+        let backend = TcpListener::bind("127.0.0.1:0")?;
+        let backend_port = backend.local_addr()?.port() as u32;
+        thread::spawn(move || {
+            let (mut stream, _) = backend.accept().unwrap();
+            let mut buf = Vec::new();
+            stream.read_to_end(&mut buf).unwrap();
+            stream.write_all(&buf).unwrap();
+        });
+        write_backend_port(65012, backend_port)?;
+
+        let proxy = TcpListener::bind("127.0.0.1:0")?;
+        let proxy_addr = proxy.local_addr()?;
+        let handle = thread::spawn(move || {
+            let (client, _) = proxy.accept().unwrap();
+            forward(65012, client, Duration::from_secs(0)).unwrap();
+        });
+
+        let mut client = TcpStream::connect(proxy_addr)?;
+        client.write_all(b"hello")?;
+        client.shutdown(Shutdown::Write)?;
+        let mut response = String::new();
+        client.read_to_string(&mut response)?;
+        assert_eq!("hello", response);
+
+        handle.join().unwrap();
+        remove_backend_port(65012);
+        Ok(())
+    }
+
+    #[test]
+    fn forward_reject_without_backend() -> Result<(), Box<dyn std::error::Error>> {
+        let proxy = TcpListener::bind("127.0.0.1:0")?;
+        let proxy_addr = proxy.local_addr()?;
+        let mut client = TcpStream::connect(proxy_addr)?;
+        client.write_all(b"GET / HTTP/1.1\r\n\r\n")?;
+        let (accepted, _) = proxy.accept()?;
+
+        assert!(forward(65013, accepted, Duration::from_secs(0)).is_err());
+
+        let mut response = String::new();
+        client.read_to_string(&mut response)?;
+        assert!(response.starts_with("HTTP/1.1 503 Service Unavailable"));
+        assert!(response.ends_with("\r\n\r\nService Unavailable: 65013 端口上的 APP 暂时无法访问，请稍后重试"));
+        Ok(())
+    }
+
+    #[test]
+    fn service_unavailable_response_content_length() {
+        let response = service_unavailable_response(65015);
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        assert!(head.contains(&format!("\r\nContent-Length: {}\r\n", body.len())));
+    }
+
+    #[test]
+    fn forward_hold_until_backend_started() -> Result<(), Box<dyn std::error::Error>> {
+        // 还没有后端端口，模拟正在重启的 APP
+        remove_backend_port(65014);
+
+        let proxy = TcpListener::bind("127.0.0.1:0")?;
+        let proxy_addr = proxy.local_addr()?;
+        let handle = thread::spawn(move || {
+            let (client, _) = proxy.accept().unwrap();
+            forward(65014, client, Duration::from_secs(10)).unwrap();
+        });
+
+        let mut client = TcpStream::connect(proxy_addr)?;
+        client.write_all(b"hello")?;
+        client.shutdown(Shutdown::Write)?;
+
+        // APP 启动后，代理才建立到 APP 的连接。
+        // 不复用刚释放的端口，而是在 APP 启动后再记录后端端口，防止端口被其他程序占用
+        thread::sleep(Duration::from_millis(600));
+        let backend = TcpListener::bind("127.0.0.1:0")?;
+        write_backend_port(65014, backend.local_addr()?.port() as u32)?;
+        let (mut stream, _) = backend.accept()?;
+        let mut buf = Vec::new();
+        stream.read_to_end(&mut buf)?;
+        stream.write_all(&buf)?;
+        drop(stream);
+
+        let mut response = String::new();
+        client.read_to_string(&mut response)?;
+        assert_eq!("hello", response);
+
+        handle.join().unwrap();
+        remove_backend_port(65014);
+        Ok(())
+    }
+}
@@ -0,0 +1,254 @@
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{self, prelude::*};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde_derive::{Deserialize, Serialize};
+use toml;
+
+use crate::config::ROOT_PATH_RUN;
+use crate::util::process;
+use crate::progressln;
+
+/// 比较进程的启动时间时允许的误差秒数。
+///
+/// 进程的启动时间由 `/proc/stat` 中的系统启动时间和 `/proc/<pid>/stat` 中以时钟周期计的启动时间换算而来，
+/// 两者都取整到秒，与系统时间相比可能有 1 秒左右的误差。
+const START_TIME_TOLERANCE: u64 = 2;
+
+/// 记录 installer 启动的 JVM 进程信息，用于确认运行在端口上的进程是否由 installer 启动。
+///
+/// 每个端口对应一个记录文件，存放在 `run/<app_run_port>.toml` 中。
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct RunRecord {
+    /// 进程 id
+    pub pid: u32,
+    /// 进程的启动时间，是从 1970-01-01 00:00:00 UTC 开始的秒数
+    pub start_time: u64,
+    /// 启动进程的完整命令
+    pub command_line: String,
+}
+
+impl RunRecord {
+
+    /// 根据进程 id 创建运行记录，启动时间为进程实际的启动时间，获取不到时使用当前时间
+    pub fn new(pid: u32) -> Self {
+        let start_time = process::get_start_time(pid).unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_secs())
+                .unwrap_or(0)
+        });
+
+        RunRecord {
+            pid,
+            start_time,
+            command_line: process::get_command_line(pid).unwrap_or_default(),
+        }
+    }
+
+    /// 读取运行在 `app_run_port` 端口上的进程的记录，如果没有记录则返回 `None`
+    pub fn load(app_run_port: u32) -> Option<Self> {
+        Self::load_from(&get_record_path(app_run_port))
+    }
+
+    pub fn load_from(file_path: &Path) -> Option<Self> {
+        let mut content = String::new();
+        File::open(file_path).ok()?.read_to_string(&mut content).ok()?;
+        toml::from_str::<RunRecord>(&content).ok()
+    }
+
+    /// 保存运行在 `app_run_port` 端口上的进程的记录，会覆盖之前的记录
+    pub fn save(&self, app_run_port: u32) -> std::io::Result<()> {
+        self.save_to(&get_record_path(app_run_port))
+    }
+
+    pub fn save_to(&self, file_path: &Path) -> io::Result<()> {
+        if let Some(parent) = file_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let toml_content = toml::to_vec(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let mut file = File::create(file_path)?;
+        file.write_all(toml_content.as_slice())
+    }
+
+    /// 删除运行在 `app_run_port` 端口上的进程的记录
+    pub fn remove(app_run_port: u32) {
+        let file_path = get_record_path(app_run_port);
+        if file_path.exists() {
+            fs::remove_file(file_path).unwrap_or_else(|e| {
+                progressln!("> [WARN]: 删除进程记录文件失败：{}", e);
+            });
+        }
+    }
+
+    /// 判断进程是否是此记录对应的进程
+    ///
+    /// 除了比较进程 id 外，还要比较启动进程的命令和进程的启动时间，防止进程 id 被系统重新分配给其他进程。
+    /// 获取不到启动命令时，无法确认是否是同一个进程，所以认为不是此记录对应的进程；
+    /// 获取不到启动时间时(如 windows 下)，只比较进程 id 和启动命令。
+    pub fn matches(&self, pid: u32) -> bool {
+        if self.pid != pid {
+            return false;
+        }
+
+        match process::get_command_line(pid) {
+            Some(command_line) if command_line == self.command_line => {},
+            _ => return false,
+        }
+
+        match process::get_start_time(pid) {
+            Some(start_time) => start_time.abs_diff(self.start_time) <= START_TIME_TOLERANCE,
+            None => true,
+        }
+    }
+}
+
+/// 获取运行记录文件的路径
+fn get_record_path(app_run_port: u32) -> PathBuf {
+    Path::new(ROOT_PATH_RUN).join(format!("{}.toml", app_run_port))
+}
+
+/// 获取端口操作锁文件的路径
+fn get_lock_path(app_run_port: u32) -> PathBuf {
+    Path::new(ROOT_PATH_RUN).join(format!("{}.lock", app_run_port))
+}
+
+/// 端口上 APP 的操作锁，防止 `update`、`watch` 和守护进程等同时停止或启动同一个 APP。
+///
+/// 锁文件存放在 `run/<app_run_port>.lock` 中，drop 时释放锁，进程意外退出时操作系统也会释放锁。
+pub struct PortLock {
+    _file: File,
+}
+
+impl PortLock {
+
+    /// 尝试获取 `app_run_port` 端口上的操作锁，如果锁已被其他命令持有则返回 `None`
+    pub fn try_acquire(app_run_port: u32) -> io::Result<Option<Self>> {
+        Self::try_acquire_at(&get_lock_path(app_run_port))
+    }
+
+    pub fn try_acquire_at(lock_path: &Path) -> io::Result<Option<Self>> {
+        if let Some(parent) = lock_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let file = OpenOptions::new().create(true).truncate(false).write(true).open(lock_path)?;
+        match file.try_lock() {
+            Ok(()) => Ok(Some(PortLock { _file: file })),
+            Err(TryLockError::WouldBlock) => Ok(None),
+            Err(TryLockError::Error(e)) => Err(e),
+        }
+    }
+
+    /// 判断 `app_run_port` 端口上的操作锁是否已被其他命令持有
+    pub fn is_held(app_run_port: u32) -> bool {
+        Self::is_held_at(&get_lock_path(app_run_port))
+    }
+
+    pub fn is_held_at(lock_path: &Path) -> bool {
+        matches!(Self::try_acquire_at(lock_path), Ok(None))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::fs;
+    use std::path::Path;
+    use std::process;
+    use super::{PortLock, RunRecord};
+
+    #[test]
+    fn save_and_load_success() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = "run_record_save_and_load_success";
+        let file_path = Path::new(dir).join("80.toml");
+
+        let record = RunRecord {
+            pid: 1,
+            start_time: 2,
+            command_line: "java -jar demo.jar --server.port=80".to_string(),
+        };
+        record.save_to(&file_path)?;
+
+        assert_eq!(Some(record), RunRecord::load_from(&file_path));
+
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn load_not_exist() {
+        assert_eq!(None, RunRecord::load_from(Path::new("run_record_not_exist.toml")));
+    }
+
+    #[test]
+    fn matches_current_process() {
+        let record = RunRecord::new(process::id());
+
+        assert!(record.start_time > 0);
+        assert!(record.matches(process::id()));
+    }
+
+    #[test]
+    fn matches_pid_not_match() {
+        let record = RunRecord::new(process::id());
+
+        assert!(!record.matches(process::id() + 1));
+    }
+
+    #[test]
+    fn matches_command_line_not_match() {
+        let record = RunRecord {
+            pid: process::id(),
+            start_time: 1,
+            command_line: "not-match-command".to_string(),
+        };
+
+        if cfg!(target_os = "linux") {
+            assert!(!record.matches(process::id()));
+        }
+    }
+
+    #[test]
+    fn matches_start_time_not_match() {
+        let record = RunRecord {
+            start_time: 1,
+            ..RunRecord::new(process::id())
+        };
+
+        // 进程 id 和启动命令都相同，但启动时间不同，说明进程 id 已被重新分配给其他进程
+        if cfg!(target_os = "linux") {
+            assert!(!record.matches(process::id()));
+        }
+    }
+
+    #[test]
+    fn matches_process_not_exist() {
+        let record = RunRecord {
+            pid: u32::MAX,
+            start_time: 1,
+            command_line: "java -jar demo.jar --server.port=80".to_string(),
+        };
+
+        assert!(!record.matches(u32::MAX));
+    }
+
+    #[test]
+    fn port_lock_held_until_dropped() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = "run_record_port_lock_held_until_dropped";
+        let lock_path = Path::new(dir).join("80.lock");
+
+        let lock = PortLock::try_acquire_at(&lock_path)?;
+        assert!(lock.is_some());
+        assert!(PortLock::try_acquire_at(&lock_path)?.is_none());
+        assert!(PortLock::is_held_at(&lock_path));
+
+        drop(lock);
+        assert!(!PortLock::is_held_at(&lock_path));
+        assert!(PortLock::try_acquire_at(&lock_path)?.is_some());
+
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,90 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::Path;
+use sha2::{Digest, Sha256};
+
+use crate::error::InstallerError;
+
+/// 计算文件的 SHA-256 校验和，返回小写的 16 进制字符串。
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::path::Path;
+/// use installer::util::checksum::sha256;
+///
+/// fn main() -> std::io::Result<()> {
+///     let checksum = sha256(Path::new("apps/app/0.1.0/app-0.1.0.jar"))?;
+///     println!("{}", checksum);
+///     Ok(())
+/// }
+/// ```
+pub fn sha256(file_path: &Path) -> io::Result<String> {
+    let mut reader = BufReader::new(File::open(file_path)?);
+    let mut hasher = Sha256::new();
+    io::copy(&mut reader, &mut hasher)?;
+
+    Ok(format!("{:x}", hasher.result()))
+}
+
+/// 校验文件的 SHA-256 校验和是否与 `expected` 一致，不区分大小写。
+///
+/// 如果校验和不一致，则删除此文件，并返回错误，这样下次运行时会重新下载文件。
+pub fn verify_sha256(file_path: &Path, expected: &str) -> Result<(), InstallerError> {
+    let actual = sha256(file_path)?;
+    if actual.eq_ignore_ascii_case(expected.trim()) {
+        return Ok(());
+    }
+
+    fs::remove_file(file_path)?;
+    Err(InstallerError::Extraction(format!("文件 {} 的 SHA-256 校验和不一致，期望值为 {}，实际值为 {}，已删除此文件",
+        file_path.display(),
+        expected,
+        actual)))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::fs::{self, File};
+    use std::io::prelude::*;
+    use std::path::Path;
+    use super::{sha256, verify_sha256};
+
+    // "Hello, World!" 的 SHA-256 校验和
+    const HELLO_WORLD_SHA256: &str = "dffd6021bb2bd5b0af676290809ec3a53191dd81c7f70a4b28688a362182986f";
+
+    #[test]
+    fn sha256_success() -> Result<(), Box<dyn std::error::Error>> {
+        let file_name = "checksum_sha256_success.txt";
+        File::create(file_name)?.write_all(b"Hello, World!")?;
+
+        assert_eq!(HELLO_WORLD_SHA256, sha256(Path::new(file_name))?);
+
+        fs::remove_file(file_name)?;
+        Ok(())
+    }
+
+    #[test]
+    fn verify_sha256_success() -> Result<(), Box<dyn std::error::Error>> {
+        let file_name = "checksum_verify_sha256_success.txt";
+        File::create(file_name)?.write_all(b"Hello, World!")?;
+
+        assert!(verify_sha256(Path::new(file_name), &HELLO_WORLD_SHA256.to_uppercase()).is_ok());
+        assert!(Path::new(file_name).exists());
+
+        fs::remove_file(file_name)?;
+        Ok(())
+    }
+
+    #[test]
+    fn verify_sha256_not_match_then_remove_file() -> Result<(), Box<dyn std::error::Error>> {
+        let file_name = "checksum_verify_sha256_not_match.txt";
+        File::create(file_name)?.write_all(b"Hello, World?")?;
+
+        assert!(verify_sha256(Path::new(file_name), HELLO_WORLD_SHA256).is_err());
+        assert!(!Path::new(file_name).exists());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,89 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, prelude::*};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// 同一个进程中生成不同的临时文件名，防止多个线程同时写入同一个临时文件
+static TEMP_FILE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// 先将内容写入临时文件再重命名为 `path`，防止其他进程读到写了一半的文件。
+///
+/// 如果 `path` 已存在，则覆盖此文件。
+pub fn write_atomically(path: &Path, content: &[u8]) -> io::Result<()> {
+    let temp_path = write_temp_file(path, content)?;
+    fs::rename(&temp_path, path).inspect_err(|_| {
+        let _ = fs::remove_file(&temp_path);
+    })
+}
+
+/// 同 `write_atomically`，但 `path` 已存在时不覆盖此文件，而是返回 `io::ErrorKind::AlreadyExists` 错误。
+///
+/// 用于创建默认的配置文件，防止覆盖其他进程同时创建并修改过的配置文件。
+pub fn create_atomically(path: &Path, content: &[u8]) -> io::Result<()> {
+    let temp_path = write_temp_file(path, content)?;
+    // 创建硬链接时，如果目标文件已存在则失败，不会覆盖目标文件
+    let result = fs::hard_link(&temp_path, path);
+    let _ = fs::remove_file(&temp_path);
+    result
+}
+
+fn write_temp_file(path: &Path, content: &[u8]) -> io::Result<PathBuf> {
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(format!(".{}.{}.tmp", process::id(), TEMP_FILE_COUNT.fetch_add(1, Ordering::SeqCst)));
+    let temp_path = PathBuf::from(temp_path);
+
+    let mut file = File::create(&temp_path)?;
+    file.write_all(content)?;
+    file.sync_all()?;
+    Ok(temp_path)
+}
+
+/// 跨进程的文件锁，drop 时释放锁，进程意外退出时操作系统也会释放锁。
+///
+/// 同一个进程中的多个线程分别获取锁时也会互斥。
+pub struct FileLock {
+    _file: File,
+}
+
+impl FileLock {
+
+    /// 获取 `path` 上的文件锁，如果锁已被其他进程或线程持有，则一直等待
+    pub fn acquire(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).truncate(false).write(true).open(path)?;
+        file.lock()?;
+        Ok(FileLock { _file: file })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::fs;
+    use std::io;
+    use std::path::Path;
+    use super::{create_atomically, write_atomically};
+
+    #[test]
+    fn write_atomically_overwrite() -> Result<(), Box<dyn std::error::Error>> {
+        let path = Path::new("file_write_atomically_overwrite.toml");
+        write_atomically(path, b"a = 1")?;
+        write_atomically(path, b"a = 2")?;
+        assert_eq!("a = 2", fs::read_to_string(path)?);
+
+        fs::remove_file(path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn create_atomically_not_overwrite() -> Result<(), Box<dyn std::error::Error>> {
+        let path = Path::new("file_create_atomically_not_overwrite.toml");
+        create_atomically(path, b"a = 1")?;
+        let e = create_atomically(path, b"a = 2").unwrap_err();
+        assert_eq!(io::ErrorKind::AlreadyExists, e.kind());
+        assert_eq!("a = 1", fs::read_to_string(path)?);
+
+        fs::remove_file(path)?;
+        Ok(())
+    }
+}
@@ -1,4 +1,7 @@
+pub mod checksum;
+pub mod file;
 pub mod net;
 pub mod os;
+pub mod parallel;
 pub mod process;
 pub mod zip;
\ No newline at end of file
@@ -12,8 +12,10 @@ use ipconfig::{self, IfType, OperStatus};
 #[cfg(target_os = "windows")]
 /// 获取 Windows 服务器的 IP 地址和 MAC 地址。
 /// 优先获取有线网络，如果没有找到有线网络，再获取无线网络
+/// 
+/// 如果没有获取到网络适配器信息，则返回 `None`
 pub fn get_interface_address() -> Option<InterfaceAddr> {
-    let adapters = ipconfig::get_adapters().expect("没有获取到网络适配器信息！");
+    let adapters = ipconfig::get_adapters().ok()?;
     // 先获取有线网络
     let mut matched = adapters.iter().find(|adapter| {
         adapter.oper_status() == OperStatus::IfOperStatusUp
@@ -28,16 +30,16 @@ pub fn get_interface_address() -> Option<InterfaceAddr> {
         });
     }
 
-    matched.map(|adapter| {
-            let ip_address = adapter.ip_addresses().get(1);
-            let mac_address = adapter.physical_address().unwrap();
+    matched.and_then(|adapter| {
+            let ip_address = adapter.ip_addresses().get(1)?;
+            let mac_address = adapter.physical_address()?;
             let mac_address: Vec<String> = mac_address.iter().map(|x| format!("{:x}", x)).collect();
             let mac_address = mac_address.join(":");
 
-            InterfaceAddr {
-                ip_address: ip_address.unwrap().to_string(),
+            Some(InterfaceAddr {
+                ip_address: ip_address.to_string(),
                 mac_address: mac_address.to_uppercase(),
-            }
+            })
         })
 }
 
@@ -48,27 +50,22 @@ use mac_address::mac_address_by_name;
 
 #[cfg(not(target_os = "windows"))]
 /// 获取 Linux 服务器的 IP 地址和 MAC 地址。
+/// 
+/// 如果没有找到能联网的网卡，或获取不到网卡的 MAC 地址，则返回 `None`
 pub fn get_interface_address() -> Option<InterfaceAddr> {
     // IP 地址
-    let ifaces = get_if_addrs::get_if_addrs().unwrap();
-    let iface = ifaces.iter().find(|&interface| !interface.is_loopback() && interface.ip().is_ipv4());
-    if iface == None {
-        return None;
-    }
+    let ifaces = get_if_addrs::get_if_addrs().ok()?;
+    let iface = ifaces.iter().find(|&interface| !interface.is_loopback() && interface.ip().is_ipv4())?;
 
     // MAC 地址
     // 注意，在 windows 平台下要传入的 name 不是适配器的 name，而是 Friendly Name
     // 但是 get_if_addrs 获取的却是适配器的 name。
-    let interface_name = &iface.unwrap().name;
-    let mac_address = match mac_address_by_name(interface_name) {
-        Ok(Some(mac)) => Some(mac),
-        Ok(None) => None,
-        Err(_) => None,
-    };
+    let interface_name = &iface.name;
+    let mac_address = mac_address_by_name(interface_name).ok()??;
 
     Some(InterfaceAddr {
-        ip_address: iface.unwrap().ip().to_string(),
-        mac_address: mac_address.unwrap().to_string().to_uppercase(),
+        ip_address: iface.ip().to_string(),
+        mac_address: mac_address.to_string().to_uppercase(),
     })
 }
 
@@ -0,0 +1,123 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+
+use crate::error::InstallerError;
+
+/// 使用最多 `jobs` 个线程处理 `items`，`f` 的参数是元素的索引和元素，返回的结果与 `items` 的顺序一致。
+///
+/// `jobs` 为 0 或 1 时，在当前线程中依次处理。如果有元素没有处理完成，则返回错误。
+pub fn map<T, R, F>(items: &[T], jobs: usize, f: F) -> Result<Vec<R>, InstallerError>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(usize, &T) -> R + Sync,
+{
+    if jobs <= 1 || items.len() <= 1 {
+        return Ok(items.iter().enumerate().map(|(index, item)| f(index, item)).collect());
+    }
+
+    let next_index = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<R>>> = Mutex::new(items.iter().map(|_| None).collect());
+
+    thread::scope(|scope| {
+        for _ in 0..jobs.min(items.len()) {
+            scope.spawn(|| loop {
+                let index = next_index.fetch_add(1, Ordering::SeqCst);
+                let item = match items.get(index) {
+                    Some(item) => item,
+                    None => break,
+                };
+
+                let result = f(index, item);
+                results.lock().unwrap_or_else(PoisonError::into_inner)[index] = Some(result);
+            });
+        }
+    });
+
+    results.into_inner()
+        .unwrap_or_else(PoisonError::into_inner)
+        .into_iter()
+        .collect::<Option<Vec<R>>>()
+        .ok_or_else(|| InstallerError::Process("并发处理时有 installer 没有处理完成".to_string()))
+}
+
+/// 按文件路径加锁，同一个路径同时只有一个线程能持有锁，
+/// 用于防止多个线程同时下载或解压同一个文件。
+pub struct PathLocks {
+    locks: Mutex<BTreeMap<PathBuf, Arc<Mutex<()>>>>,
+}
+
+impl PathLocks {
+
+    pub const fn new() -> Self {
+        PathLocks {
+            locks: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// 获取 `path` 对应的锁，相同的路径返回同一个锁
+    pub fn get(&self, path: &Path) -> Arc<Mutex<()>> {
+        self.locks.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(path.to_path_buf())
+            .or_default()
+            .clone()
+    }
+}
+
+impl Default for PathLocks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::path::Path;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+    use super::{map, PathLocks};
+
+    #[test]
+    fn map_keep_order() {
+        let items = [5, 1, 4, 2, 3];
+        let expected = vec![10, 2, 8, 4, 6];
+        for jobs in 0..7 {
+            assert_eq!(expected, map(&items, jobs, |_, item| {
+                thread::sleep(Duration::from_millis(*item as u64 * 5));
+                item * 2
+            }).unwrap());
+        }
+        assert_eq!(vec![0, 1, 2], map(&["a", "b", "c"], 2, |index, _| index).unwrap());
+    }
+
+    #[test]
+    fn map_bounded_jobs() {
+        let running = AtomicUsize::new(0);
+        let max_running = AtomicUsize::new(0);
+        map(&[0; 8], 3, |_, _| {
+            let count = running.fetch_add(1, Ordering::SeqCst) + 1;
+            max_running.fetch_max(count, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(20));
+            running.fetch_sub(1, Ordering::SeqCst);
+        }).unwrap();
+        // 线程的调度顺序不确定，只能保证同时运行的数量不超过 jobs
+        assert!(max_running.load(Ordering::SeqCst) <= 3);
+    }
+
+    #[test]
+    fn path_locks_same_path() {
+        let path_locks = PathLocks::new();
+        let a = path_locks.get(Path::new("apps/jdk/11.0.2/jdk.zip"));
+        let b = path_locks.get(Path::new("apps/jdk/11.0.2/jdk.zip"));
+        let c = path_locks.get(Path::new("apps/jdk/12.0.1/jdk.zip"));
+        assert!(Arc::ptr_eq(&a, &b));
+        assert!(!Arc::ptr_eq(&a, &c));
+    }
+}
@@ -1,34 +1,198 @@
-use std::process::{Command, Stdio};
-use std::io::{BufRead, BufReader};
+use std::process::Command;
+use std::thread;
+use std::time::{Duration, Instant};
+use std::fs;
+use std::path::Path;
 
-/// 根据指定的端口号获取进程 id，
-/// 如果返回 `None`，说明端口没有被占用。
-pub fn get_id(port: u32) -> Option<u32> {
-    let child = if cfg!(target_os = "windows") {
+use crate::progressln;
+use crate::error::InstallerError;
+#[cfg(target_os = "linux")]
+use std::io;
+
+/// 监听端口的进程
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PortOwner {
+    /// 端口没有被监听
+    Free,
+    /// 端口被此进程监听
+    Process(u32),
+    /// 端口已被监听，但没有权限查看或无法确定监听端口的进程
+    Unknown,
+}
+
+impl PortOwner {
+
+    /// 端口是否没有被监听
+    pub fn is_free(&self) -> bool {
+        *self == PortOwner::Free
+    }
+
+    /// 监听端口的进程 id，端口没有被监听或无法确定进程时返回 `None`
+    pub fn pid(&self) -> Option<u32> {
+        match *self {
+            PortOwner::Process(pid) => Some(pid),
+            _ => None,
+        }
+    }
+}
+
+/// 根据指定的端口号获取监听此端口的进程。
+/// 
+/// 在 linux 下优先解析 `/proc/net/tcp`、`/proc/net/tcp6` 和 `/proc/<pid>/fd`，
+/// 如果无法读取 `/proc` 或没有权限查看占用端口的进程，则使用 `netstat` 命令查找。
+/// 
+/// 如果确定端口已被监听，但是找不到对应的进程(如没有权限或没有安装 `netstat`)，
+/// 则返回 `PortOwner::Unknown`，不能当作端口没有被占用。
+pub fn get_port_owner(port: u32) -> PortOwner {
+    #[cfg(target_os = "linux")]
+    {
+        match get_id_from_proc(port) {
+            Ok(Some(pid)) => return PortOwner::Process(pid),
+            Ok(None) => return PortOwner::Free,
+            // 端口已被监听，但是没有权限查看对应的进程
+            Err(ref e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                return match get_owner_from_netstat(port) {
+                    Some(PortOwner::Process(pid)) => PortOwner::Process(pid),
+                    _ => PortOwner::Unknown,
+                };
+            },
+            Err(_) => {},
+        }
+    }
+
+    get_owner_from_netstat(port).unwrap_or(PortOwner::Free)
+}
+
+/// 使用 `netstat` 命令查找监听端口的进程，如果无法执行 `netstat` 命令，则返回 `None`
+fn get_owner_from_netstat(port: u32) -> Option<PortOwner> {
+    let output = if cfg!(target_os = "windows") {
         // netstat -ano | findstr 8080
         Command::new("cmd")
-            .args(&["/C", &format!("netstat -ano | findstr {}", port)])
-            .stdout(Stdio::piped())
-            .spawn()
-            .unwrap_or_else(|_| panic!("Failed to execute netstat -ano | findstr {}", port))
-    } else if cfg!(target_os = "linux") {
+            .args(["/C", &format!("netstat -ano | findstr {}", port)])
+            .output()
+    } else {
         // netstat -apn | grep 8080
         Command::new("sh")
-            .args(&["-c", &format!("netstat -apn | grep {}", port)])
-            .stdout(Stdio::piped())
-            .spawn()
-            .unwrap_or_else(|_| panic!("Failed to execute netstat -apn | grep {}", port))
-    } else {
-        unimplemented!();
+            .args(["-c", &format!("netstat -apn | grep {}", port)])
+            .output()
+    };
+
+    // 使用 output 等待 netstat 命令执行完成，防止产生僵尸进程
+    let output = match output {
+        Ok(output) => output,
+        Err(e) => {
+            eprintln!("> [ERROR]: 执行 netstat 命令时出错：{}", e);
+            return None;
+        }
     };
 
-    BufReader::new(child.stdout.unwrap())
+    let owner = String::from_utf8_lossy(&output.stdout)
         .lines()
-        .find_map(|line| extract_process_id(line.unwrap(), port))
+        .find_map(|line| extract_port_owner(line, port))
+        .unwrap_or(PortOwner::Free);
+    Some(owner)
 }
 
-#[cfg(target_os = "windows")]
+/// 通过解析 `/proc` 文件系统查找监听端口的进程 id。
+/// 
+/// 1. 在 `/proc/net/tcp` 和 `/proc/net/tcp6` 中找到监听此端口的 socket inode；
+/// 2. 在 `/proc/<pid>/fd` 中找到指向此 socket inode 的进程。
+/// 
+/// 返回 `Ok(None)` 表示端口没有被监听；如果端口已被监听，但是没有权限查看对应的进程，
+/// 或者无法读取 `/proc/net/tcp`，则返回 `Err`。
+#[cfg(target_os = "linux")]
+fn get_id_from_proc(port: u32) -> io::Result<Option<u32>> {
+    let mut inodes = Vec::new();
+    for file_name in &["/proc/net/tcp", "/proc/net/tcp6"] {
+        match fs::read_to_string(file_name) {
+            Ok(content) => {
+                // 第一行是标题行
+                inodes.extend(content.lines().skip(1).filter_map(|line| extract_listen_inode(line, port)));
+            },
+            // 系统可能没有启用 IPv6
+            Err(ref e) if *file_name == "/proc/net/tcp6" && e.kind() == io::ErrorKind::NotFound => {},
+            Err(e) => return Err(e),
+        }
+    }
+
+    if inodes.is_empty() {
+        return Ok(None);
+    }
+
+    match find_process_id_by_inodes(&inodes) {
+        Some(pid) => Ok(Some(pid)),
+        None => Err(io::Error::new(io::ErrorKind::PermissionDenied, 
+            format!("端口 {} 已被监听，但没有找到对应的进程", port))),
+    }
+}
+
+/// 从 `/proc/net/tcp` 的一行数据中解析出监听 `port` 端口的 socket inode。
+/// 
+/// ```text
+/// sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+/// 0: 00000000:1F90 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 12345 ...
+/// ```
+/// 
+/// 其中 `local_address` 中的端口号和状态 `st` 都是 16 进制，`0A` 表示 `LISTEN`。
+#[cfg(target_os = "linux")]
+fn extract_listen_inode(line: &str, port: u32) -> Option<u64> {
+    // TCP_LISTEN
+    const STATE_LISTEN: &str = "0A";
+
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    let local_address = parts.get(1)?;
+    let state = parts.get(3)?;
+    let inode = parts.get(9)?;
+
+    let local_port = local_address.rsplit(':').next()?;
+    if u32::from_str_radix(local_port, 16).ok()? != port {
+        return None;
+    }
+    if *state != STATE_LISTEN {
+        return None;
+    }
+
+    match inode.parse::<u64>().ok()? {
+        0 => None,
+        inode => Some(inode),
+    }
+}
+
+/// 遍历 `/proc/<pid>/fd`，找到打开了 socket inode 的进程。
+/// 
+/// 注意：没有权限读取的进程会被跳过。
+#[cfg(target_os = "linux")]
+fn find_process_id_by_inodes(inodes: &[u64]) -> Option<u32> {
+    let sockets: Vec<String> = inodes.iter().map(|inode| format!("socket:[{}]", inode)).collect();
+
+    fs::read_dir("/proc").ok()?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.file_name().to_str().and_then(|name| name.parse::<u32>().ok()))
+        .find(|pid| {
+            fs::read_dir(format!("/proc/{}/fd", pid))
+                .map(|fds| {
+                    fds.filter_map(|fd| fd.ok())
+                        .filter_map(|fd| fs::read_link(fd.path()).ok())
+                        .any(|link| sockets.iter().any(|socket| link.to_str() == Some(socket)))
+                })
+                .unwrap_or(false)
+        })
+}
+
+/// 根据指定的端口号获取监听此端口的进程 id，端口没有被监听或无法确定进程时返回 `None`，详见 `get_port_owner`
+pub fn get_id(port: u32) -> Option<u32> {
+    get_port_owner(port).pid()
+}
+
+/// 从 `netstat` 命令输出的一行数据中解析出监听 `port` 端口的进程 id，
+/// 不是监听此端口的行，或者无法确定进程时返回 `None`
 pub fn extract_process_id(line: String, port: u32) -> Option<u32> {
+    extract_port_owner(&line, port).and_then(|owner| owner.pid())
+}
+
+/// 从 `netstat` 命令输出的一行数据中解析出监听 `port` 端口的进程，不是监听此端口的行返回 `None`
+#[cfg(target_os = "windows")]
+fn extract_port_owner(line: &str, port: u32) -> Option<PortOwner> {
     // TCP    0.0.0.0:8080    0.0.0.0:0    LISTENING    1
     let trimed_line = line.trim();
     if trimed_line.is_empty() {
@@ -38,21 +202,24 @@ pub fn extract_process_id(line: String, port: u32) -> Option<u32> {
         .split(|c: char| c.is_whitespace() || c.is_control())
         .filter(|&s| !s.is_empty())
         .collect();
-    if parts[0] != "TCP" {
+    if *parts.first()? != "TCP" {
         return None;
     }
-    if !parts[1].ends_with(&format!(":{}", port)) {
+    if !parts.get(1)?.ends_with(&format!(":{}", port)) {
         return None;
     }
-    if parts[3] != "LISTENING" {
+    if *parts.get(3)? != "LISTENING" {
         return None;
     }
-    let pid = parts[4].parse::<u32>().expect("将字符串类型的 pid 转换为 u32 类型时出错");
-    Some(pid)
+    match parts.get(4).and_then(|pid| pid.parse::<u32>().ok()) {
+        Some(pid) => Some(PortOwner::Process(pid)),
+        None => Some(PortOwner::Unknown),
+    }
 }
 
-#[cfg(target_os = "linux")]
-pub fn extract_process_id(line: String, port: u32) -> Option<u32> {
+/// 从 `netstat` 命令输出的一行数据中解析出监听 `port` 端口的进程，不是监听此端口的行返回 `None`
+#[cfg(not(target_os = "windows"))]
+fn extract_port_owner(line: &str, port: u32) -> Option<PortOwner> {
     // tcp    0    0 0.0.0.0:8080    0.0.0.0:*    LISTEN    1/java
     let trimed_line = line.trim();
     if trimed_line.is_empty() {
@@ -62,46 +229,273 @@ pub fn extract_process_id(line: String, port: u32) -> Option<u32> {
         .split(|c: char| c.is_whitespace() || c.is_control())
         .filter(|&s| s.len() > 0)
         .collect();
-    if parts[0] != "tcp" {
+    // 同时支持 IPv4 和 IPv6
+    if *parts.first()? != "tcp" && *parts.first()? != "tcp6" {
         return None;
     }
-    if !parts[3].ends_with(&format!(":{}", port)) {
+    if !parts.get(3)?.ends_with(&format!(":{}", port)) {
         return None;
     }
-    if parts[5] != "LISTEN" {
+    if *parts.get(5)? != "LISTEN" {
         return None;
     }
-    let pid_parts: Vec<&str> = parts[6].split("/").collect();
-    let pid = pid_parts[0].parse::<u32>().expect("将字符串类型的 pid 转换为 u32 类型时出错");
-    Some(pid)
+    // 没有权限查看进程信息时，pid 部分显示为 `-`
+    match parts.get(6).and_then(|pid| pid.split('/').next()?.parse::<u32>().ok()) {
+        Some(pid) => Some(PortOwner::Process(pid)),
+        None => Some(PortOwner::Unknown),
+    }
 }
 
 // 搜 80 端口时，需要精确匹配，不要匹配到 8080 端口
 
-/// 根据进程 id 杀死进程
-pub fn kill(process_id: u32) {
+/// 停止进程，并等待进程释放端口。
+/// 
+/// 先通知进程正常退出（linux 下发送 `SIGTERM`，windows 下执行不带 `/F` 的 `taskkill`），
+/// 让 Spring Boot 有机会执行 shutdown hook、处理完已建立的连接和输出日志。
+/// 如果在 `grace_period` 内端口依然被此进程占用，则强制杀死进程。
+/// 
+/// windows 下没有窗口的进程（如 `javaw`）不接受不带 `/F` 的 `taskkill`，此时直接强制杀死进程。
+pub fn terminate(process_id: u32, port: u32, grace_period: Duration) -> Result<(), InstallerError> {
+    // 无法确定监听端口的进程时，视为进程依然在运行
+    terminate_with(process_id, grace_period, || match get_port_owner(port) {
+        PortOwner::Process(pid) => pid == process_id,
+        PortOwner::Unknown => true,
+        PortOwner::Free => false,
+    })
+}
+
+/// 停止进程，`is_running` 用于判断进程是否依然在运行
+fn terminate_with<F>(process_id: u32, grace_period: Duration, mut is_running: F) -> Result<(), InstallerError>
+    where F: FnMut() -> bool {
+    if !request_exit(process_id)? {
+        if !is_running() {
+            return Ok(());
+        }
+        progressln!("> [WARN]: 无法通知进程 {} 正常退出，强制关闭进程", process_id);
+        return kill(process_id);
+    }
+
+    progressln!("> [INFO]: 已通知进程 {} 退出，最多等待 {} 秒", process_id, grace_period.as_secs());
+
+    let started = Instant::now();
+    while started.elapsed() < grace_period {
+        if !is_running() {
+            return Ok(());
+        }
+        thread::sleep(Duration::from_millis(500));
+    }
+
+    progressln!("> [WARN]: 进程 {} 未在 {} 秒内退出，强制关闭进程", process_id, grace_period.as_secs());
+    kill(process_id)
+}
+
+/// 通知进程正常退出，返回通知是否成功
+fn request_exit(process_id: u32) -> Result<bool, InstallerError> {
+    let output = if cfg!(target_os = "windows") {
+        // taskkill /PID xxx
+        Command::new("taskkill").args(["/PID", &process_id.to_string()]).output()
+    } else {
+        // kill -15 xxx
+        Command::new("kill").args(["-15", &process_id.to_string()]).output()
+    };
+    let output = output.map_err(|e| InstallerError::Process(format!("通知进程 {} 退出时出错：{}", process_id, e)))?;
+    Ok(output.status.success())
+}
+
+/// 获取启动进程的完整命令，命令和参数之间用空格分隔。
+/// 
+/// 如果进程不存在或没有权限查看，则返回 `None`。
+pub fn get_command_line(process_id: u32) -> Option<String> {
+    let command_line = if cfg!(target_os = "windows") {
+        // wmic process where processid=xxx get commandline /value
+        let output = Command::new("cmd")
+            .args(["/C", &format!("wmic process where processid={} get commandline /value", process_id)])
+            .output()
+            .ok()?;
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("CommandLine=").map(|value| value.trim().to_string()))?
+    } else {
+        // /proc/<pid>/cmdline 中的参数以 \0 分隔
+        let content = fs::read(format!("/proc/{}/cmdline", process_id)).ok()?;
+        content.split(|&b| b == 0)
+            .filter(|arg| !arg.is_empty())
+            .map(|arg| String::from_utf8_lossy(arg).to_string())
+            .collect::<Vec<String>>()
+            .join(" ")
+    };
+
+    if command_line.is_empty() {
+        None
+    } else {
+        Some(command_line)
+    }
+}
+
+/// 判断进程是否还在运行，无法确定时返回 `None`。
+/// 
+/// linux 下根据 `/proc/<pid>` 是否存在判断；其他类 unix 系统使用 `kill -0`，没有权限向进程发信号时无法确定；
+/// windows 下使用 `tasklist` 查找进程，`tasklist` 执行失败时无法确定。
+pub fn is_alive(process_id: u32) -> Option<bool> {
     if cfg!(target_os = "windows") {
-        // taskkill /F /PID xxx
-        Command::new("cmd")
-            .args(&["/C", &format!("taskkill /F /PID {}", process_id)])
+        // tasklist /FI "PID eq xxx" /FO CSV /NH，没有找到进程时只输出一行不带引号的提示信息
+        let output = Command::new("tasklist")
+            .args(["/FI", &format!("PID eq {}", process_id), "/FO", "CSV", "/NH"])
             .output()
-            .unwrap_or_else(|_| panic!("执行 taskkill /F /PID {} 时出错", process_id));
-        println!("> [INFO]: 关闭进程 {} ", process_id);
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        Some(stdout.contains(&format!(",\"{}\",", process_id)))
     } else if cfg!(target_os = "linux") {
-        // kill -9 xxx
-        Command::new("sh")
-            .args(&["-c", &format!("kill -9 {}", process_id)])
-            .output()
-            .unwrap_or_else(|_| panic!("执行 kill -9 {} 时出错", process_id));
+        // 没有挂载 /proc 时无法确定
+        if !Path::new("/proc/self").exists() {
+            return None;
+        }
+        Some(Path::new("/proc").join(process_id.to_string()).exists())
+    } else {
+        let output = Command::new("kill").args(["-0", &process_id.to_string()]).output().ok()?;
+        if output.status.success() {
+            return Some(true);
+        }
+        // 进程存在但没有权限发信号时也会失败，只有进程不存在时才认为进程已退出
+        if String::from_utf8_lossy(&output.stderr).contains("No such process") {
+            Some(false)
+        } else {
+            None
+        }
+    }
+}
+
+/// 获取进程的启动时间，是从 1970-01-01 00:00:00 UTC 开始的秒数，只支持 linux。
+/// 
+/// 根据 `/proc/stat` 中的系统启动时间 `btime` 和 `/proc/<pid>/stat` 中进程相对于系统启动的时间计算。
+/// 如果进程不存在、没有权限查看或者不是 linux 系统，则返回 `None`。
+pub fn get_start_time(process_id: u32) -> Option<u64> {
+    #[cfg(target_os = "linux")]
+    {
+        let stat = fs::read_to_string(format!("/proc/{}/stat", process_id)).ok()?;
+        let system_stat = fs::read_to_string("/proc/stat").ok()?;
+
+        let (start_ticks, _) = parse_stat_ticks(&stat)?;
+        let boot_time = parse_boot_time(&system_stat)?;
+        Some(boot_time + start_ticks / CLOCK_TICKS_PER_SEC)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = process_id;
+        None
+    }
+}
+
+/// 从 `/proc/stat` 中解析出系统的启动时间，如 `btime 1700000000`
+#[cfg(target_os = "linux")]
+fn parse_boot_time(system_stat: &str) -> Option<u64> {
+    system_stat.lines()
+        .find_map(|line| line.strip_prefix("btime "))?
+        .trim()
+        .parse::<u64>()
+        .ok()
+}
+
+/// 从 `/proc` 中读取的进程资源使用情况
+#[derive(Debug, PartialEq)]
+pub struct ProcessStats {
+    /// 进程已运行的时间
+    pub uptime: Duration,
+    /// 进程占用的物理内存(RSS)，单位为字节
+    pub memory: u64,
+    /// 进程在用户态和内核态使用的 CPU 时间之和
+    pub cpu_time: Duration,
+}
+
+/// 获取进程的运行时间、内存和 CPU 使用情况，只支持 linux。
+/// 
+/// 如果进程不存在、没有权限查看或者不是 linux 系统，则返回 `None`。
+pub fn get_stats(process_id: u32) -> Option<ProcessStats> {
+    #[cfg(target_os = "linux")]
+    {
+        let stat = fs::read_to_string(format!("/proc/{}/stat", process_id)).ok()?;
+        let status = fs::read_to_string(format!("/proc/{}/status", process_id)).ok()?;
+        let system_uptime = fs::read_to_string("/proc/uptime").ok()?;
+
+        let (start_ticks, cpu_ticks) = parse_stat_ticks(&stat)?;
+        let system_uptime = system_uptime.split_whitespace().next()?.parse::<f64>().ok()?;
+        let uptime = (system_uptime - start_ticks as f64 / CLOCK_TICKS_PER_SEC as f64).max(0.0);
+
+        Some(ProcessStats {
+            uptime: Duration::from_secs_f64(uptime),
+            memory: parse_status_rss(&status)?,
+            cpu_time: Duration::from_millis(cpu_ticks * 1000 / CLOCK_TICKS_PER_SEC),
+        })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = process_id;
+        None
+    }
+}
+
+/// `/proc/<pid>/stat` 中时间的单位，即 `USER_HZ`，linux 对用户空间固定为 100
+#[cfg(target_os = "linux")]
+const CLOCK_TICKS_PER_SEC: u64 = 100;
+
+/// 从 `/proc/<pid>/stat` 中解析出进程的启动时间和使用的 CPU 时间，单位都是 `USER_HZ`。
+/// 
+/// ```text
+/// pid (comm) state ppid ... utime(14) stime(15) ... starttime(22) ...
+/// ```
+/// 
+/// 进程名 `comm` 中可能包含空格和括号，所以从最后一个 `)` 之后开始解析。
+#[cfg(target_os = "linux")]
+fn parse_stat_ticks(stat: &str) -> Option<(u64, u64)> {
+    let fields: Vec<&str> = stat[stat.rfind(')')? + 1..].split_whitespace().collect();
+    // fields[0] 是第 3 个字段 state
+    let utime = fields.get(11)?.parse::<u64>().ok()?;
+    let stime = fields.get(12)?.parse::<u64>().ok()?;
+    let start_time = fields.get(19)?.parse::<u64>().ok()?;
+    Some((start_time, utime + stime))
+}
+
+/// 从 `/proc/<pid>/status` 中解析出进程占用的物理内存，如 `VmRSS:    123456 kB`
+#[cfg(target_os = "linux")]
+fn parse_status_rss(status: &str) -> Option<u64> {
+    let kb = status.lines()
+        .find_map(|line| line.strip_prefix("VmRSS:"))?
+        .split_whitespace()
+        .next()?
+        .parse::<u64>()
+        .ok()?;
+    Some(kb * 1024)
+}
+
+/// 根据进程 id 杀死进程
+pub fn kill(process_id: u32) -> Result<(), InstallerError> {
+    let output = if cfg!(target_os = "windows") {
+        // taskkill /F /PID xxx
+        Command::new("taskkill").args(["/F", "/PID", &process_id.to_string()]).output()
     } else {
-        unimplemented!();
+        // kill -9 xxx
+        Command::new("kill").args(["-9", &process_id.to_string()]).output()
+    };
+    let output = output.map_err(|e| InstallerError::Process(format!("强制关闭进程 {} 时出错：{}", process_id, e)))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(InstallerError::Process(format!("强制关闭进程 {} 失败：{}", process_id, stderr.trim())));
     }
+    progressln!("> [INFO]: 关闭进程 {} ", process_id);
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
 
-    use super::{get_id, extract_process_id};
+    use super::{get_id, get_port_owner, extract_process_id, extract_port_owner, PortOwner};
+    #[cfg(target_os = "linux")]
+    use super::{is_alive, terminate_with, extract_listen_inode, get_start_time, get_stats, parse_boot_time, parse_stat_ticks, parse_status_rss};
 
     #[test]
     fn get_id_none() {
@@ -167,4 +561,191 @@ mod tests {
         
         assert_eq!(Some(1), extract_process_id(input.to_string(), 80));
     }
+
+    #[test]
+    fn get_port_owner_free() {
+        let not_exist_port = 12_345_678;
+        assert_eq!(PortOwner::Free, get_port_owner(not_exist_port));
+    }
+
+    #[test]
+    fn extract_port_owner_success() {
+        let input = if cfg!(target_os = "windows") {
+             r#"TCP    0.0.0.0:80      0.0.0.0:0    LISTENING    1"#
+        } else {
+             r#"tcp    0    0 0.0.0.0:80      0.0.0.0:*    LISTEN    1/java"#
+        };
+        
+        assert_eq!(Some(PortOwner::Process(1)), extract_port_owner(input, 80));
+    }
+
+    #[test]
+    fn extract_port_owner_tcp6_success() {
+        let input = if cfg!(target_os = "windows") {
+             r#"TCP    [::]:80      [::]:0    LISTENING    1"#
+        } else {
+             r#"tcp6    0    0 :::80      :::*    LISTEN    1/java"#
+        };
+        
+        assert_eq!(Some(PortOwner::Process(1)), extract_port_owner(input, 80));
+    }
+
+    #[test]
+    fn extract_port_owner_malformed_line() {
+        let input = if cfg!(target_os = "windows") {
+             r#"TCP    0.0.0.0:80"#
+        } else {
+             r#"tcp    0    0 0.0.0.0:80"#
+        };
+        
+        assert_eq!(None, extract_port_owner(input, 80));
+    }
+
+    #[test]
+    fn extract_port_owner_pid_is_hidden() {
+        let input = if cfg!(target_os = "windows") {
+             r#"TCP    0.0.0.0:80      0.0.0.0:0    LISTENING    -"#
+        } else {
+             r#"tcp    0    0 0.0.0.0:80      0.0.0.0:*    LISTEN    -"#
+        };
+        
+        // 端口已被监听，只是无法确定进程
+        assert_eq!(Some(PortOwner::Unknown), extract_port_owner(input, 80));
+        assert_eq!(None, extract_process_id(input.to_string(), 80));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn extract_listen_inode_success() {
+        // 1F90 即 8080
+        let input = "   0: 00000000:1F90 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 12345 1 0000000000000000 100 0 0 10 0";
+        assert_eq!(Some(12345), extract_listen_inode(input, 8080));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn is_alive_current_process() {
+        assert_eq!(Some(true), is_alive(std::process::id()));
+        assert_eq!(Some(false), is_alive(u32::MAX));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn extract_listen_inode_tcp6_success() {
+        let input = "   0: 00000000000000000000000000000000:0050 00000000000000000000000000000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 23456 1 0000000000000000 100 0 0 10 0";
+        assert_eq!(Some(23456), extract_listen_inode(input, 80));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn extract_listen_inode_port_not_match() {
+        // 1F90 即 8080，不能匹配到 80 端口
+        let input = "   0: 00000000:1F90 00000000:0000 0A 00000000:00000000 00:00000000 00000000  1000        0 12345 1 0000000000000000 100 0 0 10 0";
+        assert_eq!(None, extract_listen_inode(input, 80));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn extract_listen_inode_not_listen() {
+        // 01 表示 ESTABLISHED
+        let input = "   1: 0100007F:0050 0100007F:D2F0 01 00000000:00000000 00:00000000 00000000  1000        0 34567 1 0000000000000000 20 4 30 10 -1";
+        assert_eq!(None, extract_listen_inode(input, 80));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn extract_listen_inode_malformed_line() {
+        assert_eq!(None, extract_listen_inode("", 80));
+        assert_eq!(None, extract_listen_inode("  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode", 80));
+        assert_eq!(None, extract_listen_inode("   0: 00000000:ZZZZ 00000000:0000 0A", 80));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn parse_stat_ticks_success() {
+        // 进程名中包含空格和括号
+        let input = "1234 (java (a) b) S 1 1234 1234 0 -1 4194560 100 0 0 0 250 50 0 0 20 0 30 0 98765 1000000 2000 18446744073709551615";
+        assert_eq!(Some((98765, 300)), parse_stat_ticks(input));
+        assert_eq!(None, parse_stat_ticks("1234 (java) S 1"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn parse_status_rss_success() {
+        let input = "Name:\tjava\nVmPeak:\t  300000 kB\nVmRSS:\t  123456 kB\nThreads:\t30\n";
+        assert_eq!(Some(123456 * 1024), parse_status_rss(input));
+        assert_eq!(None, parse_status_rss("Name:\tjava\n"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn parse_boot_time_success() {
+        let input = "cpu  1 2 3 4\nintr 123\nbtime 1700000000\nprocesses 100\n";
+        assert_eq!(Some(1_700_000_000), parse_boot_time(input));
+        assert_eq!(None, parse_boot_time("cpu  1 2 3 4\n"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn get_start_time_current_process() {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let start_time = get_start_time(std::process::id()).unwrap();
+        assert!(start_time <= now + 1);
+        assert!(start_time + 600 > now);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn get_stats_current_process() {
+        let stats = get_stats(std::process::id()).unwrap();
+        assert!(stats.memory > 0);
+    }
+
+    /// 启动一个收到 `SIGTERM` 后执行 `on_term` 的进程
+    #[cfg(target_os = "linux")]
+    fn spawn_trap_term(on_term: &str) -> std::process::Child {
+        let script = format!("trap '{}' TERM; while true; do sleep 0.1; done", on_term);
+        let child = std::process::Command::new("sh").args(["-c", &script]).spawn().unwrap();
+        // 等待 trap 生效
+        std::thread::sleep(std::time::Duration::from_millis(300));
+        child
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn terminate_exit_after_sigterm() -> Result<(), Box<dyn std::error::Error>> {
+        use std::time::{Duration, Instant};
+
+        let mut child = spawn_trap_term("exit 0");
+        let started = Instant::now();
+        terminate_with(child.id(), Duration::from_secs(10), || matches!(child.try_wait(), Ok(None)))?;
+
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(child.wait()?.success());
+        Ok(())
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn terminate_send_sigterm_before_kill() -> Result<(), Box<dyn std::error::Error>> {
+        use std::fs;
+        use std::os::unix::process::ExitStatusExt;
+        use std::time::Duration;
+
+        let marker = "process_terminate_send_sigterm_before_kill.txt";
+        let _ = fs::remove_file(marker);
+        // 收到 SIGTERM 后只记录下来，不退出，超时后被强制关闭
+        let mut child = spawn_trap_term(&format!("echo TERM > {}", marker));
+        terminate_with(child.id(), Duration::from_secs(1), || matches!(child.try_wait(), Ok(None)))?;
+
+        let status = child.wait()?;
+        let received = fs::read_to_string(marker);
+        let _ = fs::remove_file(marker);
+        assert_eq!("TERM", received?.trim());
+        assert_eq!(Some(9), status.signal());
+        Ok(())
+    }
 }
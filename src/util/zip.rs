@@ -1,24 +1,172 @@
-use std::path::Path;
+use std::path::{Component, Path, PathBuf};
 use std::fs::{self, File};
-use std::io::{self, BufReader};
+use std::io::{self, BufReader, Read};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use flate2::read::GzDecoder;
+use tar::Archive;
+use xz2::read::XzDecoder;
 use zip::ZipArchive;
 
+use crate::config::{MAX_EXTRACT_SIZE, MAX_EXTRACT_ENTRIES};
+use crate::error::{ExtractError, InstallerError};
+use crate::util::process;
+
+/// 解压时使用的临时文件夹名中的分隔符，其后为创建临时文件夹的进程 id 和序号，如 `11.0.1_extracting_1234_0`
+const TEMP_DIR_INFIX: &str = "_extracting_";
+/// 临时文件夹的序号，同一进程中的多个线程同时解压时，临时文件夹名也不会重复
+static TEMP_DIR_SEQ: AtomicUsize = AtomicUsize::new(0);
+
+/// unix 文件类型的掩码，及符号链接的文件类型
+const S_IFMT: u32 = 0o170000;
+const S_IFLNK: u32 = 0o120000;
+
+/// 压缩文件的格式
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ArchiveFormat {
+    Zip,
+    TarGz,
+    TarXz,
+}
+
+/// 判断压缩文件的格式。
+/// 
+/// 先根据文件扩展名判断，支持 `.zip`、`.tar.gz`、`.tgz`、`.tar.xz` 和 `.txz`；
+/// 如果扩展名无法识别，则根据文件头判断。如果都无法识别，则返回 `None`。
+pub fn detect_format(file_path: &Path) -> io::Result<Option<ArchiveFormat>> {
+    let file_name = file_path.file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    if file_name.ends_with(".zip") {
+        return Ok(Some(ArchiveFormat::Zip));
+    }
+    if file_name.ends_with(".tar.gz") || file_name.ends_with(".tgz") {
+        return Ok(Some(ArchiveFormat::TarGz));
+    }
+    if file_name.ends_with(".tar.xz") || file_name.ends_with(".txz") {
+        return Ok(Some(ArchiveFormat::TarXz));
+    }
+
+    let mut magic = [0_u8; 6];
+    let mut file = File::open(file_path)?;
+    let mut len = 0;
+    while len < magic.len() {
+        match file.read(&mut magic[len..])? {
+            0 => break,
+            n => len += n,
+        }
+    }
+    let magic = &magic[..len];
+
+    if magic.starts_with(b"PK\x03\x04") {
+        Ok(Some(ArchiveFormat::Zip))
+    } else if magic.starts_with(&[0x1f, 0x8b]) {
+        Ok(Some(ArchiveFormat::TarGz))
+    } else if magic.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+        Ok(Some(ArchiveFormat::TarXz))
+    } else {
+        Ok(None)
+    }
+}
+
 /// 将 `source_file_path` 的压缩文件解压到 `target_dir_path` 目录下。
 /// 
+/// 支持 zip、tar.gz 和 tar.xz 格式，详见 `detect_format`。解压时会保留文件的权限。
+/// 
+/// 因为压缩文件是从远程下载的，所以如果压缩文件中的文件或链接指向目标文件夹之外，
+/// 或者解压出的总字节数或文件数超过 `MAX_EXTRACT_SIZE` 和 `MAX_EXTRACT_ENTRIES`，则返回错误。
+/// 
 /// # Examples
 /// 
 /// ```no_run
 /// use installer::util::zip::unzip_to;
 /// 
-/// fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// fn main() -> Result<(), installer::error::InstallerError> {
 ///     unzip_to("test.zip", "another/folder")?;
 ///     Ok(())
 /// }
 /// ```
-pub fn unzip_to(source_file_path: &str, target_dir_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+pub fn unzip_to(source_file_path: &str, target_dir_path: &str) -> Result<(), InstallerError> {
+    extract_to(source_file_path, target_dir_path, ExtractLimits::default())
+}
+
+/// 将 `source_file_path` 的压缩文件解压到 `target_dir_path` 目录下，解压失败时不会留下解压了一部分的文件。
+/// 
+/// 先解压到同级的临时文件夹 `<target_dir_path>_extracting_<pid>_<序号>` 中，解压成功后再重命名为 `target_dir_path`。
+/// 如果 `target_dir_path` 已存在，则会先删除。
+/// 
+/// 如果解压过程中 installer 异常退出，留下的临时文件夹可通过 `remove_stale_temp_dirs` 清理。
+pub fn unzip_to_atomically(source_file_path: &str, target_dir_path: &str) -> Result<(), InstallerError> {
+    let target_path = Path::new(target_dir_path);
+    let temp_path = get_temp_dir_path(target_path, std::process::id(), TEMP_DIR_SEQ.fetch_add(1, Ordering::SeqCst));
+    if temp_path.exists() {
+        fs::remove_dir_all(&temp_path)?;
+    }
+
+    if let Err(e) = extract_to(source_file_path, &temp_path.to_string_lossy(), ExtractLimits::default()) {
+        if temp_path.exists() {
+            fs::remove_dir_all(&temp_path)?;
+        }
+        return Err(e);
+    }
+
+    if target_path.exists() {
+        fs::remove_dir_all(target_path)?;
+    }
+    fs::rename(&temp_path, target_path)?;
+
+    Ok(())
+}
+
+/// 删除 `dir_path` 文件夹中由 `unzip_to_atomically` 创建的，且创建它的进程已退出的临时文件夹。
+/// 
+/// 如果创建临时文件夹的进程还在运行，说明可能正在解压，则不删除；无法确定进程是否还在运行时也不删除。
+/// 返回删除的临时文件夹。
+pub fn remove_stale_temp_dirs(dir_path: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut removed = Vec::new();
+    if !dir_path.is_dir() {
+        return Ok(removed);
+    }
+
+    for entry in fs::read_dir(dir_path)? {
+        let path = entry?.path();
+        if !path.is_dir() {
+            continue;
+        }
+
+        let pid = path.file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.rsplit_once(TEMP_DIR_INFIX))
+            .and_then(|(_, suffix)| suffix.split('_').next())
+            .and_then(|pid| pid.parse::<u32>().ok());
+        let is_stale = match pid {
+            Some(pid) => pid != std::process::id() && process::is_alive(pid) == Some(false),
+            None => false,
+        };
+
+        if is_stale {
+            fs::remove_dir_all(&path)?;
+            removed.push(path);
+        }
+    }
+
+    Ok(removed)
+}
+
+fn get_temp_dir_path(target_path: &Path, pid: u32, seq: usize) -> PathBuf {
+    let mut file_name = target_path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(format!("{}{}_{}", TEMP_DIR_INFIX, pid, seq));
+    target_path.with_file_name(file_name)
+}
+
+fn extract_to(source_file_path: &str,
+    target_dir_path: &str,
+    limits: ExtractLimits) -> Result<(), InstallerError> {
+
     let source_path = Path::new(source_file_path);
 
-    let file_name = source_path.file_name().unwrap().to_str().unwrap();
+    let file_name = source_path.file_name()
+        .ok_or_else(|| InstallerError::Extraction(format!("{} 不是有效的压缩文件", source_file_path)))?;
     let target_path = Path::new(target_dir_path).join(file_name);
 
     let is_in_same_dir = source_path == target_path;
@@ -31,7 +179,18 @@ pub fn unzip_to(source_file_path: &str, target_dir_path: &str) -> Result<(), Box
     }
 
     // 解压文件
-    unzip_file(target_path.to_str().unwrap())?;
+    let result = match detect_format(&target_path)? {
+        Some(ArchiveFormat::Zip) => unzip_file(&target_path, limits).map_err(InstallerError::from),
+        Some(ArchiveFormat::TarGz) => untar_file(&target_path, GzDecoder::new, limits).map_err(InstallerError::from),
+        Some(ArchiveFormat::TarXz) => untar_file(&target_path, XzDecoder::new, limits).map_err(InstallerError::from),
+        None => Err(InstallerError::Extraction(format!("不支持 {} 的压缩格式", source_file_path))),
+    };
+    if let Err(e) = result {
+        if !is_in_same_dir {
+            fs::remove_file(target_path)?;
+        }
+        return Err(e);
+    }
 
     // 删除目标文件夹中的压缩文件
     if !is_in_same_dir {
@@ -41,31 +200,130 @@ pub fn unzip_to(source_file_path: &str, target_dir_path: &str) -> Result<(), Box
     Ok(())
 }
 
+/// 解压文件时的限制，防止解压恶意构造的压缩文件(如 zip 炸弹)时耗尽磁盘空间
+#[derive(Debug, Clone, Copy)]
+struct ExtractLimits {
+    /// 允许解压出的最大总字节数
+    max_size: u64,
+    /// 允许的最大文件数，包括文件夹和链接
+    max_entries: usize,
+}
+
+impl Default for ExtractLimits {
+    fn default() -> Self {
+        ExtractLimits {
+            max_size: MAX_EXTRACT_SIZE,
+            max_entries: MAX_EXTRACT_ENTRIES,
+        }
+    }
+}
+
+/// 将压缩文件中的路径 `entry_name` 规范化为相对于目标文件夹的路径。
+/// 
+/// 如果是绝对路径，或者通过 `..` 跳出了目标文件夹，则返回 `None`。
+fn normalize_entry_path(entry_name: &Path) -> Option<PathBuf> {
+    let mut result = PathBuf::new();
+    for component in entry_name.components() {
+        match component {
+            Component::Normal(name) => result.push(name),
+            Component::CurDir => {},
+            Component::ParentDir => {
+                if !result.pop() {
+                    return None;
+                }
+            },
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(result)
+}
+
+/// 检查符号链接 `entry_path` 指向的 `link_target` 是否在目标文件夹中。
+/// 
+/// `link_target` 是相对于链接所在文件夹的路径，JDK 中的 `legal` 等文件夹就使用了这种链接。
+fn check_symlink(entry_path: &Path, link_target: &Path) -> Result<(), ExtractError> {
+    let link_dir = entry_path.parent().unwrap_or_else(|| Path::new(""));
+    if link_target.is_absolute() || normalize_entry_path(&link_dir.join(link_target)).is_none() {
+        return Err(ExtractError::UnsafeLink(entry_path.display().to_string()));
+    }
+    Ok(())
+}
+
+/// 检查 `out_path` 所在的文件夹确实在目标文件夹 `parent_dir` 中，
+/// 防止通过之前解压出的多个符号链接组合后跳出目标文件夹。
+fn check_out_dir(parent_dir: &Path, out_path: &Path) -> Result<(), ExtractError> {
+    let out_dir = out_path.parent().unwrap_or(parent_dir);
+    let canonical_parent_dir = fs::canonicalize(parent_dir)?;
+    if !fs::canonicalize(out_dir)?.starts_with(canonical_parent_dir) {
+        return Err(ExtractError::UnsafePath(out_path.display().to_string()));
+    }
+    Ok(())
+}
+
+/// 获取被压缩文件所在的文件夹
+fn get_parent_dir(source_file_path: &Path) -> &Path {
+    match source_file_path.parent() {
+        Some(parent) if parent != Path::new("") => parent,
+        _ => Path::new("."),
+    }
+}
+
 /// 将压缩文件解压到当前目录，即存放压缩文件的目录中。
 /// 
 /// 注意：解压完成后，并不会删除之前的压缩文件 `source_file_path`
-fn unzip_file(source_file_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+fn unzip_file(source_file_path: &Path, limits: ExtractLimits) -> Result<(), ExtractError> {
     let source_file = File::open(source_file_path)?;
     let source_reader = BufReader::new(source_file);
     let mut archive = ZipArchive::new(source_reader)?;
 
-    // 获取被压缩文件所在的文件夹
-    let parent_dir = Path::new(source_file_path).parent().unwrap();
+    if archive.len() > limits.max_entries {
+        return Err(ExtractError::TooManyEntries(limits.max_entries));
+    }
+
+    let parent_dir = get_parent_dir(source_file_path);
+    let mut total_size: u64 = 0;
 
     for i in 0..archive.len() {
         let mut file = archive.by_index(i)?;
-        let out_path = parent_dir.join(&file.sanitized_name());
+        // 不使用 `sanitized_name()`，因为它会悄悄去掉 `..`，而这里需要拒绝此类文件
+        let entry_name = file.name().to_string();
+        let entry_path = normalize_entry_path(Path::new(&entry_name))
+            .ok_or_else(|| ExtractError::UnsafePath(entry_name.clone()))?;
+        let out_path = parent_dir.join(&entry_path);
 
-        if (&*file.name()).ends_with('/') {
+        if entry_name.ends_with('/') {
             fs::create_dir_all(&out_path)?;
+            check_out_dir(parent_dir, &out_path)?;
         } else {
             if let Some(p) = out_path.parent() {
                 if !p.exists() {
                     fs::create_dir_all(&p)?;
                 }
             }
+            check_out_dir(parent_dir, &out_path)?;
+
+            let is_symlink = file.unix_mode().is_some_and(|mode| mode & S_IFMT == S_IFLNK);
+            // 不信任压缩文件中记录的大小，按实际解压出的字节数计算
+            let mut content = (&mut file).take(limits.max_size.saturating_sub(total_size) + 1);
+
+            if is_symlink {
+                let mut link_target = String::new();
+                content.read_to_string(&mut link_target)?;
+                check_symlink(&entry_path, Path::new(&link_target))?;
+                total_size += link_target.len() as u64;
+                if total_size > limits.max_size {
+                    return Err(ExtractError::TooLarge(limits.max_size));
+                }
+                create_symlink(Path::new(&link_target), &out_path)?;
+                // 符号链接的权限没有意义，不需要设置
+                continue;
+            }
+
             let mut out_file = fs::File::create(&out_path)?;
-            io::copy(&mut file, &mut out_file)?;
+            total_size += io::copy(&mut content, &mut out_file)?;
+            if total_size > limits.max_size {
+                return Err(ExtractError::TooLarge(limits.max_size));
+            }
         }
 
         // Get and Set permissions
@@ -82,6 +340,67 @@ fn unzip_file(source_file_path: &str) -> Result<(), Box<dyn std::error::Error>>
     Ok(())
 }
 
+#[cfg(unix)]
+fn create_symlink(link_target: &Path, out_path: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(link_target, out_path)
+}
+
+/// Windows 下创建符号链接需要管理员权限，所以不创建链接，与之前的行为保持一致
+#[cfg(not(unix))]
+fn create_symlink(_link_target: &Path, _out_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// 将 tar 压缩文件解压到当前目录，即存放压缩文件的目录中。
+/// 
+/// `decoder` 用于解压 gzip 或 xz 格式的压缩流。
+/// 
+/// 注意：解压完成后，并不会删除之前的压缩文件 `source_file_path`
+fn untar_file<D, F>(source_file_path: &Path, decoder: F, limits: ExtractLimits) -> Result<(), ExtractError>
+    where D: Read, F: FnOnce(BufReader<File>) -> D {
+    let source_file = File::open(source_file_path)?;
+    let mut archive = Archive::new(decoder(BufReader::new(source_file)));
+    archive.set_preserve_permissions(true);
+
+    let parent_dir = get_parent_dir(source_file_path);
+    let mut total_size: u64 = 0;
+
+    for (index, entry) in archive.entries()?.enumerate() {
+        if index >= limits.max_entries {
+            return Err(ExtractError::TooManyEntries(limits.max_entries));
+        }
+
+        let mut entry = entry?;
+        let entry_name = entry.path()?.into_owned();
+        let entry_path = normalize_entry_path(&entry_name)
+            .ok_or_else(|| ExtractError::UnsafePath(entry_name.display().to_string()))?;
+
+        let entry_type = entry.header().entry_type();
+        if entry_type.is_symlink() || entry_type.is_hard_link() {
+            let link_target = entry.link_name()?
+                .ok_or_else(|| ExtractError::UnsafeLink(entry_name.display().to_string()))?
+                .into_owned();
+            if entry_type.is_symlink() {
+                check_symlink(&entry_path, &link_target)?;
+            } else if normalize_entry_path(&link_target).is_none() {
+                // 硬链接的路径是相对于压缩文件的根目录
+                return Err(ExtractError::UnsafeLink(entry_name.display().to_string()));
+            }
+        }
+
+        total_size += entry.header().size()?;
+        if total_size > limits.max_size {
+            return Err(ExtractError::TooLarge(limits.max_size));
+        }
+
+        // `unpack_in` 也会检查文件是否在目标文件夹中，如果不在则跳过并返回 `false`
+        if !entry.unpack_in(parent_dir)? {
+            return Err(ExtractError::UnsafePath(entry_name.display().to_string()));
+        }
+    }
+
+    Ok(())
+}
 #[cfg(test)]
 mod tests {
 
@@ -91,7 +410,13 @@ mod tests {
     use zip::result::{ZipResult};
     use zip::write::{ZipWriter, FileOptions};
     use zip::CompressionMethod::Stored;
-    use super::unzip_to;
+    use flate2::Compression;
+    use flate2::read::GzDecoder;
+    use flate2::write::GzEncoder;
+    use xz2::write::XzEncoder;
+    use crate::error::ExtractError;
+    use super::{unzip_to, detect_format, ArchiveFormat, unzip_file, untar_file, ExtractLimits};
+    use super::{unzip_to_atomically, remove_stale_temp_dirs, get_temp_dir_path, TEMP_DIR_INFIX};
 
     const TEMP_FILE_NAME: &str = "hello_world.txt";
 
@@ -125,6 +450,369 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn detect_format_by_extension() -> Result<(), Box<dyn std::error::Error>> {
+        assert_eq!(Some(ArchiveFormat::Zip), detect_format(Path::new("jdk-11.0.1.zip"))?);
+        assert_eq!(Some(ArchiveFormat::TarGz), detect_format(Path::new("jdk-11.0.1.tar.gz"))?);
+        assert_eq!(Some(ArchiveFormat::TarGz), detect_format(Path::new("jdk-11.0.1.TGZ"))?);
+        assert_eq!(Some(ArchiveFormat::TarXz), detect_format(Path::new("jdk-11.0.1.tar.xz"))?);
+        Ok(())
+    }
+
+    #[test]
+    fn detect_format_by_magic() -> Result<(), Box<dyn std::error::Error>> {
+        let file_name = "zip_detect_format_by_magic";
+        File::create(file_name)?.write_all(&[0x1f, 0x8b, 0x08])?;
+        assert_eq!(Some(ArchiveFormat::TarGz), detect_format(Path::new(file_name))?);
+
+        File::create(file_name)?.write_all(b"PK\x03\x04")?;
+        assert_eq!(Some(ArchiveFormat::Zip), detect_format(Path::new(file_name))?);
+
+        File::create(file_name)?.write_all(&[0xfd, b'7', b'z', b'X', b'Z', 0x00, 0x00])?;
+        assert_eq!(Some(ArchiveFormat::TarXz), detect_format(Path::new(file_name))?);
+
+        File::create(file_name)?.write_all(b"Hello")?;
+        assert_eq!(None, detect_format(Path::new(file_name))?);
+
+        fs::remove_file(file_name)?;
+        Ok(())
+    }
+
+    #[test]
+    fn unzip_to_tar_gz_success() -> Result<(), Box<dyn std::error::Error>> {
+        let file_name = "zip_unzip_to_tar_gz_success.tar.gz";
+        let tar = generate_tar(GzEncoder::new(File::create(file_name)?, Compression::default()))?;
+        tar.finish()?;
+
+        assert_untar_success(file_name, "zip_unzip_to_tar_gz_success")
+    }
+
+    #[test]
+    fn unzip_to_tar_xz_success() -> Result<(), Box<dyn std::error::Error>> {
+        let file_name = "zip_unzip_to_tar_xz_success.tar.xz";
+        let tar = generate_tar(XzEncoder::new(File::create(file_name)?, 6))?;
+        tar.finish()?;
+
+        assert_untar_success(file_name, "zip_unzip_to_tar_xz_success")
+    }
+
+    #[test]
+    fn unzip_to_not_supported() -> Result<(), Box<dyn std::error::Error>> {
+        let file_name = "zip_unzip_to_not_supported.txt";
+        File::create(file_name)?.write_all(b"Hello, World!")?;
+        let target_dir = "zip_unzip_to_not_supported";
+
+        assert!(unzip_to(file_name, target_dir).is_err());
+        // 解压失败后，删除复制到目标文件夹中的压缩文件
+        assert!(!Path::new(target_dir).join(file_name).exists());
+
+        fs::remove_file(file_name)?;
+        fs::remove_dir_all(target_dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn unzip_file_reject_parent_dir() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = "zip_unzip_file_reject_parent_dir";
+        fs::create_dir_all(dir)?;
+        let zip_path = Path::new(dir).join("test.zip");
+        generate_zip_file_with_entries(&zip_path, &[("../evil.txt", b"evil")])?;
+
+        let result = unzip_file(&zip_path, ExtractLimits::default());
+        assert!(matches!(result, Err(ExtractError::UnsafePath(_))));
+        assert!(!Path::new("evil.txt").exists());
+
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn unzip_file_reject_absolute_path() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = "zip_unzip_file_reject_absolute_path";
+        fs::create_dir_all(dir)?;
+        let zip_path = Path::new(dir).join("test.zip");
+        generate_zip_file_with_entries(&zip_path, &[("/tmp/zip_evil.txt", b"evil")])?;
+
+        let result = unzip_file(&zip_path, ExtractLimits::default());
+        assert!(matches!(result, Err(ExtractError::UnsafePath(_))));
+
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn unzip_file_too_large() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = "zip_unzip_file_too_large";
+        fs::create_dir_all(dir)?;
+        let zip_path = Path::new(dir).join("test.zip");
+        generate_zip_file_with_entries(&zip_path, &[("a.txt", b"12345"), ("b.txt", b"67890")])?;
+
+        let limits = ExtractLimits { max_size: 8, max_entries: 10 };
+        assert!(matches!(unzip_file(&zip_path, limits), Err(ExtractError::TooLarge(8))));
+
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn unzip_file_symlink_too_large() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = "zip_unzip_file_symlink_too_large";
+        fs::create_dir_all(dir)?;
+        let zip_path = Path::new(dir).join("test.zip");
+        generate_zip_file_with_entries(&zip_path, &[("a.txt", b"1234567890"), ("link", b"a.txt")])?;
+        mark_as_symlink(&zip_path, "link")?;
+
+        // 符号链接指向的路径也计入解压出的字节数
+        let limits = ExtractLimits { max_size: 12, max_entries: 10 };
+        assert!(matches!(unzip_file(&zip_path, limits), Err(ExtractError::TooLarge(12))));
+        assert!(!Path::new(dir).join("link").exists());
+
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn unzip_file_too_many_entries() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = "zip_unzip_file_too_many_entries";
+        fs::create_dir_all(dir)?;
+        let zip_path = Path::new(dir).join("test.zip");
+        generate_zip_file_with_entries(&zip_path, &[("a.txt", b"1"), ("b.txt", b"2"), ("c.txt", b"3")])?;
+
+        let limits = ExtractLimits { max_size: 1024, max_entries: 2 };
+        assert!(matches!(unzip_file(&zip_path, limits), Err(ExtractError::TooManyEntries(2))));
+        // 在解压任何文件之前就返回错误
+        assert!(!Path::new(dir).join("a.txt").exists());
+
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn untar_file_reject_parent_dir() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = "zip_untar_file_reject_parent_dir";
+        let tar_path = generate_tar_file_with_link(dir, "jdk/../../evil.txt", tar::EntryType::Regular, "")?;
+
+        let result = untar_file(&tar_path, GzDecoder::new, ExtractLimits::default());
+        assert!(matches!(result, Err(ExtractError::UnsafePath(_))));
+        assert!(!Path::new("evil.txt").exists());
+
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn untar_file_reject_symlink_escape() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = "zip_untar_file_reject_symlink_escape";
+        let tar_path = generate_tar_file_with_link(dir, "jdk/legal", tar::EntryType::Symlink, "../../..")?;
+
+        let result = untar_file(&tar_path, GzDecoder::new, ExtractLimits::default());
+        assert!(matches!(result, Err(ExtractError::UnsafeLink(_))));
+
+        fs::remove_dir_all(dir)?;
+
+        let tar_path = generate_tar_file_with_link(dir, "jdk/passwd", tar::EntryType::Symlink, "/etc/passwd")?;
+        let result = untar_file(&tar_path, GzDecoder::new, ExtractLimits::default());
+        assert!(matches!(result, Err(ExtractError::UnsafeLink(_))));
+
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn untar_file_allow_symlink_inside() -> Result<(), Box<dyn std::error::Error>> {
+        // JDK 中的 `legal` 文件夹中包含指向 `java.base` 的符号链接
+        let dir = "zip_untar_file_allow_symlink_inside";
+        let tar_path = generate_tar_file_with_link(dir, "jdk/lib/java", tar::EntryType::Symlink, "../bin/java")?;
+
+        untar_file(&tar_path, GzDecoder::new, ExtractLimits::default())?;
+        assert_eq!("#!/bin/sh", fs::read_to_string(Path::new(dir).join("jdk").join("lib").join("java"))?);
+
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn untar_file_too_large() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = "zip_untar_file_too_large";
+        let tar_path = generate_tar_file_with_link(dir, "jdk/bin/javac", tar::EntryType::Regular, "")?;
+
+        let limits = ExtractLimits { max_size: 12, max_entries: 10 };
+        assert!(matches!(untar_file(&tar_path, GzDecoder::new, limits), Err(ExtractError::TooLarge(12))));
+
+        let limits = ExtractLimits { max_size: 1024, max_entries: 1 };
+        assert!(matches!(untar_file(&tar_path, GzDecoder::new, limits), Err(ExtractError::TooManyEntries(1))));
+
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn unzip_to_atomically_success() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = "zip_unzip_to_atomically_success";
+        fs::create_dir_all(dir)?;
+        let zip_path = Path::new(dir).join("test.zip");
+        generate_zip_file_with_entries(&zip_path, &[("jdk/bin/java", b"new")])?;
+        // 目标文件夹中已存在的文件会被删除
+        let target_dir = Path::new(dir).join("11.0.1");
+        fs::create_dir_all(target_dir.join("jdk"))?;
+        File::create(target_dir.join("jdk").join("old"))?;
+
+        unzip_to_atomically(&zip_path.to_string_lossy(), &target_dir.to_string_lossy())?;
+
+        assert_eq!("new", fs::read_to_string(target_dir.join("jdk").join("bin").join("java"))?);
+        assert!(!target_dir.join("jdk").join("old").exists());
+        assert!(!has_temp_dir(Path::new(dir))?);
+
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn unzip_to_atomically_fail() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = "zip_unzip_to_atomically_fail";
+        fs::create_dir_all(dir)?;
+        let zip_path = Path::new(dir).join("test.zip");
+        generate_zip_file_with_entries(&zip_path, &[("jdk/bin/java", b"new"), ("../evil.txt", b"evil")])?;
+        let target_dir = Path::new(dir).join("11.0.1");
+
+        assert!(unzip_to_atomically(&zip_path.to_string_lossy(), &target_dir.to_string_lossy()).is_err());
+
+        // 不会留下解压了一部分的文件
+        assert!(!target_dir.exists());
+        assert!(!has_temp_dir(Path::new(dir))?);
+
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn remove_stale_temp_dirs_success() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = Path::new("zip_remove_stale_temp_dirs_success");
+        let target_dir = dir.join("11.0.1");
+        // 进程已退出
+        let stale_dir = get_temp_dir_path(&target_dir, u32::MAX, 0);
+        // 进程还在运行
+        let running_dir = get_temp_dir_path(&target_dir, std::process::id(), 0);
+        fs::create_dir_all(&target_dir)?;
+        fs::create_dir_all(&stale_dir)?;
+        fs::create_dir_all(&running_dir)?;
+
+        assert_eq!(vec![stale_dir.clone()], remove_stale_temp_dirs(dir)?);
+        assert!(!stale_dir.exists());
+        assert!(running_dir.exists());
+        assert!(target_dir.exists());
+
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    /// 在 `dir` 文件夹中生成一个 tar.gz 文件，其中包含 `jdk/bin/java` 和名为 `name` 的文件或链接。
+    /// 
+    /// 直接修改 tar 文件头，因为 `tar::Builder` 不允许写入包含 `..` 的路径。
+    fn generate_tar_file_with_link(dir: &str,
+        name: &str,
+        entry_type: tar::EntryType,
+        link_name: &str) -> std::io::Result<std::path::PathBuf> {
+
+        fs::create_dir_all(dir)?;
+        let tar_path = Path::new(dir).join("test.tar.gz");
+        let mut builder = tar::Builder::new(GzEncoder::new(File::create(&tar_path)?, Compression::default()));
+
+        let content = b"#!/bin/sh";
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o755);
+        header.set_cksum();
+        builder.append_data(&mut header, "jdk/bin/java", &content[..])?;
+
+        let mut header = tar::Header::new_old();
+        header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+        header.as_old_mut().linkname[..link_name.len()].copy_from_slice(link_name.as_bytes());
+        header.set_entry_type(entry_type);
+        header.set_mode(0o755);
+        if entry_type == tar::EntryType::Regular {
+            header.set_size(content.len() as u64);
+        } else {
+            header.set_size(0);
+        }
+        header.set_cksum();
+        let data: &[u8] = if entry_type == tar::EntryType::Regular { &content[..] } else { &[] };
+        builder.append(&header, data)?;
+
+        builder.into_inner()?.finish()?;
+        Ok(tar_path)
+    }
+
+    /// 生成一个 zip 文件，其中包含 `entries` 中的文件
+    fn generate_zip_file_with_entries(zip_path: &Path, entries: &[(&str, &[u8])]) -> ZipResult<()> {
+        let mut zip = ZipWriter::new(File::create(zip_path)?);
+        let options = FileOptions::default().compression_method(Stored);
+        for (name, content) in entries {
+            zip.start_file(*name, options)?;
+            zip.write_all(content)?;
+        }
+        zip.finish()?;
+        Ok(())
+    }
+
+    /// 判断 `dir` 中是否有解压时使用的临时文件夹
+    fn has_temp_dir(dir: &Path) -> std::io::Result<bool> {
+        for entry in fs::read_dir(dir)? {
+            if entry?.file_name().to_string_lossy().contains(TEMP_DIR_INFIX) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// 将 zip 文件中的 `name` 标记为符号链接，即修改中央目录中的外部属性，因为 `ZipWriter` 不支持写入符号链接
+    fn mark_as_symlink(zip_path: &Path, name: &str) -> std::io::Result<()> {
+        let mut bytes = fs::read(zip_path)?;
+        let mut index = 0;
+        while index + 46 <= bytes.len() {
+            let name_len = u16::from_le_bytes([bytes[index + 28], bytes[index + 29]]) as usize;
+            if bytes[index..index + 4] == b"PK\x01\x02"[..] && bytes.get(index + 46..index + 46 + name_len) == Some(name.as_bytes()) {
+                let mode: u32 = 0o120_777 << 16;
+                bytes[index + 38..index + 42].copy_from_slice(&mode.to_le_bytes());
+            }
+            index += 1;
+        }
+        fs::write(zip_path, bytes)
+    }
+
+    /// 生成一个 tar 文件，其中包含 `jdk/bin/java` 可执行文件
+    fn generate_tar<W: Write>(writer: W) -> std::io::Result<W> {
+        let mut builder = tar::Builder::new(writer);
+        let content = b"#!/bin/sh";
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o755);
+        header.set_cksum();
+        builder.append_data(&mut header, "jdk/bin/java", &content[..])?;
+        builder.into_inner()
+    }
+
+    fn assert_untar_success(file_name: &str, target_dir: &str) -> Result<(), Box<dyn std::error::Error>> {
+        unzip_to(file_name, target_dir)?;
+
+        let java_path = Path::new(target_dir).join("jdk").join("bin").join("java");
+        assert_eq!("#!/bin/sh", fs::read_to_string(&java_path)?);
+        // 删除了复制到目标文件夹中的压缩文件
+        assert!(!Path::new(target_dir).join(file_name).exists());
+
+        // 保留文件的权限
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(0o755, fs::metadata(&java_path)?.permissions().mode() & 0o777);
+        }
+
+        fs::remove_file(file_name)?;
+        fs::remove_dir_all(target_dir)?;
+        Ok(())
+    }
+
     fn generate_zip_file(zip_file_name: &str) -> ZipResult<()> {
         //  1. 生成一个临时文件
         //  2. 将临时文件压缩成 zip
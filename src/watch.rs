@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::thread;
+use std::time::{Duration, Instant};
+use chrono::{Local, NaiveTime};
+
+use crate::command;
+use crate::error::InstallerError;
+use crate::installer_config::InstallerConfig;
+use crate::run_record::PortLock;
+use crate::progressln;
+
+/// 检查是否需要升级的时间间隔
+const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// 维护窗口，只在此时间段内自动升级 APP，使用服务器的本地时间。
+///
+/// 如果 `start` 晚于 `end`，则表示跨越午夜，如 `23:00-01:00`。
+#[derive(Debug, PartialEq)]
+pub struct MaintenanceWindow {
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+impl MaintenanceWindow {
+
+    /// 解析 `HH:MM-HH:MM` 格式的维护窗口，如 `02:00-04:00`
+    pub fn parse(window: &str) -> Result<Self, InstallerError> {
+        let invalid = || InstallerError::Validation(format!("无效的维护窗口 {}，格式为 HH:MM-HH:MM", window));
+
+        let (start, end) = window.split_once('-').ok_or_else(invalid)?;
+        let start = NaiveTime::parse_from_str(start.trim(), "%H:%M").map_err(|_| invalid())?;
+        let end = NaiveTime::parse_from_str(end.trim(), "%H:%M").map_err(|_| invalid())?;
+        if start == end {
+            return Err(invalid());
+        }
+
+        Ok(MaintenanceWindow { start, end })
+    }
+
+    /// 判断 `time` 是否在维护窗口中，包含开始时间，不包含结束时间
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start < self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+/// 解析检查更新的时间间隔，支持 `s`、`m`、`h` 和 `d` 单位，如 `30m`、`6h`、`1d`，没有单位时为秒
+pub fn parse_interval(interval: &str) -> Result<Duration, InstallerError> {
+    let invalid = || InstallerError::Validation(format!("无效的时间间隔 {}，如 30m、6h 或 1d", interval));
+
+    let interval = interval.trim();
+    let (number, unit) = match interval.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => interval.split_at(index),
+        None => (interval, "s"),
+    };
+    let number: u64 = number.parse().map_err(|_| invalid())?;
+    let multiplier = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 60 * 60 * 24,
+        _ => return Err(invalid()),
+    };
+    let seconds = number.checked_mul(multiplier).ok_or_else(invalid)?;
+    if seconds == 0 {
+        return Err(invalid());
+    }
+
+    Ok(Duration::from_secs(seconds))
+}
+
+/// 按 `installer_config.toml` 中配置的 `auto_update` 定时检查并升级 APP，按 CTRL + C 退出。
+///
+/// 每个 installer 每隔 `interval` 调用一次 `update_app`，只升级有新版本的 APP；
+/// 如果配置了维护窗口 `window`，则到期后等到进入维护窗口时再检查。
+/// 每次检查前都会重新读取 `installer_config.toml`，因此修改配置后不需要重启。
+/// 升级时持有端口上的操作锁，如果守护进程或其他命令正在操作此 APP，则等下一轮再检查。
+pub fn start() -> Result<(), InstallerError> {
+    progressln!("开始定时检查并升级 APP，按 CTRL + C 退出");
+
+    // 每个端口上次检查更新的时间
+    let mut last_checked: HashMap<u32, Instant> = HashMap::new();
+
+    loop {
+        let installer_config = match InstallerConfig::new() {
+            Ok(installer_config) => installer_config,
+            Err(e) => {
+                progressln!("> [ERROR]: 读取 installer 配置信息失败，{}", e);
+                thread::sleep(CHECK_INTERVAL);
+                continue;
+            }
+        };
+        for installer in installer_config.get_data().installers.iter() {
+            let auto_update = match installer_config.get_auto_update(installer) {
+                Some(auto_update) => auto_update,
+                None => continue,
+            };
+
+            let interval = match parse_interval(&auto_update.interval) {
+                Ok(interval) => interval,
+                Err(e) => {
+                    progressln!("> [WARN]: 端口 {} 上的 installer 的自动升级配置有误，{}", installer.app_run_port, e);
+                    continue;
+                }
+            };
+            let window = match auto_update.window.as_deref().map(MaintenanceWindow::parse).transpose() {
+                Ok(window) => window,
+                Err(e) => {
+                    progressln!("> [WARN]: 端口 {} 上的 installer 的自动升级配置有误，{}", installer.app_run_port, e);
+                    continue;
+                }
+            };
+
+            let now = Instant::now();
+            if !is_due(last_checked.get(&installer.app_run_port).copied(), interval, now) {
+                continue;
+            }
+            if let Some(window) = &window {
+                if !window.contains(Local::now().time()) {
+                    continue;
+                }
+            }
+            // 守护进程或其他命令正在停止或启动此 APP 时，等下一轮再检查
+            if PortLock::is_held(installer.app_run_port) {
+                continue;
+            }
+
+            last_checked.insert(installer.app_run_port, now);
+            progressln!();
+            progressln!("===== 开始检查 {} 端口上的项目 {} 是否有新版本 =====", installer.app_run_port, installer.app_name);
+            if let Err(e) = command::update_app(installer, false) {
+                progressln!("> [ERROR]: 自动升级失败，{}，将在 {} 秒后重试", e, interval.as_secs());
+            }
+        }
+
+        thread::sleep(CHECK_INTERVAL);
+    }
+}
+
+/// 判断是否到了检查更新的时间，从没检查过时立即检查
+fn is_due(last_checked: Option<Instant>, interval: Duration, now: Instant) -> bool {
+    match last_checked {
+        Some(last_checked) => now.duration_since(last_checked) >= interval,
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::time::{Duration, Instant};
+    use chrono::NaiveTime;
+    use super::{is_due, parse_interval, MaintenanceWindow};
+
+    #[test]
+    fn parse_interval_success() {
+        assert_eq!(Duration::from_secs(90), parse_interval("90").unwrap());
+        assert_eq!(Duration::from_secs(30 * 60), parse_interval("30m").unwrap());
+        assert_eq!(Duration::from_secs(6 * 60 * 60), parse_interval("6h").unwrap());
+        assert_eq!(Duration::from_secs(24 * 60 * 60), parse_interval(" 1d ").unwrap());
+    }
+
+    #[test]
+    fn parse_interval_invalid() {
+        assert!(parse_interval("").is_err());
+        assert!(parse_interval("0").is_err());
+        assert!(parse_interval("h").is_err());
+        assert!(parse_interval("6w").is_err());
+        assert!(parse_interval("-1h").is_err());
+        assert!(parse_interval("999999999999999d").is_err());
+    }
+
+    #[test]
+    fn maintenance_window_contains() {
+        let window = MaintenanceWindow::parse("02:00-04:00").unwrap();
+        assert!(window.contains(time(2, 0)));
+        assert!(window.contains(time(3, 59)));
+        assert!(!window.contains(time(4, 0)));
+        assert!(!window.contains(time(1, 59)));
+
+        // 跨越午夜
+        let window = MaintenanceWindow::parse("23:00-01:00").unwrap();
+        assert!(window.contains(time(23, 30)));
+        assert!(window.contains(time(0, 30)));
+        assert!(!window.contains(time(1, 0)));
+        assert!(!window.contains(time(12, 0)));
+    }
+
+    #[test]
+    fn maintenance_window_invalid() {
+        assert!(MaintenanceWindow::parse("02:00").is_err());
+        assert!(MaintenanceWindow::parse("02:00-25:00").is_err());
+        assert!(MaintenanceWindow::parse("02:00-02:00").is_err());
+    }
+
+    #[test]
+    fn is_due_success() {
+        let now = Instant::now();
+        let interval = Duration::from_secs(60);
+        assert!(is_due(None, interval, now));
+        assert!(!is_due(Some(now), interval, now + Duration::from_secs(59)));
+        assert!(is_due(Some(now), interval, now + Duration::from_secs(60)));
+    }
+
+    fn time(hour: u32, min: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, min, 0).unwrap()
+    }
+}
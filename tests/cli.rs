@@ -1,5 +1,6 @@
 use std::process::Command;
 use assert_cmd::prelude::*;
+use predicates::prelude::*;
 
 #[test]
 fn command_update_success() -> Result<(), Box<dyn std::error::Error>> {
@@ -10,4 +11,70 @@ fn command_update_success() -> Result<(), Box<dyn std::error::Error>> {
     //     .stdout(predicate::str::contains("更新失败"));
     // TODO: 考虑如何准备好前置条件，以做详细的集成测试。
     Ok(())
-}
\ No newline at end of file
+}
+#[test]
+fn command_register_non_interactive_invalid_url() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("installer")?;
+    cmd.args(["register", "--non-interactive", "--url", "ftp://blocklang.com", "--token", "t0k3n"]);
+    // 校验失败时以 Validation 错误的退出码退出，不会提示重新输入
+    cmd.assert().failure().code(3);
+    Ok(())
+}
+
+#[test]
+fn command_register_non_interactive_token_is_empty() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("installer")?;
+    cmd.env_remove("BLOCKLANG_TOKEN");
+    cmd.args(["register", "--non-interactive", "--url", "https://blocklang.com"]);
+    cmd.assert().failure().code(3);
+    Ok(())
+}
+
+#[test]
+fn command_update_dry_run_not_create_config_file() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::TempDir::new()?;
+    let mut cmd = Command::cargo_bin("installer")?;
+    cmd.current_dir(dir.path());
+    cmd.args(["update", "--all", "--dry-run"]);
+    cmd.assert().success();
+    assert!(!dir.path().join("installer_config.toml").exists());
+    Ok(())
+}
+
+#[test]
+fn command_stop_port_conflicts_with_app() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("installer")?;
+    cmd.args(["stop", "--port", "80", "--app", "@user/project"]);
+    cmd.assert().failure().code(1);
+    Ok(())
+}
+
+#[test]
+fn command_run_jobs_must_be_positive() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cmd = Command::cargo_bin("installer")?;
+    cmd.args(["run", "--all", "--jobs", "0"]);
+    cmd.assert().failure().code(1).stderr(predicate::str::contains("--jobs 必须大于 0"));
+    Ok(())
+}
+
+#[test]
+fn command_stop_port_not_registered() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::TempDir::new()?;
+    let mut cmd = Command::cargo_bin("installer")?;
+    cmd.current_dir(dir.path());
+    cmd.args(["stop", "--port", "65530"]);
+    // 端口上没有注册 installer 时以 NotFound 错误的退出码退出
+    cmd.assert().failure().code(4);
+    Ok(())
+}
+
+#[test]
+fn command_status_no_installer_matched() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempfile::TempDir::new()?;
+    let mut cmd = Command::cargo_bin("installer")?;
+    cmd.current_dir(dir.path());
+    cmd.args(["status", "--tag", "env=not-exist"]);
+    // 没有匹配的 installer 时以 NotFound 错误的退出码退出
+    cmd.assert().failure().code(4);
+    Ok(())
+}